@@ -48,7 +48,7 @@ function App() {
       <Routes>
         <Route path="/" element={<SlidesPage />} />
         <Route path="/slides" element={<SlidesPage />} />
-        <Route path="/users" element={user?.permission === 'Admin' ? <UsersPage /> : <AccessDeniedPage />} />
+        <Route path="/users" element={user?.permissions.includes('users_manage') ? <UsersPage /> : <AccessDeniedPage />} />
       </Routes>
       }
     </div>
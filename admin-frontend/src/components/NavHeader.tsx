@@ -11,7 +11,7 @@ const NavHeader = ({ user }: Props) => {
   return (
     <div className="nav-header">
         <Link to="/slides" className="slides-page-button">Slides</Link>
-        {user?.permission === 'Admin' && <Link to="/users" className="users-page-button">Users</Link>}
+        {user?.permissions.includes('users_manage') && <Link to="/users" className="users-page-button">Users</Link>}
     </div>
   )
 }
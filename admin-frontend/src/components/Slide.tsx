@@ -11,7 +11,8 @@ type Props = {
 const Slide = ({ slide, setSlides }: Props) => {
     const handleRemove = () => {
         fetch(`${import.meta.env.VITE_API_BASE_URL}/screen/slides/${slide.id}`, {
-            method: 'DELETE'
+            method: 'DELETE',
+            credentials: 'include',
         }).then(response => {
             if (response.ok) {
                 console.log('Slide removed successfully');
@@ -22,6 +23,30 @@ const Slide = ({ slide, setSlides }: Props) => {
         });
     }
 
+    const changeStatus = (action: 'submit' | 'review', body?: { approve: boolean, comment?: string }) => {
+        fetch(`${import.meta.env.VITE_API_BASE_URL}/screen/slides/${slide.id}/${action}`, {
+            method: 'POST',
+            credentials: 'include',
+            headers: {
+                'Content-Type': 'application/json',
+            },
+            body: body && JSON.stringify(body),
+        }).then(response => {
+            if (response.ok) {
+                updateSlides(setSlides); // Refresh slides
+            } else {
+                console.log(`Failed to ${action} slide`);
+            }
+        });
+    }
+
+    const handleReject = () => {
+        const comment = prompt('Why is the slide rejected?');
+        if (comment) {
+            changeStatus('review', { approve: false, comment });
+        }
+    }
+
     const truncateCaption = (caption: string) => {
         return caption.length > 30 ? caption.substring(0, 30) + '...' : caption;
     }
@@ -34,6 +59,13 @@ const Slide = ({ slide, setSlides }: Props) => {
             <p>
                 {new Date(slide.start_date).toLocaleString()} &ndash; {new Date(slide.end_date).toLocaleString()}
             </p>
+            <p className={`status ${slide.status}`}>{slide.status}</p>
+            {(slide.status === 'draft' || slide.status === 'rejected') &&
+                <button onClick={() => changeStatus('submit')}>Submit</button>}
+            {slide.status === 'pending' && <>
+                <button onClick={() => changeStatus('review', { approve: true })}>Approve</button>
+                <button onClick={handleReject}>Reject</button>
+            </>}
             <button className='remove-button' onClick={handleRemove}>X</button>
         </div>
     )
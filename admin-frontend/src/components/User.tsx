@@ -1,7 +1,7 @@
 import React from 'react'
 import Popup from 'reactjs-popup';
 import 'reactjs-popup/dist/index.css';
-import { UserData } from '../types'
+import { ROLES, UserData } from '../types'
 import '../styles/User.css'
 import { updateUsers } from '../util/utils';
 
@@ -16,7 +16,7 @@ const User = ({ userData, setUsers }: Props) => {
             return;
         }
 
-        fetch(`${import.meta.env.VITE_API_BASE_URL}/api/auth/remove_user`, {
+        fetch(`${import.meta.env.VITE_API_BASE_URL}/auth/remove_user`, {
             method: 'POST',
             credentials: 'include',
             headers: {
@@ -27,6 +27,8 @@ const User = ({ userData, setUsers }: Props) => {
             if (response.ok) {
                 console.log('User removed successfully');
                 updateUsers(setUsers); // Refresh users
+            } else if (response.status === 409) {
+                alert('The last admin can not be removed');
             } else {
                 console.log('Failed to remove user');
             }
@@ -35,10 +37,32 @@ const User = ({ userData, setUsers }: Props) => {
         });
     }
 
+    const handleRoleChange = (event: React.ChangeEvent<HTMLSelectElement>) => {
+        fetch(`${import.meta.env.VITE_API_BASE_URL}/auth/users/${userData.id}`, {
+            method: 'PATCH',
+            credentials: 'include',
+            headers: {
+                'Content-Type': 'application/json',
+            },
+            body: JSON.stringify({ role: event.target.value }),
+        }).then(response => {
+            if (response.status === 409) {
+                alert('There has to be at least one admin');
+            } else if (!response.ok) {
+                console.log('Failed to change role');
+            }
+            updateUsers(setUsers); // Refresh users
+        }).catch(error => {
+            console.error('Error changing role:', error);
+        });
+    }
+
     return (
         <div className='user'>
             <p>{userData.email}</p>
-            <p>{userData.admin ? 'Admin' : 'Not admin'}</p>
+            <select value={userData.role} onChange={handleRoleChange}>
+                {ROLES.map(role => <option value={role} key={role}>{role}</option>)}
+            </select>
             <button className="remove-user-button" onClick={handleRemove}>Remove User</button>
 
         </div>
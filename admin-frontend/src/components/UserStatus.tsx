@@ -25,7 +25,12 @@ const UserStatus = ({ user, setUser }: Props) => {
     } else if (user === null) {
         return <GoogleLogin
             onSuccess={credentialResponse => {
-                const body: { id_token: string } = { id_token: credentialResponse.credential as string };
+                // Invite links look like /?invite=<token>, see POST /auth/invites
+                const invite = new URLSearchParams(window.location.search).get('invite');
+                const body: { id_token: string, invite?: string } = { id_token: credentialResponse.credential as string };
+                if (invite) {
+                    body.invite = invite;
+                }
                 fetch(`${import.meta.env.VITE_API_BASE_URL}/auth/verify`, {
                     method: 'POST',
                     credentials: 'include',
@@ -35,9 +40,15 @@ const UserStatus = ({ user, setUser }: Props) => {
 
                     body: JSON.stringify(body),
                 }).then(res => {
+                    if (!res.ok) {
+                        throw new Error(`Login failed: ${res.status}`);
+                    }
+                    if (invite) {
+                        window.history.replaceState(null, '', window.location.pathname);
+                    }
                     res.json().then(body => {
                         console.log('Login successful');
-                        setUser({ email: body.email, permission: body.permission });
+                        setUser({ email: body.email, role: body.role, permissions: body.permissions });
                     })
                 }).catch(err => {
                     console.error(err);
@@ -51,7 +62,7 @@ const UserStatus = ({ user, setUser }: Props) => {
     } else {
         return (<>
             <p onClick={logout}>{user.email}</p>
-            {user.permission === "Admin" ? <p>Admin</p> : <></>}
+            <p>{user.role}</p>
         </>)
     }
 }
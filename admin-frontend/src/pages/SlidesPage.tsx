@@ -1,17 +1,19 @@
 import React from 'react';
 import { useEffect, useState } from 'react';
-import { SlideData } from '../types';
+import { GroupData, SlideData } from '../types';
 import Slide from '../components/Slide';
 import '../styles/SlidesPage.css';
 import Popup from 'reactjs-popup';
-import { updateSlides } from '../util/utils';
+import { updateGroups, updateSlides } from '../util/utils';
 
 const SlidesPage = () => {
 
     const [slides, setSlides] = useState<SlideData[]>([]);
+    const [groups, setGroups] = useState<GroupData[]>([]);
 
     useEffect(() => {
         updateSlides(setSlides);
+        updateGroups(setGroups);
     }, []);
 
     const handleAddSlide = (event: React.FormEvent<HTMLFormElement>) => {
@@ -25,6 +27,7 @@ const SlidesPage = () => {
 
         fetch(`${import.meta.env.VITE_API_BASE_URL}/screen/slides/save`, {
             method: 'POST',
+            credentials: 'include',
             body: data
         }).then(response => {
             if (response.ok) {
@@ -54,6 +57,11 @@ const SlidesPage = () => {
                             <input type='checkbox' id='active' name='visible' value='true' /> */}
                             <label htmlFor='file'>Image</label>
                             <input type='file' id='file' name='imageFile' />
+                            <label htmlFor='group'>Group</label>
+                            <select id='group' name='group_id' defaultValue=''>
+                                <option value=''>No group</option>
+                                {groups.map(group => <option value={group.id} key={group.id}>{group.name}</option>)}
+                            </select>
                             <button type='submit'>Submit</button>
                         </form>
                 </Popup>
@@ -2,32 +2,67 @@ import { useEffect, useState } from 'react';
 import Popup from 'reactjs-popup';
 import 'reactjs-popup/dist/index.css';
 import User from '../components/User';
-import { UserData } from '../types';
+import { InviteData, ROLES, UserData } from '../types';
 import '../styles/UsersPage.css';
-import { updateUsers } from '../util/utils';
+import { updateInvites, updateUsers } from '../util/utils';
 
 type Props = {}
 
 const UsersPage = (props: Props) => {
     const [users, setUsers] = useState<UserData[]>([]);
+    const [invites, setInvites] = useState<InviteData[]>([]);
+    // The link for the invite that was just created. The token can't be fetched again.
+    const [inviteLink, setInviteLink] = useState<string | null>(null);
 
     useEffect(() => {
       updateUsers(setUsers);
+      updateInvites(setInvites);
     }, []);
 
+    const handleCreateInvite = (event: React.FormEvent<HTMLFormElement>) => {
+      event.preventDefault();
+      const data = new FormData(event.currentTarget);
+      const invite = { role: data.get('role') as string, valid_days: Number(data.get('valid_days')) };
+
+      fetch(`${import.meta.env.VITE_API_BASE_URL}/auth/invites`, {
+          method: 'POST',
+          credentials: 'include',
+          headers: {
+              'Content-Type': 'application/json',
+          },
+          body: JSON.stringify(invite),
+      }).then(response => {
+        if (!response.ok) {
+            throw new Error('Failed to create invite');
+          }
+          return response.json();
+        }).then(created => {
+          setInviteLink(`${window.location.origin}/?invite=${created.token}`);
+          updateInvites(setInvites);
+        }).catch(err => console.error(err));
+    }
+
+    const revokeInvite = (id: string) => {
+      fetch(`${import.meta.env.VITE_API_BASE_URL}/auth/invites/${id}`, {
+          method: 'DELETE',
+          credentials: 'include',
+      }).then(() => updateInvites(setInvites))
+        .catch(err => console.error(err));
+    }
+
     const handleAddUser = (event: React.FormEvent<HTMLFormElement>) => {
       event.preventDefault();
       const data = new FormData(event.currentTarget);
       console.log(data);
       const email = data.get('email') as string;
-      const admin = data.get('admin') === 'on';
+      const role = data.get('role') as string;
 
       if (users.some(user => user.email === email)) {
         alert('User already exists');
         return;
       }
 
-      const newUser = { email, permission: admin ? 'Admin' : 'User' };
+      const newUser = { email, role };
 
       fetch(`${import.meta.env.VITE_API_BASE_URL}/auth/add_user`, {
           method: 'POST',
@@ -58,13 +93,38 @@ const UsersPage = (props: Props) => {
             <form onSubmit={(event) => handleAddUser(event)}>
               <label>Email:</label>
               <input type="email" name="email" required />
-              <label>Admin:</label>
-              <input type="checkbox" name="admin" />
+              <label>Role:</label>
+              <select name="role" defaultValue="slide_editor">
+                {ROLES.map(role => <option value={role} key={role}>{role}</option>)}
+              </select>
               <button type="submit">Submit</button>
             </form>
         </Popup>
+        <Popup className="add-user-popup" trigger={<button className='add-user-button'>Invite</button>} modal onClose={() => setInviteLink(null)}>
+            <h2>Invite</h2>
+            {inviteLink ? <>
+              <p>Send this link to the person you're inviting. It can only be used once.</p>
+              <input type="text" value={inviteLink} readOnly onFocus={event => event.currentTarget.select()} />
+            </> :
+            <form onSubmit={(event) => handleCreateInvite(event)}>
+              <label>Role:</label>
+              <select name="role" defaultValue="slide_editor">
+                {ROLES.map(role => <option value={role} key={role}>{role}</option>)}
+              </select>
+              <label>Valid for (days):</label>
+              <input type="number" name="valid_days" min={1} max={30} defaultValue={7} required />
+              <button type="submit">Create link</button>
+            </form>}
+        </Popup>
 
       </div>
+      {invites.length > 0 && <div className="pending-invites">
+        <h2>Pending invites</h2>
+        {invites.map(invite => <p key={invite.id}>
+          {invite.role}, by {invite.created_by}, expires {new Date(invite.expires_at + 'Z').toLocaleString()}
+          <button onClick={() => revokeInvite(invite.id)}>Revoke</button>
+        </p>)}
+      </div>}
       {users.map(user => <User userData = {user} setUsers={setUsers} key={user.id} />)}  
     </div>
   )
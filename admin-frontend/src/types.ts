@@ -7,17 +7,41 @@ interface SlideData {
     end_date: Date;
     active: boolean;
     filetype: string;
+    created_by: string | null;
+    group_id: string | null;
+    status: 'draft' | 'pending' | 'approved' | 'rejected';
 }
 
+interface GroupData {
+    id: string;
+    name: string;
+    members: { user_id: string; email: string; role: Role }[];
+}
+
+type Role = 'viewer' | 'slide_editor' | 'slide_publisher' | 'screen_operator' | 'admin';
+
+const ROLES: Role[] = ['viewer', 'slide_editor', 'slide_publisher', 'screen_operator', 'admin'];
+
 interface UserData {
     id: string;
     email: string;
-    admin: boolean;
+    role: Role;
+}
+
+interface InviteData {
+    id: string;
+    role: Role;
+    created_by: string;
+    created_at: string;
+    expires_at: string;
 }
 
 interface User {
     email: string;
-    permission: 'Admin' | 'User';
+    role: Role;
+    // See "Roles and permissions" in endpoints.md
+    permissions: string[];
 }
 
-export type { SlideData, UserData, User };
+export { ROLES };
+export type { SlideData, UserData, User, Role, GroupData, InviteData };
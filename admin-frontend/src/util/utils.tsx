@@ -1,7 +1,7 @@
-import { UserData, SlideData } from "../types";
+import { UserData, SlideData, GroupData, InviteData } from "../types";
 
 export const updateSlides = (setSlides: (slides: SlideData[]) => void) => {
-    fetch(`${import.meta.env.VITE_API_BASE_URL}/screen/slides`, {
+    fetch(`${import.meta.env.VITE_API_BASE_URL}/screen/slides/all`, {
         method: 'GET',
         credentials: 'include'
     })
@@ -20,4 +20,24 @@ export const updateUsers = (setUsers: (users: UserData[]) => void) => {
         .then(json => setUsers(json))
         .catch(error => console.error('Error fetching users:', error));
     // setUsers([{ id: 'dummy-id', email: 'user1@example.com', admin: true }, { id: 'dummy-id2', email: 'user2@example.com', admin: false }, { id: 'dummy-id3', email: 'user3@example.com', admin: true }]);
-}
\ No newline at end of file
+}
+
+export const updateInvites = (setInvites: (invites: InviteData[]) => void) => {
+    fetch(`${import.meta.env.VITE_API_BASE_URL}/auth/invites`, {
+        method: 'GET',
+        credentials: 'include'
+    })
+        .then(response => response.json())
+        .then(json => setInvites(json))
+        .catch(error => console.error('Error fetching invites:', error));
+}
+
+export const updateGroups = (setGroups: (groups: GroupData[]) => void) => {
+    fetch(`${import.meta.env.VITE_API_BASE_URL}/groups`, {
+        method: 'GET',
+        credentials: 'include'
+    })
+        .then(response => response.json())
+        .then(json => setGroups(json))
+        .catch(error => console.error('Error fetching groups:', error));
+}
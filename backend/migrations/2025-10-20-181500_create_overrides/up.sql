@@ -0,0 +1,13 @@
+-- Emergency messages that preempt all other content on the screens.
+-- Rows are never deleted, so the table doubles as a log of every activation.
+CREATE TABLE overrides (
+    id TEXT PRIMARY KEY NOT NULL,
+    message TEXT,
+    slide_id TEXT,
+    created_by TEXT NOT NULL,
+    created_at DATETIME NOT NULL,
+    expires_at DATETIME NOT NULL,
+    cleared_by TEXT,
+    cleared_at DATETIME,
+    CHECK (message IS NOT NULL OR slide_id IS NOT NULL)
+)
@@ -0,0 +1,3 @@
+DROP TABLE slide_screens;
+DROP TABLE screen_sl_sites;
+DROP TABLE screens;
@@ -0,0 +1,24 @@
+-- The settings row holds the defaults for every screen, so it has to exist
+INSERT OR IGNORE INTO settings (id) VALUES (1);
+
+CREATE TABLE screens (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL UNIQUE,
+    -- NULL means the screen uses the value from `settings`
+    layout_type TEXT,
+    color_mode TEXT
+);
+
+-- SL sites whose departures are shown on a screen
+CREATE TABLE screen_sl_sites (
+    screen_id TEXT NOT NULL REFERENCES screens(id) ON DELETE CASCADE,
+    site_id INTEGER NOT NULL,
+    PRIMARY KEY (screen_id, site_id)
+);
+
+-- Slides without any rows here are shown on every screen
+CREATE TABLE slide_screens (
+    slide_id TEXT NOT NULL REFERENCES slides(id) ON DELETE CASCADE,
+    screen_id TEXT NOT NULL REFERENCES screens(id) ON DELETE CASCADE,
+    PRIMARY KEY (slide_id, screen_id)
+);
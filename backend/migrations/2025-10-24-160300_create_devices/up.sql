@@ -0,0 +1,16 @@
+-- Kiosks that show a screen. A device is created when a kiosk asks to be paired,
+-- and is pending until an admin approves its pairing code and assigns it to a screen.
+CREATE TABLE devices (
+    id TEXT PRIMARY KEY NOT NULL,
+    -- SHA-256 of the device token; the token itself is only known by the kiosk
+    token_hash TEXT NOT NULL UNIQUE,
+    -- Shown on the kiosk while pending. NULL once paired.
+    pairing_code TEXT UNIQUE,
+    pairing_expires_at DATETIME,
+    name TEXT,
+    screen_id TEXT REFERENCES screens(id),
+    approved_by TEXT,
+    created_at DATETIME NOT NULL,
+    approved_at DATETIME,
+    revoked_at DATETIME
+)
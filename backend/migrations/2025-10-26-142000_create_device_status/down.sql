@@ -0,0 +1 @@
+DROP TABLE device_status
\ No newline at end of file
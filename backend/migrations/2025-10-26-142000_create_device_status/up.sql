@@ -0,0 +1,10 @@
+-- The latest heartbeat from each device
+CREATE TABLE device_status (
+    device_id TEXT PRIMARY KEY NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    last_seen_at DATETIME NOT NULL,
+    current_slide_id TEXT,
+    manifest_version TEXT,
+    uptime_seconds BIGINT NOT NULL,
+    -- JSON array of error messages the screen reported since its previous heartbeat
+    client_errors TEXT NOT NULL DEFAULT '[]'
+)
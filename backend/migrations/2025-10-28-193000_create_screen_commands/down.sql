@@ -0,0 +1 @@
+DROP TABLE screen_commands
\ No newline at end of file
@@ -0,0 +1,16 @@
+-- Commands queued by admins for the devices showing a screen
+CREATE TABLE screen_commands (
+    id TEXT PRIMARY KEY NOT NULL,
+    screen_id TEXT NOT NULL REFERENCES screens(id) ON DELETE CASCADE,
+    -- reload | jump_to_slide | identify | clear_cache
+    command TEXT NOT NULL,
+    slide_id TEXT,
+    issued_by TEXT NOT NULL,
+    issued_at DATETIME NOT NULL,
+    -- A command that hasn't been picked up by then is dropped
+    expires_at DATETIME NOT NULL,
+    acknowledged_at DATETIME,
+    acknowledged_by TEXT REFERENCES devices(id),
+    success BOOLEAN,
+    result_message TEXT
+)
@@ -0,0 +1,2 @@
+DROP INDEX play_events_started_at;
+DROP TABLE play_events;
@@ -0,0 +1,11 @@
+-- Every time a screen showed a slide, as reported by the screen itself
+CREATE TABLE play_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    slide_id TEXT NOT NULL,
+    screen_id TEXT NOT NULL,
+    device_id TEXT NOT NULL,
+    started_at DATETIME NOT NULL,
+    duration_ms BIGINT NOT NULL
+);
+
+CREATE INDEX play_events_started_at ON play_events (started_at);
@@ -0,0 +1,20 @@
+-- Settings that apply during a time window, on top of the defaults in `settings`
+-- and the settings of each screen. All times are local time.
+CREATE TABLE settings_schedules (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    -- NULL leaves the setting alone
+    layout_type TEXT,
+    color_mode TEXT,
+    -- Weekly rules: a bitmask of weekdays (Monday is 1, Sunday 64) and a time of day.
+    -- If end_time is before start_time the window ends the next day.
+    weekdays INTEGER,
+    start_time TIME,
+    end_time TIME,
+    -- One-off rules: a date range
+    start_at DATETIME,
+    end_at DATETIME,
+    created_at DATETIME NOT NULL,
+    CHECK ((weekdays IS NOT NULL AND start_time IS NOT NULL AND end_time IS NOT NULL)
+        OR (start_at IS NOT NULL AND end_at IS NOT NULL))
+)
@@ -0,0 +1,2 @@
+DROP TABLE quiet_hours_exceptions;
+DROP TABLE quiet_hours
@@ -0,0 +1,17 @@
+-- Weekly windows when the screens show a clock instead of their content. Times are local time,
+-- and if end_time is before start_time the window ends the next day.
+CREATE TABLE quiet_hours (
+    id INTEGER PRIMARY KEY NOT NULL,
+    -- Bitmask of weekdays, Monday is 1 and Sunday 64
+    weekdays INTEGER NOT NULL,
+    start_time TIME NOT NULL,
+    end_time TIME NOT NULL
+);
+
+-- Days that don't follow the weekly windows, like holidays
+CREATE TABLE quiet_hours_exceptions (
+    date DATE PRIMARY KEY NOT NULL,
+    -- Quiet the whole day, or never quiet that day
+    quiet BOOLEAN NOT NULL,
+    name TEXT NOT NULL
+)
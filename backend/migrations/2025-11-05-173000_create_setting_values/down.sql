@@ -0,0 +1,13 @@
+CREATE TABLE settings (
+    id INTEGER NOT NULL PRIMARY KEY CHECK (id = 1),
+    layout_type TEXT NOT NULL DEFAULT 'mixed',
+    color_mode TEXT NOT NULL DEFAULT 'light_mode'
+);
+
+INSERT INTO settings (id) VALUES (1);
+UPDATE settings SET layout_type = (SELECT trim(value, '"') FROM setting_values WHERE key = 'layout_type')
+    WHERE EXISTS (SELECT 1 FROM setting_values WHERE key = 'layout_type');
+UPDATE settings SET color_mode = (SELECT trim(value, '"') FROM setting_values WHERE key = 'color_mode')
+    WHERE EXISTS (SELECT 1 FROM setting_values WHERE key = 'color_mode');
+
+DROP TABLE setting_values
@@ -0,0 +1,12 @@
+-- Settings as key/value pairs, see `settings_registry.rs` for the keys and their types.
+-- Values are JSON. Settings without a row use their default.
+CREATE TABLE setting_values (
+    key TEXT PRIMARY KEY NOT NULL,
+    value TEXT NOT NULL
+);
+
+-- The layout and colour mode used to be the only settings, in a table with a single row
+INSERT INTO setting_values (key, value) SELECT 'layout_type', '"' || layout_type || '"' FROM settings;
+INSERT INTO setting_values (key, value) SELECT 'color_mode', '"' || color_mode || '"' FROM settings;
+
+DROP TABLE settings
@@ -0,0 +1,2 @@
+DROP TABLE sl_tracked_lines;
+DROP TABLE sl_tracked_sites
@@ -0,0 +1,19 @@
+-- SL sites shown on screens that don't have their own sites in `screen_sl_sites`
+CREATE TABLE sl_tracked_sites (
+    site_id INTEGER PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL
+);
+
+-- Lines shown from a site. A site without any lines shows every departure.
+CREATE TABLE sl_tracked_lines (
+    id INTEGER PRIMARY KEY NOT NULL,
+    site_id INTEGER NOT NULL REFERENCES sl_tracked_sites(site_id) ON DELETE CASCADE,
+    -- TRAIN | METRO | BUS | TRAM | FERRY | SHIP | TAXI
+    transport_mode TEXT NOT NULL,
+    line_id INTEGER NOT NULL,
+    -- NULL means both directions
+    direction_code INTEGER
+);
+
+-- The sites the screen used to have hard-coded
+INSERT INTO sl_tracked_sites (site_id, name) VALUES (9204, 'Tekniska högskolan'), (9600, 'Östra station')
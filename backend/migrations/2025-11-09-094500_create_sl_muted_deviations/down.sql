@@ -0,0 +1 @@
+DROP TABLE sl_muted_deviations
@@ -0,0 +1,6 @@
+-- SL deviations that admins have chosen not to show on the screens
+CREATE TABLE sl_muted_deviations (
+    deviation_case_id BIGINT PRIMARY KEY NOT NULL,
+    muted_by TEXT NOT NULL,
+    muted_at TIMESTAMP NOT NULL
+)
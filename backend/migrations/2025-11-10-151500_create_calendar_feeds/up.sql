@@ -0,0 +1,15 @@
+-- ICS feeds whose events are shown on the screens
+CREATE TABLE calendar_feeds (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    url TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL
+);
+
+-- The calendar the screen used to embed
+INSERT INTO calendar_feeds (id, name, url, created_at) VALUES (
+    '0193f0c2-5a1e-7c3d-9b8e-2f4a6d8c0e12',
+    'Fysiksektionens kalender',
+    'https://calendar.google.com/calendar/ical/fysiksektionen.se_0187vbmdcivl8mtio142e23cas%40group.calendar.google.com/public/basic.ics',
+    CURRENT_TIMESTAMP
+)
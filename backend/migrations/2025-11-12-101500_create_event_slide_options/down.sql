@@ -0,0 +1,2 @@
+DROP TABLE event_slide_options;
+ALTER TABLE calendar_feeds DROP COLUMN slide_days_before
@@ -0,0 +1,15 @@
+-- Generate slides for the events of a feed, shown from this many days before each event. NULL means no slides.
+ALTER TABLE calendar_feeds ADD COLUMN slide_days_before INTEGER;
+
+-- Per-event changes to the generated slides. `event_key` identifies an event (not an occurrence) in a feed,
+-- see `calendar::Event::slide_key`
+CREATE TABLE event_slide_options (
+    event_key TEXT PRIMARY KEY NOT NULL,
+    feed_id TEXT NOT NULL,
+    event_uid TEXT NOT NULL,
+    suppressed BOOLEAN NOT NULL DEFAULT FALSE,
+    -- Set if a poster has been uploaded, which is then shown instead of the generated text
+    poster_filetype TEXT,
+    updated_by TEXT NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+)
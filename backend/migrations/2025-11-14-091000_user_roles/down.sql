@@ -0,0 +1,11 @@
+CREATE TABLE users_with_admin (
+    id TEXT PRIMARY KEY NOT NULL,
+    email TEXT NOT NULL UNIQUE,
+    admin BOOLEAN NOT NULL
+);
+
+INSERT INTO users_with_admin (id, email, admin)
+SELECT id, email, role = 'admin' FROM users;
+
+DROP TABLE users;
+ALTER TABLE users_with_admin RENAME TO users;
@@ -0,0 +1,12 @@
+-- Replaces the admin flag with a role. Users that weren't admins could already manage slides, so they become slide editors.
+CREATE TABLE users_with_roles (
+    id TEXT PRIMARY KEY NOT NULL,
+    email TEXT NOT NULL UNIQUE,
+    role TEXT NOT NULL
+);
+
+INSERT INTO users_with_roles (id, email, role)
+SELECT id, email, CASE WHEN admin THEN 'admin' ELSE 'slide_editor' END FROM users;
+
+DROP TABLE users;
+ALTER TABLE users_with_roles RENAME TO users;
@@ -0,0 +1,4 @@
+ALTER TABLE slides DROP COLUMN group_id;
+ALTER TABLE slides DROP COLUMN created_by;
+DROP TABLE group_members;
+DROP TABLE groups;
@@ -0,0 +1,18 @@
+-- Committees and other groups that own slides
+CREATE TABLE groups (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMP NOT NULL
+);
+
+-- `role` is a user role, but only its slide permissions apply, and only to the group's slides
+CREATE TABLE group_members (
+    group_id TEXT NOT NULL REFERENCES groups(id) ON DELETE CASCADE,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role TEXT NOT NULL,
+    PRIMARY KEY (group_id, user_id)
+);
+
+-- Both are NULL for slides uploaded before groups
+ALTER TABLE slides ADD COLUMN created_by TEXT;
+ALTER TABLE slides ADD COLUMN group_id TEXT REFERENCES groups(id);
@@ -0,0 +1,2 @@
+DROP TABLE slide_status_changes;
+ALTER TABLE slides DROP COLUMN status;
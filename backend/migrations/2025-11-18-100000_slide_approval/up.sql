@@ -0,0 +1,16 @@
+-- Slides uploaded before the approval workflow were already shown on the screens
+ALTER TABLE slides ADD COLUMN status TEXT NOT NULL DEFAULT 'approved';
+
+-- Every change of a slide's status, including the one when it was uploaded. Kept after the slide is removed.
+CREATE TABLE slide_status_changes (
+    id TEXT PRIMARY KEY NOT NULL,
+    slide_id TEXT NOT NULL,
+    -- NULL when the slide was uploaded
+    from_status TEXT,
+    to_status TEXT NOT NULL,
+    changed_by TEXT NOT NULL,
+    comment TEXT,
+    changed_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX slide_status_changes_slide_id ON slide_status_changes (slide_id);
@@ -0,0 +1,13 @@
+-- Every change made through the admin API, see `audit.rs`
+CREATE TABLE audit_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    actor TEXT NOT NULL,
+    action TEXT NOT NULL,
+    target_id TEXT NOT NULL,
+    -- JSON of the target before and after the change. NULL for targets that were created or removed.
+    before TEXT,
+    after TEXT,
+    created_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX audit_events_created_at ON audit_events (created_at);
@@ -0,0 +1,15 @@
+-- Login sessions, see `sessions.rs`. The cookie only holds the session key, of which only the hash is stored.
+CREATE TABLE sessions (
+    id TEXT PRIMARY KEY NOT NULL,
+    key_hash TEXT NOT NULL UNIQUE,
+    -- JSON object with the session state
+    state TEXT NOT NULL,
+    -- Copied from the state so sessions can be listed per user. NULL until someone logs in.
+    email TEXT,
+    user_agent TEXT,
+    created_at TIMESTAMP NOT NULL,
+    last_seen_at TIMESTAMP NOT NULL,
+    expires_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX sessions_email ON sessions (email);
@@ -0,0 +1,2 @@
+DROP TABLE provisioning_emails;
+DROP TABLE provisioning_domains;
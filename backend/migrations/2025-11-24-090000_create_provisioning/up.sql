@@ -0,0 +1,15 @@
+-- Users with a verified email in one of these domains are added with `role` the first time they log in
+CREATE TABLE provisioning_domains (
+    domain TEXT PRIMARY KEY NOT NULL,
+    role TEXT NOT NULL,
+    created_by TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL
+);
+
+-- Exceptions to the domains for single emails. A NULL role means the email is never added automatically.
+CREATE TABLE provisioning_emails (
+    email TEXT PRIMARY KEY NOT NULL,
+    role TEXT,
+    created_by TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL
+);
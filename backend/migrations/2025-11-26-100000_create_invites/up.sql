@@ -0,0 +1,12 @@
+-- Single-use links for adding users. Whoever logs in with the invite first is added with `role`.
+-- Only the hash of the token is stored, like device tokens.
+CREATE TABLE invites (
+    id TEXT PRIMARY KEY NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    role TEXT NOT NULL,
+    created_by TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    used_by TEXT,
+    used_at TIMESTAMP
+);
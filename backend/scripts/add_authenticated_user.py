@@ -1,5 +1,5 @@
 """
-Adds a new user with a random uuid id and admin status 
+Adds a new user with a random uuid id and the admin role
 to the `users` table in the database.
 Usage:
     python add_authenticated_user.py <user_email> <db_path>
@@ -14,15 +14,15 @@ import uuid
 
 def add_user(db_path: str, user: str):
     """Add a user with email `user` to the databse table `users` with a random uuid id
-    and admin status."""
+    and the admin role."""
     conn = sqlite3.connect(db_path)
     cursor = conn.cursor()
     
     id = str(uuid.uuid4())
     
     cursor.execute("""
-        INSERT INTO users (id, email, admin)
-        VALUES (?, ?, ?)""", (id, user, True))
+        INSERT INTO users (id, email, role)
+        VALUES (?, ?, ?)""", (id, user, 'admin'))
     
     conn.commit()
     conn.close()
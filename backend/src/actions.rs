@@ -1,145 +1,1482 @@
+use std::collections::HashMap;
+
+use chrono::{Local, NaiveDateTime, NaiveTime};
 use diesel::prelude::*;
 use uuid::Uuid;
 
-use crate::models::{self, Slide};
+use crate::audit::AuditQuery;
+use crate::models::{self, Slide};
+use crate::provisioning;
+use crate::schedule::{self, QuietHours, WeeklyWindow};
+use crate::settings_registry::{self, SettingValues};
+
+use crate::models::{Role, SlideStatus, User};
+use diesel::SqliteConnection;
+
+pub type DbError = Box<dyn std::error::Error + Send + Sync>;
+
+/// True if the error comes from a UNIQUE constraint, like a name that is already taken
+pub fn is_unique_violation(error: &DbError) -> bool {
+    matches!(
+        error.downcast_ref::<diesel::result::Error>(),
+        Some(diesel::result::Error::DatabaseError(diesel::result::DatabaseErrorKind::UniqueViolation, _))
+    )
+}
+
+pub fn get_all_slides(
+    conn: &mut SqliteConnection,
+) -> Result<Vec<Slide>, DbError> {
+    use crate::schema::slides::dsl::*;
+
+    let all_slides = slides.load::<Slide>(conn)?;
+
+    Ok(all_slides)
+}
+
+pub fn slide_exists(conn: &mut SqliteConnection, slide_id: &str) -> Result<bool, DbError> {
+    use crate::schema::slides::dsl::*;
+
+    let count: i64 = slides
+        .filter(id.eq(slide_id))
+        .count()
+        .get_result(conn)?;
+
+    Ok(count > 0)
+}
+
+pub fn get_slide(conn: &mut SqliteConnection, slide_id: &str) -> Result<Option<Slide>, DbError> {
+    use crate::schema::slides::dsl::*;
+
+    let slide = slides.find(slide_id).first::<Slide>(conn).optional()?;
+
+    Ok(slide)
+}
+
+pub fn insert_slide(
+    conn: &mut SqliteConnection,
+    slide: models::Slide,
+) -> Result<models::Slide, DbError> {
+    use crate::schema::slides::dsl::*;
+
+    diesel::insert_into(slides)
+        .values(&slide)
+        .execute(conn)?;
+
+    Ok(slide)
+}
+
+/// Insert a new slide and record its status as the first status change
+pub fn upload_slide(conn: &mut SqliteConnection, slide: Slide, uploaded_by: &str, now: NaiveDateTime) -> Result<Slide, DbError> {
+    let upload = models::SlideStatusChange {
+        id: Uuid::new_v4().into(),
+        slide_id: slide.id.clone(),
+        from_status: None,
+        to_status: slide.status,
+        changed_by: uploaded_by.to_string(),
+        comment: None,
+        changed_at: now,
+    };
+
+    conn.transaction(|conn| {
+        insert_slide_status_change(conn, &upload)?;
+        insert_slide(conn, slide)
+    })
+}
+
+/// Slides waiting for review, oldest first
+pub fn get_pending_slides(conn: &mut SqliteConnection) -> Result<Vec<Slide>, DbError> {
+    use crate::schema::{slides, slide_status_changes};
+
+    // When a slide was last submitted
+    let submitted_at = slide_status_changes::table
+        .filter(slide_status_changes::slide_id.eq(slides::id))
+        .filter(slide_status_changes::to_status.eq(SlideStatus::Pending))
+        .select(diesel::dsl::max(slide_status_changes::changed_at))
+        .single_value();
+
+    let pending = slides::table
+        .filter(slides::status.eq(SlideStatus::Pending))
+        .order(submitted_at.asc())
+        .load::<Slide>(conn)?;
+
+    Ok(pending)
+}
+
+pub fn insert_slide_status_change(conn: &mut SqliteConnection, change: &models::SlideStatusChange) -> Result<(), DbError> {
+    use crate::schema::slide_status_changes::dsl::*;
+
+    diesel::insert_into(slide_status_changes).values(change).execute(conn)?;
+
+    Ok(())
+}
+
+/// Change the status of a slide and record the change. Returns false, and changes nothing, if the slide
+/// doesn't have `change.from_status` (any more).
+pub fn change_slide_status(conn: &mut SqliteConnection, change: &models::SlideStatusChange) -> Result<bool, DbError> {
+    use crate::schema::slides;
+
+    let Some(from_status) = change.from_status else {
+        return Ok(false);
+    };
+
+    let changed = conn.transaction(|conn| {
+        let updated = diesel::update(slides::table.find(&change.slide_id).filter(slides::status.eq(from_status)))
+            .set(slides::status.eq(change.to_status))
+            .execute(conn)?;
+        if updated > 0 {
+            insert_slide_status_change(conn, change)?;
+        }
+        Ok::<_, DbError>(updated > 0)
+    })?;
+
+    Ok(changed)
+}
+
+/// Every status change of a slide, oldest first
+pub fn get_slide_status_changes(conn: &mut SqliteConnection, slide: &str) -> Result<Vec<models::SlideStatusChange>, DbError> {
+    use crate::schema::slide_status_changes::dsl::*;
+
+    let changes = slide_status_changes
+        .filter(slide_id.eq(slide))
+        .order(changed_at.asc())
+        .load::<models::SlideStatusChange>(conn)?;
+
+    Ok(changes)
+}
+
+/// Insert a user into the database and return it
+pub fn insert_user(conn: &mut SqliteConnection, user: User) -> Result<User, DbError> {
+    use crate::schema::users::dsl::*;
+
+    // Check if the user already exists
+    let existing_user = users
+        .filter(email.eq(user.email.clone()))
+        .first::<User>(conn)
+        .optional()?;
+
+    // If the user already exists, return it instead
+    if let Some(user) = existing_user {
+        return Ok(user);
+    }
+
+    diesel::insert_into(users)
+        .values(&user)
+        .on_conflict(email)
+        .do_nothing()
+        .execute(conn)?;
+
+    Ok(user)
+}
+
+/// Why a user couldn't be changed or removed
+#[derive(Debug, PartialEq)]
+pub enum UserChangeDenied {
+    NotFound,
+    /// Without an admin nobody could manage users any more
+    LastAdmin,
+}
+
+/// Returns the user as it was before the change, with its old role.
+/// The check that another admin is left and the change are done in one (immediate) transaction,
+/// so two admins can't demote each other at the same time.
+fn change_user(
+    conn: &mut SqliteConnection,
+    user_id: &str,
+    new_role: Option<Role>,
+    change: impl FnOnce(&mut SqliteConnection, &User) -> QueryResult<()>,
+) -> Result<Result<User, UserChangeDenied>, DbError> {
+    use crate::schema::users::dsl::*;
+
+    let changed = conn.immediate_transaction(|conn| {
+        let Some(user) = users.find(user_id).first::<User>(conn).optional()? else {
+            return Ok(Err(UserChangeDenied::NotFound));
+        };
+        if user.role == Role::Admin && new_role != Some(Role::Admin) {
+            let admins: i64 = users.filter(role.eq(Role::Admin)).count().get_result(conn)?;
+            if admins <= 1 {
+                return Ok(Err(UserChangeDenied::LastAdmin));
+            }
+        }
+        change(conn, &user)?;
+        Ok::<_, diesel::result::Error>(Ok(user))
+    })?;
+
+    Ok(changed)
+}
+
+/// Remove a user, their group memberships and their sessions, and return the removed user
+pub fn remove_user(conn: &mut SqliteConnection, user_id: &str) -> Result<Result<User, UserChangeDenied>, DbError> {
+    use crate::schema::users::dsl::*;
+    use crate::schema::{group_members, sessions};
+
+    change_user(conn, user_id, None, |conn, user| {
+        diesel::delete(group_members::table.filter(group_members::user_id.eq(user_id)))
+            .execute(conn)?;
+        diesel::delete(sessions::table.filter(sessions::email.eq(&user.email)))
+            .execute(conn)?;
+        diesel::delete(users.find(user_id))
+            .execute(conn)?;
+        Ok(())
+    })
+}
+
+/// Change a user's role, and return the user as it was before
+pub fn set_user_role(conn: &mut SqliteConnection, user_id: &str, new_role: Role) -> Result<Result<User, UserChangeDenied>, DbError> {
+    use crate::schema::users::dsl::*;
+
+    change_user(conn, user_id, Some(new_role), |conn, _| {
+        diesel::update(users.find(user_id))
+            .set(role.eq(new_role))
+            .execute(conn)?;
+        Ok(())
+    })
+}
+
+/// Remove a slide from the database and return it
+/// Returns None if there is no slide with that id
+pub fn pop_slide(
+    conn: &mut SqliteConnection,
+    uuid: &Uuid,
+) -> Result<Option<Slide>,DbError> {
+    use crate::schema::slides::dsl::*;
+    use crate::schema::slide_screens;
+
+    // Get slide
+    let Some(slide) = slides
+        .find(uuid.to_string())
+        .first(conn)
+        .optional()? else {
+        return Ok(None);
+    };
+    
+    // Delete slide along with the screens it was assigned to
+    conn.transaction(|conn| {
+        diesel::delete(slide_screens::table.filter(slide_screens::slide_id.eq(uuid.to_string())))
+            .execute(conn)?;
+        diesel::delete(slides.filter(id.eq(uuid.to_string())))
+            .execute(conn)
+    })?;
+
+    Ok(Some(slide))
+}
+
+/// Takes an `UpdateSlide` and updates every field that is Some(_)
+#[allow(dead_code)] // Not exposed through a route yet
+pub fn update_slide(
+    conn: &mut SqliteConnection,
+    update_slide: models::UpdateSlide,
+) -> Result<(), DbError> {
+    use crate::schema::slides::dsl::*;
+
+    diesel::update(slides.find(update_slide.id))
+        .set(update_slide)
+        .execute(conn)?;
+
+    Ok(())
+}
+
+pub fn get_all_users(
+    conn: &mut SqliteConnection,
+) -> Result<Vec<User>, DbError> {
+    use crate::schema::users::dsl::*;
+
+    let all_users = users.load::<User>(conn)?;
+
+    Ok(all_users)
+}
+
+pub fn user_exists(conn: &mut SqliteConnection, user_id: &str) -> Result<bool, DbError> {
+    use crate::schema::users::dsl::*;
+
+    let count: i64 = users.filter(id.eq(user_id)).count().get_result(conn)?;
+
+    Ok(count > 0)
+}
+
+/// Check a user against the database
+/// Returns None if the user does not exist, otherwise their Role
+pub fn check_user(conn: &mut SqliteConnection, email_str: &str) -> Result<Option<Role>, DbError> {
+    use crate::schema::users::dsl::*;
+
+    let user = users
+        .filter(email.eq(email_str.to_owned()))
+        .first::<models::User>(conn)
+        .optional()?;
+
+    Ok(user.map(|user| user.role))
+}
+
+pub fn get_setting_values(conn: &mut SqliteConnection) -> Result<SettingValues, DbError> {
+    use crate::schema::setting_values::dsl::*;
+
+    let stored = setting_values.select((key, value)).load::<(String, String)>(conn)?;
+
+    Ok(SettingValues::from_stored(stored)?)
+}
+
+pub fn get_settings(conn: &mut SqliteConnection) -> Result<models::Settings, DbError> {
+    Ok(get_setting_values(conn)?.settings())
+}
+
+/// Store the value of a setting. The value has to be validated against its definition first.
+pub fn set_setting_value(conn: &mut SqliteConnection, setting_key: &str, new_value: &serde_json::Value) -> Result<(), DbError> {
+    use crate::schema::setting_values::dsl::*;
+
+    diesel::replace_into(setting_values)
+        .values((key.eq(setting_key), value.eq(new_value.to_string())))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// Makes a setting use its default again
+pub fn remove_setting_value(conn: &mut SqliteConnection, setting_key: &str) -> Result<(), DbError> {
+    use crate::schema::setting_values::dsl::*;
+
+    diesel::delete(setting_values.find(setting_key)).execute(conn)?;
+
+    Ok(())
+}
+
+pub fn update_settings(
+    conn: &mut SqliteConnection,
+    new_layout_type: models::LayoutType,
+    new_color_mode: models::ColorMode,
+) -> Result<models::Settings, DbError> {
+    conn.transaction(|conn| {
+        set_setting_value(conn, settings_registry::LAYOUT_TYPE, &new_layout_type.as_str().into())?;
+        set_setting_value(conn, settings_registry::COLOR_MODE, &new_color_mode.as_str().into())?;
+
+        get_settings(conn)
+    })
+}
+
+pub fn get_settings_schedules(conn: &mut SqliteConnection) -> Result<Vec<models::SettingsSchedule>, DbError> {
+    use crate::schema::settings_schedules::dsl::*;
+
+    let schedules = settings_schedules
+        .order(created_at.asc())
+        .load::<models::SettingsSchedule>(conn)?;
+
+    Ok(schedules)
+}
+
+pub fn get_settings_schedule(conn: &mut SqliteConnection, schedule_id: &str) -> Result<Option<models::SettingsSchedule>, DbError> {
+    use crate::schema::settings_schedules::dsl::*;
+
+    let schedule = settings_schedules.find(schedule_id).first::<models::SettingsSchedule>(conn).optional()?;
+
+    Ok(schedule)
+}
+
+pub fn insert_settings_schedule(
+    conn: &mut SqliteConnection,
+    schedule: &models::SettingsSchedule,
+) -> Result<(), DbError> {
+    use crate::schema::settings_schedules::dsl::*;
+
+    diesel::insert_into(settings_schedules).values(schedule).execute(conn)?;
+
+    Ok(())
+}
+
+/// Returns false if there is no schedule with that id. `created_at` is kept as it was.
+pub fn update_settings_schedule(
+    conn: &mut SqliteConnection,
+    schedule: &models::SettingsSchedule,
+) -> Result<bool, DbError> {
+    use crate::schema::settings_schedules::dsl::*;
+
+    let updated = diesel::update(settings_schedules.find(&schedule.id))
+        .set((
+            name.eq(&schedule.name),
+            layout_type.eq(schedule.layout_type),
+            color_mode.eq(schedule.color_mode),
+            weekdays.eq(schedule.weekdays),
+            start_time.eq(schedule.start_time),
+            end_time.eq(schedule.end_time),
+            start_at.eq(schedule.start_at),
+            end_at.eq(schedule.end_at),
+        ))
+        .execute(conn)?;
+
+    Ok(updated > 0)
+}
+
+pub fn remove_settings_schedule(conn: &mut SqliteConnection, schedule_id: &str) -> Result<bool, DbError> {
+    use crate::schema::settings_schedules::dsl::*;
+
+    let removed = diesel::delete(settings_schedules.find(schedule_id)).execute(conn)?;
+
+    Ok(removed > 0)
+}
+
+pub fn get_quiet_hours(conn: &mut SqliteConnection) -> Result<QuietHours, DbError> {
+    use crate::schema::{quiet_hours, quiet_hours_exceptions};
+
+    let windows = quiet_hours::table
+        .order(quiet_hours::id.asc())
+        .select((quiet_hours::weekdays, quiet_hours::start_time, quiet_hours::end_time))
+        .load::<(i32, NaiveTime, NaiveTime)>(conn)?
+        .into_iter()
+        .map(|(weekdays, start_time, end_time)| WeeklyWindow { weekdays: schedule::mask_to_weekdays(weekdays), start_time, end_time })
+        .collect();
+    let exceptions = quiet_hours_exceptions::table
+        .order(quiet_hours_exceptions::date.asc())
+        .load::<models::QuietHoursException>(conn)?;
+
+    Ok(QuietHours { windows, exceptions })
+}
+
+/// Replace all quiet hours and exceptions
+pub fn set_quiet_hours(conn: &mut SqliteConnection, new_quiet_hours: &QuietHours) -> Result<(), DbError> {
+    use crate::schema::{quiet_hours, quiet_hours_exceptions};
+
+    conn.transaction(|conn| {
+        diesel::delete(quiet_hours::table).execute(conn)?;
+        diesel::delete(quiet_hours_exceptions::table).execute(conn)?;
+
+        let windows: Vec<_> = new_quiet_hours.windows.iter()
+            .map(|window| (
+                quiet_hours::weekdays.eq(schedule::weekdays_to_mask(&window.weekdays)),
+                quiet_hours::start_time.eq(window.start_time),
+                quiet_hours::end_time.eq(window.end_time),
+            ))
+            .collect();
+        diesel::insert_into(quiet_hours::table).values(&windows).execute(conn)?;
+        diesel::insert_into(quiet_hours_exceptions::table).values(&new_quiet_hours.exceptions).execute(conn)?;
+
+        Ok(())
+    })
+}
+
+/// Get the slides shown on a screen: every slide that isn't assigned to specific screens,
+/// plus the ones assigned to this screen. With `None`, only the slides shown everywhere.
+pub fn get_slides_for_screen(conn: &mut SqliteConnection, screen: Option<&str>) -> Result<Vec<Slide>, DbError> {
+    use crate::schema::{slides, slide_screens};
+
+    let assigned_anywhere = slide_screens::table.select(slide_screens::slide_id);
+    let everywhere = slides::id.ne_all(assigned_anywhere);
+
+    let screen_slides = match screen {
+        Some(screen) => {
+            let assigned_here = slide_screens::table
+                .filter(slide_screens::screen_id.eq(screen.to_owned()))
+                .select(slide_screens::slide_id);
+            slides::table
+                .filter(slides::status.eq(SlideStatus::Approved))
+                .filter(everywhere.or(slides::id.eq_any(assigned_here)))
+                .load::<Slide>(conn)?
+        },
+        None => slides::table
+            .filter(slides::status.eq(SlideStatus::Approved))
+            .filter(everywhere)
+            .load::<Slide>(conn)?,
+    };
+
+    Ok(screen_slides)
+}
+
+/// Get the slides a screen should show right now. While an override is active, that is
+/// only the override's slide (if it has one and it is shown on this screen). Overrides
+/// are shown even during quiet hours, but other slides are not.
+pub fn get_current_slides_for_screen(conn: &mut SqliteConnection, screen: Option<&str>, now: NaiveDateTime) -> Result<Vec<Slide>, DbError> {
+    let screen_slides = get_slides_for_screen(conn, screen)?;
+
+    Ok(match get_active_override(conn, now)? {
+        Some(active) => screen_slides.into_iter()
+            .filter(|slide| Some(&slide.id) == active.slide_id.as_ref())
+            .collect(),
+        // Quiet hours are in local time
+        None if get_quiet_hours(conn)?.is_quiet(now.and_utc().with_timezone(&Local).naive_local()) => Vec::new(),
+        None => screen_slides,
+    })
+}
+
+/// Get the ids of the screens a slide is assigned to. Empty means every screen.
+pub fn get_slide_screens(conn: &mut SqliteConnection, slide: &str) -> Result<Vec<String>, DbError> {
+    use crate::schema::slide_screens::dsl::*;
+
+    let screen_ids = slide_screens
+        .filter(slide_id.eq(slide))
+        .select(screen_id)
+        .load::<String>(conn)?;
+
+    Ok(screen_ids)
+}
+
+/// Replace the screens a slide is assigned to. An empty list means every screen.
+pub fn set_slide_screens(conn: &mut SqliteConnection, slide: &str, screen_ids: &[String]) -> Result<(), DbError> {
+    use crate::schema::slide_screens::dsl::*;
+
+    let rows: Vec<models::SlideScreen> = screen_ids.iter()
+        .map(|screen| models::SlideScreen { slide_id: slide.to_owned(), screen_id: screen.clone() })
+        .collect();
+
+    conn.transaction(|conn| {
+        diesel::delete(slide_screens.filter(slide_id.eq(slide)))
+            .execute(conn)?;
+        diesel::insert_into(slide_screens)
+            .values(&rows)
+            .execute(conn)
+    })?;
+
+    Ok(())
+}
+
+pub fn get_all_screens(conn: &mut SqliteConnection) -> Result<Vec<models::Screen>, DbError> {
+    use crate::schema::screens::dsl::*;
+
+    let all_screens = screens
+        .order(name.asc())
+        .load::<models::Screen>(conn)?;
+
+    Ok(all_screens)
+}
+
+/// Returns None if there is no screen with that id
+pub fn get_screen(conn: &mut SqliteConnection, screen_id: &str) -> Result<Option<models::Screen>, DbError> {
+    use crate::schema::screens::dsl::*;
+
+    let screen = screens
+        .find(screen_id)
+        .first::<models::Screen>(conn)
+        .optional()?;
+
+    Ok(screen)
+}
+
+/// Returns false if one of the ids is not a screen
+pub fn screens_exist(conn: &mut SqliteConnection, screen_ids: &[String]) -> Result<bool, DbError> {
+    use crate::schema::screens::dsl::*;
+
+    let count: i64 = screens
+        .filter(id.eq_any(screen_ids))
+        .count()
+        .get_result(conn)?;
+
+    Ok(count as usize == screen_ids.len())
+}
+
+pub fn insert_screen(conn: &mut SqliteConnection, screen: models::Screen) -> Result<models::Screen, DbError> {
+    use crate::schema::screens::dsl::*;
+
+    diesel::insert_into(screens)
+        .values(&screen)
+        .execute(conn)?;
+
+    Ok(screen)
+}
+
+/// Overwrite every field of a screen. Returns false if there is no screen with that id.
+pub fn update_screen(conn: &mut SqliteConnection, screen: &models::Screen) -> Result<bool, DbError> {
+    use crate::schema::screens::dsl::*;
+
+    let updated = diesel::update(screens.find(&screen.id))
+        .set(screen)
+        .execute(conn)?;
+
+    Ok(updated > 0)
+}
+
+/// Remove a screen and everything assigned to it, including its devices. Returns false if there is no screen with that id.
+pub fn remove_screen(conn: &mut SqliteConnection, screen: &str) -> Result<bool, DbError> {
+    use crate::schema::{devices, screens, screen_commands, screen_sl_sites, slide_screens};
+
+    let removed = conn.transaction(|conn| {
+        diesel::delete(screen_sl_sites::table.filter(screen_sl_sites::screen_id.eq(screen)))
+            .execute(conn)?;
+        diesel::delete(slide_screens::table.filter(slide_screens::screen_id.eq(screen)))
+            .execute(conn)?;
+        diesel::delete(screen_commands::table.filter(screen_commands::screen_id.eq(screen)))
+            .execute(conn)?;
+        // Devices can't be left pointing at a screen that doesn't exist
+        diesel::delete(devices::table.filter(devices::screen_id.eq(screen)))
+            .execute(conn)?;
+        diesel::delete(screens::table.find(screen))
+            .execute(conn)
+    })?;
+
+    Ok(removed > 0)
+}
+
+/// Get the SL sites whose departures are shown on a screen
+pub fn get_screen_sl_sites(conn: &mut SqliteConnection, screen: &str) -> Result<Vec<i32>, DbError> {
+    use crate::schema::screen_sl_sites::dsl::*;
+
+    let sites = screen_sl_sites
+        .filter(screen_id.eq(screen))
+        .order(site_id.asc())
+        .select(site_id)
+        .load::<i32>(conn)?;
+
+    Ok(sites)
+}
+
+/// Replace the SL sites shown on a screen
+pub fn set_screen_sl_sites(conn: &mut SqliteConnection, screen: &str, sites: &[i32]) -> Result<(), DbError> {
+    use crate::schema::screen_sl_sites::dsl::*;
+
+    let rows: Vec<models::ScreenSlSite> = sites.iter()
+        .map(|site| models::ScreenSlSite { screen_id: screen.to_owned(), site_id: *site })
+        .collect();
+
+    conn.transaction(|conn| {
+        diesel::delete(screen_sl_sites.filter(screen_id.eq(screen)))
+            .execute(conn)?;
+        diesel::insert_or_ignore_into(screen_sl_sites)
+            .values(&rows)
+            .execute(conn)
+    })?;
+
+    Ok(())
+}
+
+pub fn get_sl_tracked_sites(conn: &mut SqliteConnection) -> Result<Vec<models::SlTrackedSite>, DbError> {
+    use crate::schema::sl_tracked_sites::dsl::*;
+
+    let sites = sl_tracked_sites.order(site_id.asc()).load::<models::SlTrackedSite>(conn)?;
+
+    Ok(sites)
+}
+
+/// Get the tracked lines of every site, by site id
+pub fn get_sl_tracked_lines(conn: &mut SqliteConnection) -> Result<HashMap<i32, Vec<models::SlTrackedLine>>, DbError> {
+    use crate::schema::sl_tracked_lines::dsl::*;
+
+    let lines = sl_tracked_lines
+        .order(id.asc())
+        .select(models::SlTrackedLine::as_select())
+        .load(conn)?;
+
+    let mut by_site: HashMap<i32, Vec<models::SlTrackedLine>> = HashMap::new();
+    for line in lines {
+        by_site.entry(line.site_id).or_default().push(line);
+    }
+    Ok(by_site)
+}
+
+/// Add or replace a tracked site and its lines
+pub fn set_sl_tracked_site(
+    conn: &mut SqliteConnection,
+    site: &models::SlTrackedSite,
+    lines: &[models::SlTrackedLine],
+) -> Result<(), DbError> {
+    use crate::schema::{sl_tracked_lines, sl_tracked_sites};
+
+    conn.transaction(|conn| {
+        diesel::replace_into(sl_tracked_sites::table).values(site).execute(conn)?;
+        diesel::delete(sl_tracked_lines::table.filter(sl_tracked_lines::site_id.eq(site.site_id))).execute(conn)?;
+
+        let rows: Vec<_> = lines.iter()
+            .map(|line| (
+                sl_tracked_lines::site_id.eq(site.site_id),
+                sl_tracked_lines::transport_mode.eq(&line.transport_mode),
+                sl_tracked_lines::line_id.eq(line.line_id),
+                sl_tracked_lines::direction_code.eq(line.direction_code),
+            ))
+            .collect();
+        diesel::insert_into(sl_tracked_lines::table).values(&rows).execute(conn)
+    })?;
+
+    Ok(())
+}
+
+pub fn remove_sl_tracked_site(conn: &mut SqliteConnection, site: i32) -> Result<bool, DbError> {
+    use crate::schema::{sl_tracked_lines, sl_tracked_sites};
+
+    let removed = conn.transaction(|conn| {
+        diesel::delete(sl_tracked_lines::table.filter(sl_tracked_lines::site_id.eq(site))).execute(conn)?;
+        diesel::delete(sl_tracked_sites::table.find(site)).execute(conn)
+    })?;
+
+    Ok(removed > 0)
+}
+
+/// Get the ids of the muted SL deviations
+pub fn get_sl_muted_deviations(conn: &mut SqliteConnection) -> Result<Vec<i64>, DbError> {
+    use crate::schema::sl_muted_deviations::dsl::*;
+
+    let ids = sl_muted_deviations.select(deviation_case_id).load::<i64>(conn)?;
+
+    Ok(ids)
+}
+
+/// Mute an SL deviation. Muting it again keeps who muted it first.
+pub fn mute_sl_deviation(conn: &mut SqliteConnection, muted: &models::SlMutedDeviation) -> Result<(), DbError> {
+    use crate::schema::sl_muted_deviations::dsl::*;
+
+    diesel::insert_or_ignore_into(sl_muted_deviations).values(muted).execute(conn)?;
+
+    Ok(())
+}
+
+pub fn unmute_sl_deviation(conn: &mut SqliteConnection, case_id: i64) -> Result<bool, DbError> {
+    use crate::schema::sl_muted_deviations::dsl::*;
+
+    let removed = diesel::delete(sl_muted_deviations.find(case_id)).execute(conn)?;
+
+    Ok(removed > 0)
+}
+
+pub fn get_calendar_feeds(conn: &mut SqliteConnection) -> Result<Vec<models::CalendarFeed>, DbError> {
+    use crate::schema::calendar_feeds::dsl::*;
+
+    let feeds = calendar_feeds.order(created_at.asc()).load::<models::CalendarFeed>(conn)?;
+
+    Ok(feeds)
+}
+
+pub fn insert_calendar_feed(conn: &mut SqliteConnection, feed: models::CalendarFeed) -> Result<models::CalendarFeed, DbError> {
+    use crate::schema::calendar_feeds::dsl::*;
+
+    diesel::insert_into(calendar_feeds)
+        .values(&feed)
+        .execute(conn)?;
+
+    Ok(feed)
+}
+
+pub fn remove_calendar_feed(conn: &mut SqliteConnection, feed: &str) -> Result<bool, DbError> {
+    use crate::schema::calendar_feeds::dsl::*;
+
+    let removed = diesel::delete(calendar_feeds.find(feed)).execute(conn)?;
+
+    Ok(removed > 0)
+}
+
+/// Overwrite every field of a feed except when it was created. Returns false if there is no feed with that id.
+pub fn update_calendar_feed(conn: &mut SqliteConnection, feed: &models::CalendarFeed) -> Result<bool, DbError> {
+    use crate::schema::calendar_feeds::dsl::*;
+
+    let updated = diesel::update(calendar_feeds.find(&feed.id))
+        .set((name.eq(&feed.name), url.eq(&feed.url), slide_days_before.eq(feed.slide_days_before)))
+        .execute(conn)?;
+
+    Ok(updated > 0)
+}
+
+/// Get the options of every event slide that has any, by event key
+pub fn get_event_slide_options(conn: &mut SqliteConnection) -> Result<HashMap<String, models::EventSlideOptions>, DbError> {
+    use crate::schema::event_slide_options::dsl::*;
+
+    let options = event_slide_options.load::<models::EventSlideOptions>(conn)?;
+
+    Ok(options.into_iter().map(|options| (options.event_key.clone(), options)).collect())
+}
+
+pub fn set_event_slide_options(conn: &mut SqliteConnection, options: &models::EventSlideOptions) -> Result<(), DbError> {
+    use crate::schema::event_slide_options::dsl::*;
+
+    diesel::replace_into(event_slide_options).values(options).execute(conn)?;
+
+    Ok(())
+}
+
+pub fn get_groups(conn: &mut SqliteConnection) -> Result<Vec<models::Group>, DbError> {
+    use crate::schema::groups::dsl::*;
+
+    let all_groups = groups.order(name.asc()).load::<models::Group>(conn)?;
+
+    Ok(all_groups)
+}
+
+/// Every group membership, with the member's email
+pub fn get_group_members(conn: &mut SqliteConnection) -> Result<Vec<(models::GroupMember, String)>, DbError> {
+    use crate::schema::{group_members, users};
+
+    let members = group_members::table
+        .inner_join(users::table)
+        .select((models::GroupMember::as_select(), users::email))
+        .order(users::email.asc())
+        .load::<(models::GroupMember, String)>(conn)?;
+
+    Ok(members)
+}
+
+pub fn get_group(conn: &mut SqliteConnection, group: &str) -> Result<Option<models::Group>, DbError> {
+    use crate::schema::groups::dsl::*;
+
+    let found = groups.find(group).first::<models::Group>(conn).optional()?;
+
+    Ok(found)
+}
+
+pub fn group_exists(conn: &mut SqliteConnection, group: &str) -> Result<bool, DbError> {
+    use crate::schema::groups::dsl::*;
+
+    let count: i64 = groups.filter(id.eq(group)).count().get_result(conn)?;
+
+    Ok(count > 0)
+}
+
+/// Returns None if there already is a group with that name
+pub fn insert_group(conn: &mut SqliteConnection, group: models::Group) -> Result<Option<models::Group>, DbError> {
+    use crate::schema::groups::dsl::*;
+
+    let inserted = diesel::insert_or_ignore_into(groups)
+        .values(&group)
+        .execute(conn)?;
+
+    Ok((inserted > 0).then_some(group))
+}
+
+/// Remove a group and its memberships. Its slides are kept, but no longer belong to a group.
+/// Returns false if there is no group with that id.
+pub fn remove_group(conn: &mut SqliteConnection, group: &str) -> Result<bool, DbError> {
+    use crate::schema::{group_members, groups, slides};
+
+    let removed = conn.transaction(|conn| {
+        diesel::delete(group_members::table.filter(group_members::group_id.eq(group)))
+            .execute(conn)?;
+        diesel::update(slides::table.filter(slides::group_id.eq(group)))
+            .set(slides::group_id.eq(None::<String>))
+            .execute(conn)?;
+        diesel::delete(groups::table.find(group))
+            .execute(conn)
+    })?;
+
+    Ok(removed > 0)
+}
+
+/// Add a user to a group, or change their role in it
+pub fn set_group_member(conn: &mut SqliteConnection, member: &models::GroupMember) -> Result<(), DbError> {
+    use crate::schema::group_members::dsl::*;
+
+    diesel::replace_into(group_members).values(member).execute(conn)?;
+
+    Ok(())
+}
+
+pub fn get_group_member(conn: &mut SqliteConnection, group: &str, user: &str) -> Result<Option<models::GroupMember>, DbError> {
+    use crate::schema::group_members::dsl::*;
+
+    let member = group_members.find((group, user)).first::<models::GroupMember>(conn).optional()?;
+
+    Ok(member)
+}
+
+/// Returns false if the user wasn't a member of the group
+pub fn remove_group_member(conn: &mut SqliteConnection, group: &str, user: &str) -> Result<bool, DbError> {
+    use crate::schema::group_members::dsl::*;
+
+    let removed = diesel::delete(group_members.find((group, user))).execute(conn)?;
+
+    Ok(removed > 0)
+}
+
+/// The role of the user with this email in a group. None if they aren't a member.
+pub fn get_group_role(conn: &mut SqliteConnection, group: &str, user_email: &str) -> Result<Option<Role>, DbError> {
+    use crate::schema::{group_members, users};
+
+    let group_role = group_members::table
+        .inner_join(users::table)
+        .filter(group_members::group_id.eq(group))
+        .filter(users::email.eq(user_email))
+        .select(group_members::role)
+        .first::<Role>(conn)
+        .optional()?;
+
+    Ok(group_role)
+}
+
+pub fn get_provisioning_domains(conn: &mut SqliteConnection) -> Result<Vec<models::ProvisioningDomain>, DbError> {
+    use crate::schema::provisioning_domains::dsl::*;
+
+    let domains = provisioning_domains.order(domain.asc()).load::<models::ProvisioningDomain>(conn)?;
+
+    Ok(domains)
+}
+
+pub fn get_provisioning_domain(conn: &mut SqliteConnection, name: &str) -> Result<Option<models::ProvisioningDomain>, DbError> {
+    use crate::schema::provisioning_domains::dsl::*;
+
+    let found = provisioning_domains.find(name).first::<models::ProvisioningDomain>(conn).optional()?;
+
+    Ok(found)
+}
+
+/// Add a provisioning domain, or change its role
+pub fn set_provisioning_domain(conn: &mut SqliteConnection, rule: &models::ProvisioningDomain) -> Result<(), DbError> {
+    use crate::schema::provisioning_domains::dsl::*;
+
+    diesel::replace_into(provisioning_domains).values(rule).execute(conn)?;
+
+    Ok(())
+}
+
+/// Returns false if there is no such provisioning domain
+pub fn remove_provisioning_domain(conn: &mut SqliteConnection, name: &str) -> Result<bool, DbError> {
+    use crate::schema::provisioning_domains::dsl::*;
+
+    let removed = diesel::delete(provisioning_domains.find(name)).execute(conn)?;
+
+    Ok(removed > 0)
+}
+
+pub fn get_provisioning_emails(conn: &mut SqliteConnection) -> Result<Vec<models::ProvisioningEmail>, DbError> {
+    use crate::schema::provisioning_emails::dsl::*;
+
+    let emails = provisioning_emails.order(email.asc()).load::<models::ProvisioningEmail>(conn)?;
+
+    Ok(emails)
+}
+
+pub fn get_provisioning_email(conn: &mut SqliteConnection, address: &str) -> Result<Option<models::ProvisioningEmail>, DbError> {
+    use crate::schema::provisioning_emails::dsl::*;
+
+    let found = provisioning_emails.find(address).first::<models::ProvisioningEmail>(conn).optional()?;
+
+    Ok(found)
+}
+
+/// Add an exception for an email, or change it
+pub fn set_provisioning_email(conn: &mut SqliteConnection, exception: &models::ProvisioningEmail) -> Result<(), DbError> {
+    use crate::schema::provisioning_emails::dsl::*;
+
+    diesel::replace_into(provisioning_emails).values(exception).execute(conn)?;
+
+    Ok(())
+}
+
+/// Returns false if there is no exception for that email
+pub fn remove_provisioning_email(conn: &mut SqliteConnection, address: &str) -> Result<bool, DbError> {
+    use crate::schema::provisioning_emails::dsl::*;
+
+    let removed = diesel::delete(provisioning_emails.find(address)).execute(conn)?;
+
+    Ok(removed > 0)
+}
+
+/// Add a user who logged in for the first time with a verified `user_email`, if the provisioning rules allow it.
+/// Returns None if they aren't allowed in.
+pub fn provision_user(conn: &mut SqliteConnection, user_email: &str) -> Result<Option<User>, DbError> {
+    let domains = get_provisioning_domains(conn)?;
+    let exception = get_provisioning_email(conn, &provisioning::normalize_email(user_email))?;
+    let Some(user_role) = provisioning::role_for(user_email, &domains, exception.as_ref()) else {
+        return Ok(None);
+    };
+
+    let user = insert_user(conn, User { id: Uuid::new_v4().into(), email: user_email.to_string(), role: user_role })?;
+
+    Ok(Some(user))
+}
+
+pub fn insert_invite(conn: &mut SqliteConnection, invite: &models::Invite) -> Result<(), DbError> {
+    use crate::schema::invites::dsl::*;
+
+    diesel::insert_into(invites).values(invite).execute(conn)?;
+
+    Ok(())
+}
+
+/// Invites that haven't been used and haven't expired, newest first
+pub fn get_pending_invites(conn: &mut SqliteConnection, now: NaiveDateTime) -> Result<Vec<models::Invite>, DbError> {
+    use crate::schema::invites::dsl::*;
+
+    let pending = invites
+        .filter(used_at.is_null())
+        .filter(expires_at.gt(now))
+        .order(created_at.desc())
+        .load::<models::Invite>(conn)?;
+
+    Ok(pending)
+}
+
+pub fn get_pending_invite(conn: &mut SqliteConnection, invite_id: &str, now: NaiveDateTime) -> Result<Option<models::Invite>, DbError> {
+    use crate::schema::invites::dsl::*;
+
+    let invite = invites
+        .filter(id.eq(invite_id))
+        .filter(used_at.is_null())
+        .filter(expires_at.gt(now))
+        .first::<models::Invite>(conn)
+        .optional()?;
+
+    Ok(invite)
+}
+
+/// Revoke an invite. Returns whether it existed.
+pub fn remove_invite(conn: &mut SqliteConnection, invite_id: &str) -> Result<bool, DbError> {
+    use crate::schema::invites::dsl::*;
+
+    let removed = diesel::delete(invites.filter(id.eq(invite_id))).execute(conn)?;
+
+    Ok(removed > 0)
+}
+
+/// Add the user `user_email` with the role of the pending invite with this token hash, and mark the invite as used by them.
+/// Returns the user and the invite, or None if there is no such invite (or it has expired or been used).
+/// If the user already exists, they are returned as they are and the invite stays unused.
+pub fn accept_invite(conn: &mut SqliteConnection, hash: &str, user_email: &str, now: NaiveDateTime)
+-> Result<Option<(User, models::Invite)>, DbError> {
+    // Immediate, so two logins with the same invite can't both use it
+    conn.immediate_transaction(|conn| {
+        use crate::schema::invites::dsl::*;
+
+        let Some(mut invite) = invites
+            .filter(token_hash.eq(hash))
+            .filter(used_at.is_null())
+            .filter(expires_at.gt(now))
+            .first::<models::Invite>(conn)
+            .optional()? else {
+            return Ok(None);
+        };
+
+        let existing = {
+            use crate::schema::users::dsl::*;
+            users.filter(email.eq(user_email)).first::<User>(conn).optional()?
+        };
+        if let Some(user) = existing {
+            return Ok(Some((user, invite)));
+        }
+
+        let user = insert_user(conn, User { id: Uuid::new_v4().into(), email: user_email.to_string(), role: invite.role })?;
+        diesel::update(invites.filter(id.eq(&invite.id)))
+            .set((used_by.eq(user_email), used_at.eq(now)))
+            .execute(conn)?;
+        invite.used_by = Some(user_email.to_string());
+        invite.used_at = Some(now);
+
+        Ok(Some((user, invite)))
+    })
+}
+
+/// Insert a new session. Expired sessions are removed first, so they don't pile up.
+pub fn insert_session(conn: &mut SqliteConnection, session: &models::Session) -> Result<(), DbError> {
+    use crate::schema::sessions::dsl::*;
+
+    diesel::delete(sessions.filter(expires_at.le(session.created_at))).execute(conn)?;
+    diesel::insert_into(sessions).values(session).execute(conn)?;
+
+    Ok(())
+}
+
+/// The session with this key hash, unless it has expired
+pub fn get_session_by_key_hash(conn: &mut SqliteConnection, hash: &str, now: NaiveDateTime) -> Result<Option<models::Session>, DbError> {
+    use crate::schema::sessions::dsl::*;
+
+    let session = sessions
+        .filter(key_hash.eq(hash))
+        .filter(expires_at.gt(now))
+        .first::<models::Session>(conn)
+        .optional()?;
+
+    Ok(session)
+}
+
+/// Replace the state of a session. `email` and `user_agent` are copied from the state.
+pub fn update_session_state(
+    conn: &mut SqliteConnection,
+    hash: &str,
+    new_state: &str,
+    new_email: Option<&str>,
+    new_user_agent: Option<&str>,
+    now: NaiveDateTime,
+    new_expires_at: NaiveDateTime,
+) -> Result<(), DbError> {
+    use crate::schema::sessions::dsl::*;
+
+    diesel::update(sessions.filter(key_hash.eq(hash)))
+        .set((
+            state.eq(new_state),
+            email.eq(new_email),
+            user_agent.eq(new_user_agent),
+            last_seen_at.eq(now),
+            expires_at.eq(new_expires_at),
+        ))
+        .execute(conn)?;
+
+    Ok(())
+}
 
-use crate::models::User;
-use crate::auth::PermissionLevel;
-use diesel::SqliteConnection;
+/// Mark a session as used right now, and move its expiry
+pub fn touch_session(conn: &mut SqliteConnection, hash: &str, now: NaiveDateTime, new_expires_at: NaiveDateTime) -> Result<(), DbError> {
+    use crate::schema::sessions::dsl::*;
 
-type DbError = Box<dyn std::error::Error + Send + Sync>;
+    diesel::update(sessions.filter(key_hash.eq(hash)))
+        .set((last_seen_at.eq(now), expires_at.eq(new_expires_at)))
+        .execute(conn)?;
 
-pub fn get_all_slides(
-    conn: &mut SqliteConnection,
-) -> Result<Vec<Slide>, DbError> {
-    use crate::schema::slides::dsl::*;
+    Ok(())
+}
 
-    let all_slides = slides.load::<Slide>(conn)?;
+pub fn delete_session_by_key_hash(conn: &mut SqliteConnection, hash: &str) -> Result<(), DbError> {
+    use crate::schema::sessions::dsl::*;
 
-    Ok(all_slides)
+    diesel::delete(sessions.filter(key_hash.eq(hash))).execute(conn)?;
+
+    Ok(())
 }
 
-pub fn insert_slide(
+/// Sessions someone is logged in with that haven't expired, most recently used first.
+/// Only those of the user with `user_email` if it is Some.
+pub fn get_active_sessions(conn: &mut SqliteConnection, user_email: Option<&str>, now: NaiveDateTime) -> Result<Vec<models::Session>, DbError> {
+    use crate::schema::sessions::dsl::*;
+
+    let mut query = sessions
+        .filter(email.is_not_null())
+        .filter(expires_at.gt(now))
+        .into_boxed();
+    if let Some(user_email) = user_email {
+        query = query.filter(email.eq(user_email));
+    }
+
+    let active = query.order(last_seen_at.desc()).load::<models::Session>(conn)?;
+
+    Ok(active)
+}
+
+pub fn get_session(conn: &mut SqliteConnection, session_id: &str) -> Result<Option<models::Session>, DbError> {
+    use crate::schema::sessions::dsl::*;
+
+    let session = sessions.find(session_id).first::<models::Session>(conn).optional()?;
+
+    Ok(session)
+}
+
+/// Returns false if there is no session with that id
+pub fn remove_session(conn: &mut SqliteConnection, session_id: &str) -> Result<bool, DbError> {
+    use crate::schema::sessions::dsl::*;
+
+    let removed = diesel::delete(sessions.find(session_id)).execute(conn)?;
+
+    Ok(removed > 0)
+}
+
+/// Insert a new (pending) device. Expired pairing requests are removed first so their codes can be reused.
+/// Returns None without inserting it if there are already `max_pending` pairing requests.
+pub fn insert_device(conn: &mut SqliteConnection, device: models::Device, now: NaiveDateTime, max_pending: i64)
+-> Result<Option<models::Device>, DbError> {
+    use crate::schema::devices::dsl::*;
+
+    // Immediate, so concurrent requests can't all get past the limit
+    let inserted = conn.immediate_transaction(|conn| {
+        diesel::delete(devices.filter(screen_id.is_null()).filter(pairing_expires_at.le(now)))
+            .execute(conn)?;
+        let pending: i64 = devices.filter(screen_id.is_null()).filter(revoked_at.is_null()).count().get_result(conn)?;
+        if pending >= max_pending {
+            return Ok::<_, diesel::result::Error>(false);
+        }
+        diesel::insert_into(devices)
+            .values(&device)
+            .execute(conn)?;
+        Ok(true)
+    })?;
+
+    Ok(inserted.then_some(device))
+}
+
+/// Get the device with a token hash, unless it is revoked or an expired pairing request
+pub fn get_device_by_token_hash(conn: &mut SqliteConnection, hash: &str, now: NaiveDateTime) -> Result<Option<models::Device>, DbError> {
+    use crate::schema::devices::dsl::*;
+
+    let device = devices
+        .filter(token_hash.eq(hash))
+        .filter(revoked_at.is_null())
+        .filter(screen_id.is_not_null().or(pairing_expires_at.gt(now)))
+        .first::<models::Device>(conn)
+        .optional()?;
+
+    Ok(device)
+}
+
+/// Approve the pending device showing `code` and pair it to a screen
+/// Returns None if no pending device has that code
+pub fn approve_device(
     conn: &mut SqliteConnection,
-    slide: models::Slide,
-) -> Result<models::Slide, DbError> {
-    use crate::schema::slides::dsl::*;
+    code: &str,
+    screen: &str,
+    device_name: &str,
+    caller_email: &str,
+    now: NaiveDateTime,
+) -> Result<Option<models::Device>, DbError> {
+    use crate::schema::devices::dsl::*;
 
-    diesel::insert_into(slides)
-        .values(&slide)
+    let Some(pending) = devices
+        .filter(pairing_code.eq(code))
+        .filter(pairing_expires_at.gt(now))
+        .filter(screen_id.is_null())
+        .filter(revoked_at.is_null())
+        .first::<models::Device>(conn)
+        .optional()? else {
+        return Ok(None);
+    };
+
+    diesel::update(devices.find(&pending.id))
+        .set((
+            pairing_code.eq(None::<String>),
+            pairing_expires_at.eq(None::<NaiveDateTime>),
+            name.eq(device_name),
+            screen_id.eq(screen),
+            approved_by.eq(caller_email),
+            approved_at.eq(now),
+        ))
         .execute(conn)?;
 
-    Ok(slide)
+    let device = devices.find(&pending.id).first::<models::Device>(conn)?;
+
+    Ok(Some(device))
 }
 
-/// Insert a user into the database and return it
-pub fn insert_user(conn: &mut SqliteConnection, user: User) -> Result<User, DbError> {
-    use crate::schema::users::dsl::*;
+/// Get every device that is paired or waiting to be, including revoked ones
+pub fn get_all_devices(conn: &mut SqliteConnection) -> Result<Vec<models::Device>, DbError> {
+    use crate::schema::devices::dsl::*;
 
-    // Check if the user already exists
-    let existing_user = users
-        .filter(email.eq(user.email.clone()))
-        .first::<User>(conn)
-        .optional()?;
+    let all_devices = devices
+        .order(created_at.desc())
+        .load::<models::Device>(conn)?;
 
-    // If the user already exists, return it instead
-    if let Some(user) = existing_user {
-        return Ok(user);
-    }
+    Ok(all_devices)
+}
 
-    diesel::insert_into(users)
-        .values(&user)
-        .on_conflict(email)
-        .do_nothing()
+/// Revoke a device so its token stops working. Returns false if there is no such device that isn't already revoked.
+pub fn revoke_device(conn: &mut SqliteConnection, device_id: &str, now: NaiveDateTime) -> Result<bool, DbError> {
+    use crate::schema::devices::dsl::*;
+
+    let revoked = diesel::update(devices.find(device_id).filter(revoked_at.is_null()))
+        .set((revoked_at.eq(now), pairing_code.eq(None::<String>)))
         .execute(conn)?;
 
-    Ok(user)
+    Ok(revoked > 0)
 }
 
-pub fn remove_user(conn: &mut SqliteConnection, user_id: &str) -> Result<(), DbError> {
-    use crate::schema::users::dsl::*;
+/// Store the latest heartbeat from a device, replacing the previous one
+pub fn upsert_device_status(conn: &mut SqliteConnection, status: &models::DeviceStatus) -> Result<(), DbError> {
+    use crate::schema::device_status::dsl::*;
 
-    diesel::delete(users.filter(id.eq(user_id)))        
+    diesel::insert_into(device_status)
+        .values(status)
+        .on_conflict(device_id)
+        .do_update()
+        .set(status)
         .execute(conn)?;
 
     Ok(())
 }
-pub fn pop_slide(
+
+/// Get every paired, non-revoked device along with its latest heartbeat (if it has sent one)
+pub fn get_paired_devices_with_status(
     conn: &mut SqliteConnection,
-    uuid: &Uuid,
-) -> Result<Slide,DbError> {
-    use crate::schema::slides::dsl::*;
+) -> Result<Vec<(models::Device, Option<models::DeviceStatus>)>, DbError> {
+    use crate::schema::{devices, device_status};
 
-    // Get slide
-    let slide = slides
-        .find(uuid.to_string())
-        .first(conn)?;
-    
-    // Delete slide
-    diesel::delete(slides.filter(id.eq(uuid.to_string())))
+    let rows = devices::table
+        .left_join(device_status::table)
+        .filter(devices::screen_id.is_not_null())
+        .filter(devices::revoked_at.is_null())
+        .order(devices::name.asc())
+        .select((models::Device::as_select(), Option::<models::DeviceStatus>::as_select()))
+        .load::<(models::Device, Option<models::DeviceStatus>)>(conn)?;
+
+    Ok(rows)
+}
+
+pub fn insert_screen_command(conn: &mut SqliteConnection, screen_command: models::ScreenCommand) -> Result<models::ScreenCommand, DbError> {
+    use crate::schema::screen_commands::dsl::*;
+
+    diesel::insert_into(screen_commands)
+        .values(&screen_command)
         .execute(conn)?;
 
-    Ok(slide)
+    Ok(screen_command)
 }
 
-/// Takes an `UpdateSlide` and updates every field that is Some(_)
-pub fn update_slide(
+/// Get the commands for a screen that haven't been acknowledged or expired, oldest first
+pub fn get_pending_screen_commands(conn: &mut SqliteConnection, screen: &str, now: NaiveDateTime) -> Result<Vec<models::ScreenCommand>, DbError> {
+    use crate::schema::screen_commands::dsl::*;
+
+    let pending = screen_commands
+        .filter(screen_id.eq(screen))
+        .filter(acknowledged_at.is_null())
+        .filter(expires_at.gt(now))
+        .order(issued_at.asc())
+        .load::<models::ScreenCommand>(conn)?;
+
+    Ok(pending)
+}
+
+/// Get the latest commands for a screen, newest first
+pub fn get_screen_command_history(conn: &mut SqliteConnection, screen: &str, limit: i64) -> Result<Vec<models::ScreenCommand>, DbError> {
+    use crate::schema::screen_commands::dsl::*;
+
+    let history = screen_commands
+        .filter(screen_id.eq(screen))
+        .order(issued_at.desc())
+        .limit(limit)
+        .load::<models::ScreenCommand>(conn)?;
+
+    Ok(history)
+}
+
+/// Mark a pending command for a screen as acknowledged by a device
+/// Returns false if there is no such command, or it was already acknowledged
+pub fn acknowledge_screen_command(
     conn: &mut SqliteConnection,
-    update_slide: models::UpdateSlide,
-) -> Result<(), DbError> {
-    use crate::schema::slides::dsl::*;
+    command_id: &str,
+    screen: &str,
+    device: &str,
+    was_successful: bool,
+    message: Option<&str>,
+    now: NaiveDateTime,
+) -> Result<bool, DbError> {
+    use crate::schema::screen_commands::dsl::*;
 
-    diesel::update(slides.find(update_slide.id))
-        .set(update_slide)
+    let updated = diesel::update(screen_commands
+            .find(command_id)
+            .filter(screen_id.eq(screen))
+            .filter(acknowledged_at.is_null()))
+        .set((
+            acknowledged_at.eq(now),
+            acknowledged_by.eq(device),
+            success.eq(was_successful),
+            result_message.eq(message),
+        ))
         .execute(conn)?;
 
+    Ok(updated > 0)
+}
+
+pub fn insert_play_events(conn: &mut SqliteConnection, events: &[models::NewPlayEvent]) -> Result<usize, DbError> {
+    use crate::schema::play_events::dsl::*;
+
+    let inserted = diesel::insert_into(play_events)
+        .values(events)
+        .execute(conn)?;
+
+    Ok(inserted)
+}
+
+pub fn insert_audit_event(conn: &mut SqliteConnection, event: &models::NewAuditEvent) -> Result<(), DbError> {
+    use crate::schema::audit_events::dsl::*;
+
+    diesel::insert_into(audit_events).values(event).execute(conn)?;
+
     Ok(())
 }
 
-pub fn get_all_users(
+/// Audit events matching the query, newest first. At most `limit` of them.
+pub fn get_audit_events(conn: &mut SqliteConnection, query: &AuditQuery, limit: i64) -> Result<Vec<models::AuditEvent>, DbError> {
+    use crate::schema::audit_events::dsl::*;
+
+    let mut events = audit_events.into_boxed();
+    if let Some(ref query_actor) = query.actor {
+        events = events.filter(actor.eq(query_actor));
+    }
+    if let Some(ref query_action) = query.action {
+        events = events.filter(action.eq(query_action));
+    }
+    if let Some(ref query_target) = query.target_id {
+        events = events.filter(target_id.eq(query_target));
+    }
+    if let Some(from) = query.from {
+        events = events.filter(created_at.ge(from));
+    }
+    if let Some(to) = query.to {
+        events = events.filter(created_at.lt(to));
+    }
+    if let Some(before_id) = query.before_id {
+        events = events.filter(id.lt(before_id));
+    }
+
+    let events = events
+        .order(id.desc())
+        .limit(limit)
+        .load::<models::AuditEvent>(conn)?;
+
+    Ok(events)
+}
+
+/// Count impressions and total on-screen time per slide, day and screen for plays started in `[from, to)`.
+/// `slide` and `screen` narrow it down to one slide or screen.
+pub fn get_impressions(
     conn: &mut SqliteConnection,
-) -> Result<Vec<User>, DbError> {
-    use crate::schema::users::dsl::*;
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+    slide: Option<&str>,
+    screen: Option<&str>,
+) -> Result<Vec<models::Impressions>, DbError> {
+    use diesel::sql_types::{Nullable, Text, Timestamp};
 
-    let all_users = users.load::<User>(conn)?;
+    // Grouping by an expression is a lot clearer in plain SQL than in the query builder
+    let impressions = diesel::sql_query(
+        "SELECT p.slide_id, MAX(s.caption) AS caption, date(p.started_at) AS day, p.screen_id,
+                COUNT(*) AS impressions, CAST(SUM(p.duration_ms) / 1000 AS BIGINT) AS total_seconds
+         FROM play_events p
+         LEFT JOIN slides s ON s.id = p.slide_id
+         WHERE p.started_at >= ? AND p.started_at < ?
+           AND (? IS NULL OR p.slide_id = ?)
+           AND (? IS NULL OR p.screen_id = ?)
+         GROUP BY p.slide_id, day, p.screen_id
+         ORDER BY day, p.slide_id, p.screen_id")
+        .bind::<Timestamp, _>(from)
+        .bind::<Timestamp, _>(to)
+        .bind::<Nullable<Text>, _>(slide)
+        .bind::<Nullable<Text>, _>(slide)
+        .bind::<Nullable<Text>, _>(screen)
+        .bind::<Nullable<Text>, _>(screen)
+        .load::<models::Impressions>(conn)?;
 
-    Ok(all_users)
+    Ok(impressions)
 }
 
-/// Check a user against the database
-/// Returns None if the user does not exist, otherwise a PermissionLevel
-pub fn check_user(conn: &mut SqliteConnection, email_str: &str) -> Result<Option<PermissionLevel>, DbError> {
-    use crate::schema::users::dsl::*;
+/// Insert a new override. It supersedes any override that is already active.
+pub fn insert_override(conn: &mut SqliteConnection, new_override: models::Override) -> Result<models::Override, DbError> {
+    use crate::schema::overrides::dsl::*;
 
-    let user = users
-        .filter(email.eq(email_str.to_owned()))
-        .first::<models::User>(conn)
+    diesel::insert_into(overrides)
+        .values(&new_override)
+        .execute(conn)?;
+
+    Ok(new_override)
+}
+
+/// Get the override that is currently shown on the screens, if any.
+/// That is the most recently created one that is neither cleared nor expired.
+pub fn get_active_override(conn: &mut SqliteConnection, now: NaiveDateTime) -> Result<Option<models::Override>, DbError> {
+    use crate::schema::overrides::dsl::*;
+
+    let active = overrides
+        .filter(cleared_at.is_null())
+        .filter(expires_at.gt(now))
+        .order(created_at.desc())
+        .first::<models::Override>(conn)
         .optional()?;
 
-    Ok(
-        match user {    
-            Some(User { admin: true, .. }) => Some(PermissionLevel::Admin),
-            Some(User { admin: false, .. }) => Some(PermissionLevel::User),
-            None => None,
-        }
-    )
+    Ok(active)
 }
 
-pub fn get_settings(conn: &mut SqliteConnection) -> Result<models::Settings, DbError> {
-    use crate::schema::settings::dsl::*;
+/// Clear every active override and return the one that was being shown
+/// Returns None if no override was active
+pub fn clear_override(conn: &mut SqliteConnection, caller_email: &str, now: NaiveDateTime) -> Result<Option<models::Override>, DbError> {
+    use crate::schema::overrides::dsl::*;
+
+    let Some(active) = get_active_override(conn, now)? else {
+        return Ok(None);
+    };
+
+    diesel::update(overrides.filter(cleared_at.is_null()).filter(expires_at.gt(now)))
+        .set((cleared_by.eq(caller_email), cleared_at.eq(now)))
+        .execute(conn)?;
+
+    Ok(Some(active))
+}
+
+/// Get every override ever activated, newest first
+pub fn get_all_overrides(conn: &mut SqliteConnection) -> Result<Vec<models::Override>, DbError> {
+    use crate::schema::overrides::dsl::*;
 
-    log::info!("Fetching settings from database");
-    let setting = settings.first::<models::Settings>(conn);
-    log::info!("Fetched settings from database: {:?}", setting);
-    let setting = setting?;
+    let all_overrides = overrides
+        .order(created_at.desc())
+        .load::<models::Override>(conn)?;
 
-    log::info!("Fetched settings from database: {:?}", setting);
-    Ok(setting)
+    Ok(all_overrides)
 }
 
 #[cfg(test)]
 mod tests {
-    use chrono::NaiveDateTime;
+    use chrono::{TimeDelta, Utc};
+    use diesel_migrations::MigrationHarness;
 
     use super::*;
 
@@ -153,17 +1490,31 @@ mod tests {
                 .expect("Invalid datetime"),
             active: true,
             filetype: "png".to_string(),
+            created_by: None,
+            group_id: None,
+            status: SlideStatus::Approved,
         }
     }
 
     fn init_test_db() -> SqliteConnection {
         let mut conn = SqliteConnection::establish(":memory:").unwrap();
-        let slides_migration = 
-            include_str!("../migrations/2024-12-12-181858_create_slides/up.sql");
-        diesel::sql_query(slides_migration).execute(&mut conn).unwrap();
+        conn.run_pending_migrations(crate::MIGRATIONS).unwrap();
         conn
     }
 
+    fn new_test_override(message: &str, created_at: NaiveDateTime, expires_at: NaiveDateTime) -> models::Override {
+        models::Override {
+            id: Uuid::new_v4().to_string(),
+            message: Some(message.to_string()),
+            slide_id: None,
+            created_by: "admin@example.com".to_string(),
+            created_at,
+            expires_at,
+            cleared_by: None,
+            cleared_at: None,
+        }
+    }
+
     #[test]
     fn test_get_all_slides() {
         let mut conn = init_test_db();
@@ -191,10 +1542,13 @@ mod tests {
 
         insert_slide(&mut conn, slide.clone()).unwrap();
         let popped_slide = pop_slide(&mut conn, &Uuid::parse_str(&slide.id).unwrap()).unwrap();
-        assert_eq!(popped_slide, slide);
+        assert_eq!(popped_slide, Some(slide));
 
         let slides = get_all_slides(&mut conn).unwrap();
         assert!(slides.is_empty());
+
+        // Popping a slide that doesn't exist gives None
+        assert_eq!(pop_slide(&mut conn, &Uuid::new_v4()).unwrap(), None);
     }
 
     #[test]
@@ -220,4 +1574,387 @@ mod tests {
         
         assert_eq!(updated_slide, slide);
     }
+
+    #[test]
+    fn test_admin_flag_becomes_role() {
+        use diesel::migration::{MigrationConnection, MigrationSource};
+
+        // Run the migrations one at a time, so that there is a user from before roles
+        let mut conn = SqliteConnection::establish(":memory:").unwrap();
+        conn.setup().unwrap();
+        for migration in MigrationSource::<diesel::sqlite::Sqlite>::migrations(&crate::MIGRATIONS).unwrap() {
+            if migration.name().to_string().ends_with("_user_roles") {
+                diesel::sql_query("INSERT INTO users (id, email, admin) VALUES ('1', 'admin@example.com', 1), ('2', 'user@example.com', 0)")
+                    .execute(&mut conn).unwrap();
+            }
+            conn.run_migration(&migration).unwrap();
+        }
+
+        assert_eq!(check_user(&mut conn, "admin@example.com").unwrap(), Some(Role::Admin));
+        assert_eq!(check_user(&mut conn, "user@example.com").unwrap(), Some(Role::SlideEditor));
+        assert_eq!(check_user(&mut conn, "nobody@example.com").unwrap(), None);
+    }
+
+    #[test]
+    fn test_last_admin_is_kept() {
+        let conn = &mut init_test_db();
+        let mut user = |email: &str, role: Role| insert_user(conn, User { id: Uuid::new_v4().into(), email: email.to_string(), role }).unwrap();
+        let (first, second) = (user("first@example.com", Role::Admin), user("second@example.com", Role::Admin));
+
+        assert_eq!(set_user_role(conn, &first.id, Role::Viewer).unwrap().unwrap().role, Role::Admin);
+        assert_eq!(set_user_role(conn, &second.id, Role::Viewer).unwrap().unwrap_err(), UserChangeDenied::LastAdmin);
+        assert_eq!(remove_user(conn, &second.id).unwrap().unwrap_err(), UserChangeDenied::LastAdmin);
+        // Keeping the last admin an admin is fine
+        assert!(set_user_role(conn, &second.id, Role::Admin).unwrap().is_ok());
+
+        assert_eq!(remove_user(conn, &first.id).unwrap().unwrap().email, "first@example.com");
+        assert_eq!(remove_user(conn, &first.id).unwrap().unwrap_err(), UserChangeDenied::NotFound);
+        assert_eq!(check_user(conn, "second@example.com").unwrap(), Some(Role::Admin));
+    }
+
+    #[test]
+    fn test_provision_user() {
+        let conn = &mut init_test_db();
+        let now = Utc::now().naive_utc();
+        set_provisioning_domain(conn, &models::ProvisioningDomain {
+            domain: "f.kth.se".to_string(),
+            role: Role::Viewer,
+            created_by: "admin@example.com".to_string(),
+            created_at: now,
+        }).unwrap();
+        let exception = |email: &str, role| models::ProvisioningEmail {
+            email: email.to_string(),
+            role,
+            created_by: "admin@example.com".to_string(),
+            created_at: now,
+        };
+        set_provisioning_email(conn, &exception("banned@f.kth.se", None)).unwrap();
+        set_provisioning_email(conn, &exception("guest@gmail.com", Some(Role::SlideEditor))).unwrap();
+
+        assert_eq!(provision_user(conn, "member@f.kth.se").unwrap().unwrap().role, Role::Viewer);
+        assert_eq!(check_user(conn, "member@f.kth.se").unwrap(), Some(Role::Viewer));
+        assert!(provision_user(conn, "Banned@f.kth.se").unwrap().is_none());
+        assert!(provision_user(conn, "someone@gmail.com").unwrap().is_none());
+        assert_eq!(provision_user(conn, "guest@gmail.com").unwrap().unwrap().role, Role::SlideEditor);
+
+        // Changing a rule replaces it, and removing it stops new users from being added
+        set_provisioning_email(conn, &exception("banned@f.kth.se", Some(Role::Viewer))).unwrap();
+        assert_eq!(get_provisioning_emails(conn).unwrap().len(), 2);
+        assert!(remove_provisioning_domain(conn, "f.kth.se").unwrap());
+        assert!(!remove_provisioning_domain(conn, "f.kth.se").unwrap());
+        assert!(provision_user(conn, "other@f.kth.se").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_remove_group() {
+        let mut conn = init_test_db();
+        let now = chrono::Utc::now().naive_utc();
+
+        let group = insert_group(&mut conn, models::Group { id: "fkm".to_string(), name: "FKM".to_string(), created_at: now }).unwrap().unwrap();
+        // Names are unique
+        assert!(insert_group(&mut conn, models::Group { id: "fkm2".to_string(), ..group.clone() }).unwrap().is_none());
+
+        let user = insert_user(&mut conn, User { id: "1".to_string(), email: "fkm@example.com".to_string(), role: Role::Viewer }).unwrap();
+        set_group_member(&mut conn, &models::GroupMember { group_id: group.id.clone(), user_id: user.id.clone(), role: Role::SlideEditor }).unwrap();
+        assert_eq!(get_group_role(&mut conn, &group.id, "fkm@example.com").unwrap(), Some(Role::SlideEditor));
+        assert_eq!(get_group_role(&mut conn, &group.id, "other@example.com").unwrap(), None);
+
+        let slide = insert_slide(&mut conn, Slide { group_id: Some(group.id.clone()), ..new_test_slide() }).unwrap();
+
+        assert!(remove_group(&mut conn, &group.id).unwrap());
+        assert!(!remove_group(&mut conn, &group.id).unwrap());
+        assert!(get_group_members(&mut conn).unwrap().is_empty());
+        // The slide is kept without a group
+        assert_eq!(get_slide(&mut conn, &slide.id).unwrap().unwrap().group_id, None);
+    }
+
+    #[test]
+    fn test_active_override() {
+        let mut conn = init_test_db();
+        let now = NaiveDateTime::parse_from_str("2025-10-20 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        assert_eq!(get_active_override(&mut conn, now).unwrap(), None);
+
+        let first = new_test_override("Fire drill", now, now + TimeDelta::hours(1));
+        insert_override(&mut conn, first.clone()).unwrap();
+        assert_eq!(get_active_override(&mut conn, now).unwrap(), Some(first.clone()));
+
+        // A newer override takes precedence over an older one
+        let second = new_test_override("Event cancelled", now + TimeDelta::minutes(5), now + TimeDelta::hours(2));
+        insert_override(&mut conn, second.clone()).unwrap();
+        let later = now + TimeDelta::minutes(10);
+        assert_eq!(get_active_override(&mut conn, later).unwrap(), Some(second.clone()));
+
+        // Expired overrides are not active
+        assert_eq!(get_active_override(&mut conn, now + TimeDelta::hours(3)).unwrap(), None);
+
+        // Clearing removes every active override, not just the newest
+        let cleared = clear_override(&mut conn, "other@example.com", later).unwrap();
+        assert_eq!(cleared, Some(second));
+        assert_eq!(get_active_override(&mut conn, later).unwrap(), None);
+        assert_eq!(clear_override(&mut conn, "other@example.com", later).unwrap(), None);
+
+        // Both are still in the log, newest first
+        let log = get_all_overrides(&mut conn).unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0].cleared_by.as_deref(), Some("other@example.com"));
+        assert_eq!(log[1].id, first.id);
+    }
+
+    fn new_test_screen(screen_name: &str) -> models::Screen {
+        models::Screen {
+            id: Uuid::new_v4().to_string(),
+            name: screen_name.to_string(),
+            layout_type: None,
+            color_mode: None,
+        }
+    }
+
+    #[test]
+    fn test_slides_for_screen() {
+        let mut conn = init_test_db();
+        let konsulatet = insert_screen(&mut conn, new_test_screen("Konsulatet")).unwrap();
+        let entrance = insert_screen(&mut conn, new_test_screen("Entrance")).unwrap();
+
+        let everywhere = insert_slide(&mut conn, new_test_slide()).unwrap();
+        let only_entrance = insert_slide(&mut conn, new_test_slide()).unwrap();
+        set_slide_screens(&mut conn, &only_entrance.id, std::slice::from_ref(&entrance.id)).unwrap();
+
+        let ids = |slides: Vec<Slide>| slides.into_iter().map(|s| s.id).collect::<Vec<_>>();
+        assert_eq!(ids(get_slides_for_screen(&mut conn, Some(&konsulatet.id)).unwrap()), vec![everywhere.id.clone()]);
+        assert_eq!(get_slides_for_screen(&mut conn, Some(&entrance.id)).unwrap().len(), 2);
+        assert_eq!(ids(get_slides_for_screen(&mut conn, None).unwrap()), vec![everywhere.id.clone()]);
+        assert_eq!(get_slide_screens(&mut conn, &only_entrance.id).unwrap(), vec![entrance.id.clone()]);
+
+        // Removing the screen also removes the assignment, so the slide is shown everywhere again
+        assert!(remove_screen(&mut conn, &entrance.id).unwrap());
+        assert!(!remove_screen(&mut conn, &entrance.id).unwrap());
+        assert_eq!(get_slides_for_screen(&mut conn, Some(&konsulatet.id)).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_setting_values() {
+        let mut conn = init_test_db();
+        let interval = |conn: &mut SqliteConnection| get_setting_values(conn).unwrap().get("slide_interval_seconds").clone();
+        assert_eq!(interval(&mut conn), serde_json::json!(3));
+
+        set_setting_value(&mut conn, "slide_interval_seconds", &serde_json::json!(10)).unwrap();
+        set_setting_value(&mut conn, "slide_interval_seconds", &serde_json::json!(20)).unwrap();
+        assert_eq!(interval(&mut conn), serde_json::json!(20));
+
+        remove_setting_value(&mut conn, "slide_interval_seconds").unwrap();
+        assert_eq!(interval(&mut conn), serde_json::json!(3));
+    }
+
+    #[test]
+    fn test_no_slides_during_quiet_hours() {
+        let mut conn = init_test_db();
+        let now = NaiveDateTime::parse_from_str("2025-12-24 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let slide = insert_slide(&mut conn, new_test_slide()).unwrap();
+
+        let quiet_hours = QuietHours {
+            windows: Vec::new(),
+            exceptions: vec![models::QuietHoursException {
+                date: now.and_utc().with_timezone(&Local).date_naive(),
+                quiet: true,
+                name: "Julafton".to_string(),
+            }],
+        };
+        set_quiet_hours(&mut conn, &quiet_hours).unwrap();
+        assert_eq!(get_quiet_hours(&mut conn).unwrap(), quiet_hours);
+        assert!(get_current_slides_for_screen(&mut conn, None, now).unwrap().is_empty());
+
+        // Overrides are still shown
+        let mut active = new_test_override("Fire drill", now, now + TimeDelta::hours(1));
+        active.slide_id = Some(slide.id.clone());
+        insert_override(&mut conn, active).unwrap();
+        assert_eq!(get_current_slides_for_screen(&mut conn, None, now).unwrap(), vec![slide.clone()]);
+        clear_override(&mut conn, "admin@example.com", now).unwrap();
+
+        set_quiet_hours(&mut conn, &QuietHours::default()).unwrap();
+        assert_eq!(get_current_slides_for_screen(&mut conn, None, now).unwrap(), vec![slide]);
+    }
+
+    #[test]
+    fn test_screen_sl_sites() {
+        let mut conn = init_test_db();
+        let screen = insert_screen(&mut conn, new_test_screen("Konsulatet")).unwrap();
+
+        set_screen_sl_sites(&mut conn, &screen.id, &[9600, 9204, 9600]).unwrap();
+        assert_eq!(get_screen_sl_sites(&mut conn, &screen.id).unwrap(), vec![9204, 9600]);
+
+        set_screen_sl_sites(&mut conn, &screen.id, &[]).unwrap();
+        assert!(get_screen_sl_sites(&mut conn, &screen.id).unwrap().is_empty());
+    }
+
+    fn new_test_device(hash: &str, code: &str, now: NaiveDateTime) -> models::Device {
+        models::Device {
+            id: Uuid::new_v4().to_string(),
+            token_hash: hash.to_string(),
+            pairing_code: Some(code.to_string()),
+            pairing_expires_at: Some(now + TimeDelta::minutes(15)),
+            name: None,
+            screen_id: None,
+            approved_by: None,
+            created_at: now,
+            approved_at: None,
+            revoked_at: None,
+        }
+    }
+
+    #[test]
+    fn test_device_pairing() {
+        let mut conn = init_test_db();
+        let now = NaiveDateTime::parse_from_str("2025-10-24 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let screen = insert_screen(&mut conn, new_test_screen("Konsulatet")).unwrap();
+
+        let pending = insert_device(&mut conn, new_test_device("hash", "ABC234", now), now, 10).unwrap().unwrap();
+        assert_eq!(get_device_by_token_hash(&mut conn, "hash", now).unwrap(), Some(pending.clone()));
+        assert_eq!(get_device_by_token_hash(&mut conn, "other", now).unwrap(), None);
+
+        // Wrong codes don't approve anything
+        assert_eq!(approve_device(&mut conn, "XXXXXX", &screen.id, "Kiosk", "admin@example.com", now).unwrap(), None);
+
+        let paired = approve_device(&mut conn, "ABC234", &screen.id, "Kiosk", "admin@example.com", now).unwrap().unwrap();
+        assert_eq!(paired.screen_id.as_deref(), Some(screen.id.as_str()));
+        assert_eq!(paired.pairing_code, None);
+
+        // A paired device doesn't expire, but a revoked one stops working
+        let much_later = now + TimeDelta::days(365);
+        assert!(get_device_by_token_hash(&mut conn, "hash", much_later).unwrap().is_some());
+        assert!(revoke_device(&mut conn, &paired.id, much_later).unwrap());
+        assert!(!revoke_device(&mut conn, &paired.id, much_later).unwrap());
+        assert_eq!(get_device_by_token_hash(&mut conn, "hash", much_later).unwrap(), None);
+    }
+
+    #[test]
+    fn test_expired_pairing_request() {
+        let mut conn = init_test_db();
+        let now = NaiveDateTime::parse_from_str("2025-10-24 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let screen = insert_screen(&mut conn, new_test_screen("Konsulatet")).unwrap();
+        insert_device(&mut conn, new_test_device("hash", "ABC234", now), now, 10).unwrap().unwrap();
+
+        let later = now + TimeDelta::hours(1);
+        assert_eq!(get_device_by_token_hash(&mut conn, "hash", later).unwrap(), None);
+        assert_eq!(approve_device(&mut conn, "ABC234", &screen.id, "Kiosk", "admin@example.com", later).unwrap(), None);
+
+        // The expired request is cleaned up when a new one comes in, so the code can be reused
+        insert_device(&mut conn, new_test_device("hash2", "ABC234", later), later, 10).unwrap().unwrap();
+        assert_eq!(get_all_devices(&mut conn).unwrap().len(), 1);
+
+        // Only so many requests can wait at once, but expired ones don't count
+        assert_eq!(insert_device(&mut conn, new_test_device("hash3", "DEF234", later), later, 1).unwrap(), None);
+        let much_later = later + TimeDelta::hours(1);
+        assert!(insert_device(&mut conn, new_test_device("hash3", "DEF234", much_later), much_later, 1).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_device_status() {
+        let mut conn = init_test_db();
+        let now = NaiveDateTime::parse_from_str("2025-10-26 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let screen = insert_screen(&mut conn, new_test_screen("Konsulatet")).unwrap();
+        insert_device(&mut conn, new_test_device("pending", "PEND23", now), now, 10).unwrap().unwrap();
+        let device = insert_device(&mut conn, new_test_device("hash", "ABC234", now), now, 10).unwrap().unwrap();
+        approve_device(&mut conn, "ABC234", &screen.id, "Kiosk", "admin@example.com", now).unwrap();
+
+        // Pending devices are not listed, paired ones are even before their first heartbeat
+        let listed = get_paired_devices_with_status(&mut conn).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].0.id, device.id);
+        assert_eq!(listed[0].1, None);
+
+        let mut status = models::DeviceStatus {
+            device_id: device.id.clone(),
+            last_seen_at: now,
+            current_slide_id: Some("slide".to_string()),
+            manifest_version: None,
+            uptime_seconds: 10,
+            client_errors: "[]".to_string(),
+        };
+        upsert_device_status(&mut conn, &status).unwrap();
+
+        // A new heartbeat replaces the old one
+        status.last_seen_at = now + TimeDelta::seconds(30);
+        status.current_slide_id = None;
+        status.uptime_seconds = 40;
+        upsert_device_status(&mut conn, &status).unwrap();
+        assert_eq!(get_paired_devices_with_status(&mut conn).unwrap()[0].1, Some(status));
+    }
+
+    #[test]
+    fn test_screen_commands() {
+        let mut conn = init_test_db();
+        let now = NaiveDateTime::parse_from_str("2025-10-28 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let screen = insert_screen(&mut conn, new_test_screen("Konsulatet")).unwrap();
+        let other_screen = insert_screen(&mut conn, new_test_screen("Entrance")).unwrap();
+
+        let new_command = |command: &str, issued_at: NaiveDateTime| models::ScreenCommand {
+            id: Uuid::new_v4().to_string(),
+            screen_id: screen.id.clone(),
+            command: command.to_string(),
+            slide_id: None,
+            issued_by: "admin@example.com".to_string(),
+            issued_at,
+            expires_at: issued_at + TimeDelta::minutes(10),
+            acknowledged_at: None,
+            acknowledged_by: None,
+            success: None,
+            result_message: None,
+        };
+        let old = insert_screen_command(&mut conn, new_command("reload", now - TimeDelta::hours(1))).unwrap();
+        let reload = insert_screen_command(&mut conn, new_command("reload", now)).unwrap();
+        let identify = insert_screen_command(&mut conn, new_command("identify", now + TimeDelta::seconds(1))).unwrap();
+
+        // Expired commands are not pending, and the oldest pending comes first
+        let pending = get_pending_screen_commands(&mut conn, &screen.id, now).unwrap();
+        assert_eq!(pending, vec![reload.clone(), identify.clone()]);
+        assert!(get_pending_screen_commands(&mut conn, &other_screen.id, now).unwrap().is_empty());
+
+        // Only a device on the right screen can acknowledge, and only once
+        assert!(!acknowledge_screen_command(&mut conn, &reload.id, &other_screen.id, "device", true, None, now).unwrap());
+        assert!(acknowledge_screen_command(&mut conn, &reload.id, &screen.id, "device", true, None, now).unwrap());
+        assert!(!acknowledge_screen_command(&mut conn, &reload.id, &screen.id, "device", true, None, now).unwrap());
+        assert_eq!(get_pending_screen_commands(&mut conn, &screen.id, now).unwrap(), vec![identify.clone()]);
+
+        let history = get_screen_command_history(&mut conn, &screen.id, 2).unwrap();
+        assert_eq!(history.iter().map(|c| &c.id).collect::<Vec<_>>(), vec![&identify.id, &reload.id]);
+        assert_eq!(history[1].acknowledged_by.as_deref(), Some("device"));
+        assert_eq!(get_screen_command_history(&mut conn, &screen.id, 10).unwrap().last().unwrap().id, old.id);
+    }
+
+    #[test]
+    fn test_impressions() {
+        let mut conn = init_test_db();
+        let day = NaiveDateTime::parse_from_str("2025-10-30 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+
+        let play = |slide_id: &str, screen_id: &str, started_at: NaiveDateTime, duration_ms: i64| models::NewPlayEvent {
+            slide_id: slide_id.to_string(),
+            screen_id: screen_id.to_string(),
+            device_id: "device".to_string(),
+            started_at,
+            duration_ms,
+        };
+        insert_play_events(&mut conn, &[
+            play("a", "konsulatet", day, 15_000),
+            play("a", "konsulatet", day + TimeDelta::minutes(1), 15_500),
+            play("a", "entrance", day, 15_000),
+            play("b", "konsulatet", day + TimeDelta::days(1), 10_000),
+            // Outside the range
+            play("a", "konsulatet", day + TimeDelta::days(7), 10_000),
+        ]).unwrap();
+
+        let from = day - TimeDelta::hours(12);
+        let to = day + TimeDelta::days(2);
+        let impressions = get_impressions(&mut conn, from, to, None, None).unwrap();
+        let summary: Vec<_> = impressions.iter()
+            .map(|i| (i.slide_id.as_str(), i.day.as_str(), i.screen_id.as_str(), i.impressions, i.total_seconds))
+            .collect();
+        assert_eq!(summary, vec![
+            ("a", "2025-10-30", "entrance", 1, 15),
+            ("a", "2025-10-30", "konsulatet", 2, 30),
+            ("b", "2025-10-31", "konsulatet", 1, 10),
+        ]);
+
+        assert_eq!(get_impressions(&mut conn, from, to, Some("b"), None).unwrap().len(), 1);
+        assert_eq!(get_impressions(&mut conn, from, to, Some("a"), Some("entrance")).unwrap().len(), 1);
+    }
 }
@@ -0,0 +1,91 @@
+//! Who changed what through the admin API. Every route that changes something for a logged in user records
+//! an `AuditEvent` with the target as JSON before and after the change, in the same `web::block` as the change.
+//! Changes made by kiosks (pairing requests, heartbeats, plays, ...) are not recorded.
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::SqliteConnection;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::actions::{self, DbError};
+use crate::models::{AuditEvent, NewAuditEvent};
+
+/// What the target of an action looked like before and after it
+pub(crate) struct Change {
+    before: Option<String>,
+    after: Option<String>,
+}
+
+/// None for values that serialize to null, so adding something with `changed(&None, ..)` has no `before`
+fn to_json(value: &impl Serialize) -> Option<String> {
+    // Only our own models are recorded, and those always serialize
+    serde_json::to_value(value).ok().filter(|value| !value.is_null()).map(|value| value.to_string())
+}
+
+impl Change {
+    pub(crate) fn created(after: &impl Serialize) -> Self {
+        Change { before: None, after: to_json(after) }
+    }
+
+    pub(crate) fn removed(before: &impl Serialize) -> Self {
+        Change { before: to_json(before), after: None }
+    }
+
+    pub(crate) fn changed(before: &impl Serialize, after: &impl Serialize) -> Self {
+        Change { before: to_json(before), after: to_json(after) }
+    }
+}
+
+/// Record that `actor` (an email) did `action`, e.g. "slide.remove", to the target with id `target_id`
+pub(crate) fn record(conn: &mut SqliteConnection, actor: &str, action: &str, target_id: &str, change: Change) -> Result<(), DbError> {
+    actions::insert_audit_event(conn, &NewAuditEvent {
+        actor: actor.to_string(),
+        action: action.to_string(),
+        target_id: target_id.to_string(),
+        before: change.before,
+        after: change.after,
+        created_at: Utc::now().naive_utc(),
+    })
+}
+
+/// Filters for `GET /audit`. Every filter is optional.
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct AuditQuery {
+    pub actor: Option<String>,
+    pub action: Option<String>,
+    pub target_id: Option<String>,
+    /// Only events at or after this time, in UTC
+    pub from: Option<NaiveDateTime>,
+    /// Only events before this time, in UTC
+    pub to: Option<NaiveDateTime>,
+    /// Only events older than this one. Used to get the next page.
+    pub before_id: Option<i32>,
+    pub limit: Option<i64>,
+}
+
+/// An audit event as returned by `GET /audit`, with `before` and `after` as JSON instead of strings
+#[derive(Debug, Serialize)]
+pub(crate) struct AuditEventResponse {
+    id: i32,
+    actor: String,
+    action: String,
+    target_id: String,
+    before: Option<Value>,
+    after: Option<Value>,
+    created_at: NaiveDateTime,
+}
+
+impl From<AuditEvent> for AuditEventResponse {
+    fn from(event: AuditEvent) -> Self {
+        let parse = |json: Option<String>| json.and_then(|json| serde_json::from_str(&json).ok());
+        AuditEventResponse {
+            id: event.id,
+            actor: event.actor,
+            action: event.action,
+            target_id: event.target_id,
+            before: parse(event.before),
+            after: parse(event.after),
+            created_at: event.created_at,
+        }
+    }
+}
@@ -1,49 +1,174 @@
 use actix_session::SessionExt;
 use actix_web::{error, web};
+use futures_util::future::LocalBoxFuture;
 use serde::{Deserialize, Serialize};
 
-use crate::actions;
+use crate::actions::{self, DbError};
+use crate::models::Role;
+use crate::sessions;
 
 use super::DbPool;
 
-use std::future::{ready, Ready};
+use std::marker::PhantomData;
+use std::ops::Deref;
 
 use actix_web::FromRequest;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub(crate) enum PermissionLevel {
-    User,
-    Admin,
+/// Something a user can be allowed to do. Which permissions a user has depends on their `Role`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Permission {
+    /// See slides, screens, event slides and play statistics
+    SlidesRead,
+    /// Upload, change and remove slides and event slides
+    SlidesWrite,
+    /// Approve slides so they are shown on the screens
+    SlidesPublish,
+    /// Overrides, screen commands, device health and muting SL deviations
+    ScreensOperate,
+    /// Screens, devices, settings, schedules, quiet hours, SL sites and calendar feeds
+    ScreensManage,
+    /// Add and remove users and change their roles
+    UsersManage,
+    /// See who changed what, see `audit`
+    AuditRead,
+}
+
+impl Role {
+    pub(crate) fn permissions(&self) -> &'static [Permission] {
+        use Permission::*;
+
+        match self {
+            Role::Viewer => &[SlidesRead],
+            Role::SlideEditor => &[SlidesRead, SlidesWrite],
+            Role::SlidePublisher => &[SlidesRead, SlidesWrite, SlidesPublish],
+            Role::ScreenOperator => &[SlidesRead, ScreensOperate],
+            Role::Admin => &[SlidesRead, SlidesWrite, SlidesPublish, ScreensOperate, ScreensManage, UsersManage, AuditRead],
+        }
+    }
+
+    pub(crate) fn has(&self, permission: Permission) -> bool {
+        self.permissions().contains(&permission)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub(crate) struct AuthenticatedUser {
     pub(crate) email: String,
-    pub(crate) permission: PermissionLevel,
+    pub(crate) role: Role,
 }
 
 /// This allows us to extract the AuthenticatedUser from the session
 /// We protect routes by having this as a parameter to our
-/// handles (even if we don't use the data), e.g. see `add_user` and `logout`.
+/// handles (even if we don't use the data), e.g. see `logout`.
+/// Routes that need more than being logged in use `RequirePermission` instead.
+///
+/// The session only says who logged in. Their role is read from `users` on every request,
+/// so removing or demoting a user takes effect right away instead of when their cookie expires.
 impl FromRequest for AuthenticatedUser {
     type Error = actix_web::Error;
-    type Future = Ready<Result<Self, Self::Error>>;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
 
     fn from_request(req: &actix_web::HttpRequest, _payload: &mut actix_web::dev::Payload) -> Self::Future {
         let session = req.get_session();
+        let pool = req.app_data::<web::Data<DbPool>>().cloned();
 
-        match session.get::<AuthenticatedUser>("auth") {
-            Ok(Some(user)) => {
-                ready(Ok(user)) 
-            },
-            _ => ready(Err(actix_web::error::ErrorUnauthorized("Not logged in"))),
-        }
+        Box::pin(async move {
+            let Ok(Some(user)) = session.get::<AuthenticatedUser>(sessions::AUTH_KEY) else {
+                return Err(error::ErrorUnauthorized("Not logged in"));
+            };
+            let pool = pool.ok_or_else(|| error::ErrorInternalServerError("No database pool"))?;
+
+            match check_user_role(user.email.clone(), pool).await? {
+                Some(role) => Ok(AuthenticatedUser { email: user.email, role }),
+                None => {
+                    // The user has been removed
+                    log::info!("Ending the session of removed user {}", user.email);
+                    session.purge();
+                    Err(error::ErrorUnauthorized("Not logged in"))
+                },
+            }
+        })
+    }
+}
+
+/// A permission that a route can require with `RequirePermission`
+pub(crate) trait RequiredPermission {
+    const PERMISSION: Permission;
+}
+
+macro_rules! required_permissions {
+    ($($name:ident),+ $(,)?) => {
+        $(
+            #[doc = concat!("Marker for `Permission::", stringify!($name), "`")]
+            pub(crate) struct $name;
+
+            impl RequiredPermission for $name {
+                const PERMISSION: Permission = Permission::$name;
+            }
+        )+
+    };
+}
+
+required_permissions!(SlidesRead, SlidesWrite, ScreensOperate, ScreensManage, UsersManage, AuditRead);
+
+/// A logged in user whose role has the permission `P`, e.g. `RequirePermission<SlidesWrite>`.
+/// Responds 401 if nobody is logged in and 403 if the user's role doesn't have the permission.
+pub(crate) struct RequirePermission<P> {
+    user: AuthenticatedUser,
+    permission: PhantomData<P>,
+}
+
+impl<P> RequirePermission<P> {
+    pub(crate) fn into_inner(self) -> AuthenticatedUser {
+        self.user
+    }
+}
+
+impl<P> Deref for RequirePermission<P> {
+    type Target = AuthenticatedUser;
+
+    fn deref(&self) -> &Self::Target {
+        &self.user
+    }
+}
+
+impl<P: RequiredPermission> FromRequest for RequirePermission<P> {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &actix_web::HttpRequest, payload: &mut actix_web::dev::Payload) -> Self::Future {
+        let user = AuthenticatedUser::from_request(req, payload);
+
+        Box::pin(async move {
+            let user = user.await?;
+            if user.role.has(P::PERMISSION) {
+                Ok(RequirePermission { user, permission: PhantomData })
+            } else {
+                Err(error::ErrorForbidden("Missing permission"))
+            }
+        })
     }
 }
 
-/// Check a user's permission level against the database
-/// Returns None if the user does not exist, otherwise a PermissionLevel
-pub(crate) async fn check_user_permission(email: String, pool: web::Data<DbPool>) -> actix_web::Result<Option<PermissionLevel>> {
+/// Whether a user has a permission for a slide owned by `group` (None for slides without a group).
+/// Their own role applies to slides without a group. A group's slides are managed by its members,
+/// so for those only their role in the group counts, except for admins and for looking at slides.
+/// Committee members therefore usually have the viewer role, and slide_editor in their committee.
+pub(crate) fn has_slide_permission(conn: &mut diesel::SqliteConnection, user: &AuthenticatedUser, permission: Permission, group: Option<&str>)
+-> Result<bool, DbError> {
+    let Some(group) = group else {
+        return Ok(user.role.has(permission));
+    };
+    if user.role == Role::Admin || (permission == Permission::SlidesRead && user.role.has(permission)) {
+        return Ok(true);
+    }
+    Ok(actions::get_group_role(conn, group, &user.email)?.is_some_and(|role| role.has(permission)))
+}
+
+/// Check a user's role against the database
+/// Returns None if the user does not exist, otherwise their Role
+pub(crate) async fn check_user_role(email: String, pool: web::Data<DbPool>) -> actix_web::Result<Option<Role>> {
     web::block(move || {
         let mut conn = pool.get()?;
         actions::check_user(&mut conn, &email)
@@ -51,3 +176,22 @@ pub(crate) async fn check_user_permission(email: String, pool: web::Data<DbPool>
     .await?
     .map_err(error::ErrorInternalServerError)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_permissions() {
+        assert!(Role::Admin.has(Permission::UsersManage));
+        assert!(Role::SlidePublisher.has(Permission::SlidesWrite));
+        assert!(!Role::SlideEditor.has(Permission::SlidesPublish));
+        assert!(!Role::ScreenOperator.has(Permission::SlidesWrite));
+        assert!(!Role::Viewer.has(Permission::ScreensOperate));
+
+        // Every role can at least look at the slides
+        for value in Role::VALUES {
+            assert!(value.parse::<Role>().unwrap().has(Permission::SlidesRead));
+        }
+    }
+}
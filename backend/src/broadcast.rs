@@ -0,0 +1,113 @@
+use std::time::Duration;
+
+use actix_web::web::Bytes;
+use futures_util::{stream, Stream, StreamExt};
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::{BroadcastStream, IntervalStream};
+
+use crate::models::ScreenOverride;
+
+// How many events a slow screen may fall behind before it is told to resync everything
+const CHANNEL_CAPACITY: usize = 32;
+
+// Proxies (and some browsers) drop idle connections, so we send an SSE comment this often
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Events pushed to connected screens. The screen refetches the affected resource when it
+/// receives one, so most events only say *what* changed.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum ScreenEvent {
+    Slides,
+    Settings,
+    /// An override was activated or cleared. Carries the override itself so screens
+    /// can show it without waiting for a refetch. `None` means it was cleared.
+    Override { active: Option<ScreenOverride> },
+    /// New commands were queued for the devices showing a screen. Only those devices should poll for them.
+    Commands { screen_id: String },
+    /// Sent when a screen has missed events and should refetch everything
+    Resync,
+}
+
+impl ScreenEvent {
+    /// The SSE event name, used with `EventSource.addEventListener` on the screen
+    fn name(&self) -> &'static str {
+        match self {
+            ScreenEvent::Slides => "slides",
+            ScreenEvent::Settings => "settings",
+            ScreenEvent::Override { .. } => "override",
+            ScreenEvent::Commands { .. } => "commands",
+            ScreenEvent::Resync => "resync",
+        }
+    }
+
+    fn to_sse(&self) -> Bytes {
+        let data = serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string());
+        Bytes::from(format!("event: {}\ndata: {}\n\n", self.name(), data))
+    }
+}
+
+/// Fans out `ScreenEvent`s to every screen connected to `/screen/stream`.
+/// Cloning is cheap and all clones share the same channel, so it should be created once
+/// outside of `HttpServer::new` (like the DB pool) and handed to the workers as app data.
+#[derive(Debug, Clone)]
+pub(crate) struct Broadcaster {
+    sender: broadcast::Sender<ScreenEvent>,
+}
+
+impl Broadcaster {
+    pub(crate) fn new() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Broadcaster { sender }
+    }
+
+    /// Send an event to all connected screens. It's fine if no screen is connected.
+    pub(crate) fn send(&self, event: ScreenEvent) {
+        log::debug!("Broadcasting {:?} to {} screen(s)", event, self.sender.receiver_count());
+        let _ = self.sender.send(event);
+    }
+
+    /// A new SSE body stream for one screen
+    pub(crate) fn subscribe(&self) -> impl Stream<Item = Result<Bytes, actix_web::Error>> {
+        let events = BroadcastStream::new(self.sender.subscribe()).map(|event| match event {
+            Ok(event) => event.to_sse(),
+            // The screen fell too far behind, tell it to refetch everything
+            Err(_) => ScreenEvent::Resync.to_sse(),
+        });
+
+        let keep_alive = IntervalStream::new(tokio::time::interval(KEEP_ALIVE_INTERVAL))
+            .map(|_| Bytes::from_static(b": keep-alive\n\n"));
+
+        // The first chunk tells the browser how long to wait before reconnecting,
+        // and makes sure the response headers are flushed immediately
+        stream::once(async { Bytes::from_static(b"retry: 5000\n\n") })
+            .chain(stream::select(events, keep_alive))
+            .map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_subscriber_receives_event() {
+        let broadcaster = Broadcaster::new();
+        let mut stream = Box::pin(broadcaster.subscribe());
+
+        // First chunk is the retry hint
+        let first = stream.next().await.unwrap().unwrap();
+        assert_eq!(first, Bytes::from_static(b"retry: 5000\n\n"));
+
+        // The keep-alive interval ticks immediately once, skip any comments
+        broadcaster.send(ScreenEvent::Slides);
+        let event = loop {
+            let chunk = stream.next().await.unwrap().unwrap();
+            if !chunk.starts_with(b":") {
+                break chunk;
+            }
+        };
+        assert_eq!(event, Bytes::from_static(b"event: slides\ndata: {\"type\":\"slides\"}\n\n"));
+    }
+}
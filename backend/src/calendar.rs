@@ -0,0 +1,819 @@
+//! Fetches the admin-configured ICS feeds (like the section's Google calendar) and expands them into
+//! upcoming events, so the screens can show them without embedding a calendar.
+//! Feeds are fetched every few minutes in the background, and a feed that fails keeps its last events.
+//!
+//! Recurring events support the parts of RRULE that calendars actually send: FREQ (daily to yearly),
+//! INTERVAL, COUNT, UNTIL, BYDAY, BYMONTHDAY and BYMONTH. RDATE, EXDATE and moved or cancelled
+//! occurrences (RECURRENCE-ID) are handled too.
+//!
+//! Feeds can also have slides generated for their events, so committees don't have to make a poster
+//! slide for every event and keep it in sync with the calendar by hand.
+
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use actix_web::web;
+use chrono::{DateTime, Datelike, Days, Local, LocalResult, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone, Utc, Weekday};
+use ical::parser::ical::component::IcalEvent;
+use ical::property::Property;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::actions;
+use crate::broadcast::{Broadcaster, ScreenEvent};
+use crate::models::CalendarFeed;
+use crate::DbPool;
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(15 * 60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How far ahead recurring events are expanded
+pub(crate) const LOOKAHEAD_DAYS: i64 = 60;
+// Stops rules like FREQ=DAILY without an end from going on forever if they started long ago
+const MAX_PERIODS: u32 = 50_000;
+
+/// An occurrence of an event, as sent to the screens
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub(crate) struct Event {
+    pub uid: String,
+    pub feed_id: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// All-day events start and end at local midnight
+    pub all_day: bool,
+}
+
+impl Event {
+    /// Identifies the event (not one of its occurrences) in its feed, also after the feed is fetched again
+    pub(crate) fn slide_key(&self) -> String {
+        let hash = Sha256::digest(format!("{}\n{}", self.feed_id, self.uid));
+        hex::encode(&hash[..8])
+    }
+}
+
+/// The next occurrence of an event in a feed with slides enabled
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub(crate) struct EventSlide {
+    pub key: String,
+    /// Local midnight `slide_days_before` days before the event. The slide is shown from then until the event starts.
+    pub shown_from: DateTime<Utc>,
+    pub event: Event,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Zone {
+    Utc,
+    Named(chrono_tz::Tz),
+    /// No time zone, or one we don't know (like Outlook's Windows names). Uses the server's, which is the screens' too.
+    Local,
+}
+
+/// A DTSTART, DTEND, EXDATE etc. Recurrences are expanded in wall-clock time,
+/// so a weekly event at 17:00 stays at 17:00 when the clocks change.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum IcsTime {
+    Date(NaiveDate),
+    DateTime(NaiveDateTime, Zone),
+}
+
+fn resolve<Tz: TimeZone>(zone: &Tz, time: NaiveDateTime) -> DateTime<Utc> {
+    match zone.from_local_datetime(&time) {
+        LocalResult::Single(time) => time.with_timezone(&Utc),
+        LocalResult::Ambiguous(earliest, _) => earliest.with_timezone(&Utc),
+        // The time is skipped when the clocks go forward, so it happens an hour later
+        LocalResult::None => resolve(zone, time + TimeDelta::hours(1)),
+    }
+}
+
+fn param<'a>(property: &'a Property, name: &str) -> Option<&'a str> {
+    property.params.as_ref()?.iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))?
+        .1.first().map(String::as_str)
+}
+
+impl IcsTime {
+    fn parse_value(value: &str, zone: Zone) -> Result<Self, String> {
+        let value = value.trim();
+        if let Some(utc) = value.strip_suffix('Z') {
+            NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").map(|time| IcsTime::DateTime(time, Zone::Utc))
+        } else if value.contains('T') {
+            NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").map(|time| IcsTime::DateTime(time, zone))
+        } else {
+            NaiveDate::parse_from_str(value, "%Y%m%d").map(IcsTime::Date)
+        }.map_err(|e| format!("Invalid date {value}: {e}"))
+    }
+
+    /// Every value of a property, which can be a comma separated list for EXDATE and RDATE
+    fn parse_all(property: &Property) -> Result<Vec<Self>, String> {
+        let zone = param(property, "TZID")
+            .and_then(|tzid| tzid.trim_matches('"').trim_start_matches('/').parse().ok())
+            .map_or(Zone::Local, Zone::Named);
+        property.value.as_deref().unwrap_or_default()
+            .split(',')
+            .map(|value| Self::parse_value(value, zone))
+            .collect()
+    }
+
+    fn parse(property: &Property) -> Result<Self, String> {
+        Self::parse_all(property)?.into_iter().next().ok_or_else(|| format!("Missing value for {}", property.name))
+    }
+
+    fn wall(&self) -> NaiveDateTime {
+        match *self {
+            IcsTime::Date(date) => date.and_time(NaiveTime::MIN),
+            IcsTime::DateTime(time, _) => time,
+        }
+    }
+
+    fn with_wall(&self, wall: NaiveDateTime) -> Self {
+        match *self {
+            IcsTime::Date(_) => IcsTime::Date(wall.date()),
+            IcsTime::DateTime(_, zone) => IcsTime::DateTime(wall, zone),
+        }
+    }
+
+    fn to_utc(self) -> DateTime<Utc> {
+        match self {
+            IcsTime::Date(date) => resolve(&Local, date.and_time(NaiveTime::MIN)),
+            IcsTime::DateTime(time, Zone::Utc) => time.and_utc(),
+            IcsTime::DateTime(time, Zone::Named(tz)) => resolve(&tz, time),
+            IcsTime::DateTime(time, Zone::Local) => resolve(&Local, time),
+        }
+    }
+}
+
+/// Parses DURATION values like `PT1H30M` or `P1D`
+fn parse_duration(value: &str) -> Result<TimeDelta, String> {
+    let invalid = || format!("Invalid duration {value}");
+    let (negative, rest) = match value.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let rest = rest.strip_prefix('P').ok_or_else(invalid)?;
+
+    let mut duration = TimeDelta::zero();
+    let mut number = String::new();
+    for c in rest.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => continue,
+            unit => {
+                let amount: i64 = number.parse().map_err(|_| invalid())?;
+                number.clear();
+                let part = match unit {
+                    'W' => TimeDelta::try_weeks(amount),
+                    'D' => TimeDelta::try_days(amount),
+                    'H' => TimeDelta::try_hours(amount),
+                    'M' => TimeDelta::try_minutes(amount),
+                    'S' => TimeDelta::try_seconds(amount),
+                    _ => return Err(invalid()),
+                };
+                // Remote feeds can have durations too long for a TimeDelta
+                duration = part.and_then(|part| duration.checked_add(&part)).ok_or_else(invalid)?;
+            },
+        }
+    }
+    Ok(if negative { -duration } else { duration })
+}
+
+/// Undoes the escaping of TEXT values
+fn unescape(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n' | 'N') => text.push('\n'),
+                Some(escaped) => text.push(escaped),
+                None => {},
+            }
+        } else {
+            text.push(c);
+        }
+    }
+    text
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+#[derive(Debug, PartialEq)]
+struct RecurrenceRule {
+    frequency: Frequency,
+    interval: u32,
+    count: Option<usize>,
+    /// In the zone of DTSTART unless it's in UTC
+    until: Option<String>,
+    /// Weekdays, with the nth of the month for monthly and yearly rules (negative counts from the end)
+    by_day: Vec<(Option<i32>, Weekday)>,
+    /// Negative counts from the end of the month
+    by_month_day: Vec<i32>,
+    by_month: Vec<u32>,
+}
+
+impl FromStr for RecurrenceRule {
+    type Err = String;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let invalid = |part: &str| format!("Unsupported RRULE part {part}");
+        let mut frequency = None;
+        let mut parsed = RecurrenceRule {
+            frequency: Frequency::Daily,
+            interval: 1,
+            count: None,
+            until: None,
+            by_day: Vec::new(),
+            by_month_day: Vec::new(),
+            by_month: Vec::new(),
+        };
+
+        for part in rule.split(';').filter(|part| !part.is_empty()) {
+            let (key, value) = part.split_once('=').ok_or_else(|| invalid(part))?;
+            match key {
+                "FREQ" => frequency = Some(match value {
+                    "DAILY" => Frequency::Daily,
+                    "WEEKLY" => Frequency::Weekly,
+                    "MONTHLY" => Frequency::Monthly,
+                    "YEARLY" => Frequency::Yearly,
+                    _ => return Err(invalid(part)),
+                }),
+                "INTERVAL" => parsed.interval = value.parse().ok().filter(|&interval| interval > 0).ok_or_else(|| invalid(part))?,
+                "COUNT" => parsed.count = Some(value.parse().map_err(|_| invalid(part))?),
+                "UNTIL" => parsed.until = Some(value.to_string()),
+                "BYDAY" => for day in value.split(',') {
+                    // Not split_at, since a remote feed can put anything here, like `BYDAY=Åx`
+                    let (nth, weekday) = day.split_at_checked(day.len().saturating_sub(2)).ok_or_else(|| invalid(part))?;
+                    let weekday = match weekday {
+                        "MO" => Weekday::Mon,
+                        "TU" => Weekday::Tue,
+                        "WE" => Weekday::Wed,
+                        "TH" => Weekday::Thu,
+                        "FR" => Weekday::Fri,
+                        "SA" => Weekday::Sat,
+                        "SU" => Weekday::Sun,
+                        _ => return Err(invalid(part)),
+                    };
+                    let nth = if nth.is_empty() { None } else { Some(nth.parse().map_err(|_| invalid(part))?) };
+                    parsed.by_day.push((nth, weekday));
+                },
+                "BYMONTHDAY" => for day in value.split(',') {
+                    parsed.by_month_day.push(day.parse().map_err(|_| invalid(part))?);
+                },
+                "BYMONTH" => for month in value.split(',') {
+                    parsed.by_month.push(month.parse().ok().filter(|month| (1..=12).contains(month)).ok_or_else(|| invalid(part))?);
+                },
+                // Weeks always start on monday here, which is what everyone in Sweden uses anyway
+                "WKST" => {},
+                _ => return Err(invalid(part)),
+            }
+        }
+
+        parsed.frequency = frequency.ok_or("RRULE without FREQ")?;
+        Ok(parsed)
+    }
+}
+
+fn days_in_month(first: NaiveDate) -> u32 {
+    // Only the last month there is has no next month, and it's a december
+    first.checked_add_months(Months::new(1)).map_or(31, |next| next.signed_duration_since(first).num_days() as u32)
+}
+
+impl RecurrenceRule {
+    /// The dates in a month given by BYMONTHDAY and BYDAY, or `default_day` if it has neither
+    fn dates_in_month(&self, first: NaiveDate, default_day: u32) -> Vec<NaiveDate> {
+        let length = days_in_month(first) as i32;
+        let day = |day: i32| first.with_day(day as u32);
+
+        if !self.by_month_day.is_empty() {
+            self.by_month_day.iter()
+                .filter_map(|&n| day(if n < 0 { length + n + 1 } else { n }).filter(|_| n != 0 && n.abs() <= length))
+                .filter(|date| self.by_day.is_empty() || self.by_day.iter().any(|&(_, weekday)| date.weekday() == weekday))
+                .collect()
+        } else if !self.by_day.is_empty() {
+            let mut dates = Vec::new();
+            for &(nth, weekday) in &self.by_day {
+                let matching: Vec<_> = (1..=length).filter_map(day).filter(|date| date.weekday() == weekday).collect();
+                match nth {
+                    None => dates.extend(matching),
+                    Some(n) if n > 0 => dates.extend(matching.get(n as usize - 1)),
+                    Some(n) => dates.extend(matching.len().checked_sub(n.unsigned_abs() as usize).and_then(|i| matching.get(i))),
+                }
+            }
+            dates
+        } else {
+            day(default_day as i32).into_iter().collect()
+        }
+    }
+
+    /// The dates the rule gives in the nth period after the one `start` is in,
+    /// or None if that period is past the last date there is
+    fn dates_in_period(&self, start: NaiveDate, n: u32) -> Option<Vec<NaiveDate>> {
+        let steps = n.checked_mul(self.interval)?;
+        let first_of_month = start.with_day(1).unwrap();
+        let mut dates = match self.frequency {
+            Frequency::Daily => {
+                let date = start.checked_add_days(Days::new(steps.into()))?;
+                let matches = (self.by_day.is_empty() || self.by_day.iter().any(|&(_, weekday)| date.weekday() == weekday))
+                    && (self.by_month_day.is_empty() || self.dates_in_month(date.with_day(1).unwrap(), 0).contains(&date));
+                if matches { vec![date] } else { vec![] }
+            },
+            Frequency::Weekly => {
+                let monday = start.checked_sub_days(Days::new(start.weekday().num_days_from_monday().into()))?
+                    .checked_add_days(Days::new(7 * u64::from(steps)))?;
+                if self.by_day.is_empty() {
+                    vec![monday.checked_add_days(Days::new(start.weekday().num_days_from_monday().into()))?]
+                } else {
+                    self.by_day.iter()
+                        .map(|&(_, weekday)| monday.checked_add_days(Days::new(weekday.num_days_from_monday().into())))
+                        .collect::<Option<_>>()?
+                }
+            },
+            Frequency::Monthly => self.dates_in_month(first_of_month.checked_add_months(Months::new(steps))?, start.day()),
+            Frequency::Yearly => {
+                let year = first_of_month.with_month(1).unwrap().checked_add_months(Months::new(steps.checked_mul(12)?))?;
+                let months = if self.by_month.is_empty() { vec![start.month()] } else { self.by_month.clone() };
+                months.into_iter()
+                    .flat_map(|month| self.dates_in_month(year.with_month(month).unwrap(), start.day()))
+                    .collect()
+            },
+        };
+        if self.frequency != Frequency::Yearly && !self.by_month.is_empty() {
+            dates.retain(|date| self.by_month.contains(&date.month()));
+        }
+        dates.sort();
+        dates.dedup();
+        Some(dates)
+    }
+
+    /// The occurrences from `start` that begin before `window_end`
+    fn occurrences(&self, start: IcsTime, window_end: DateTime<Utc>) -> Result<Vec<IcsTime>, String> {
+        let until = match (&self.until, start) {
+            (None, _) => None,
+            (Some(until), IcsTime::DateTime(_, zone)) => Some(IcsTime::parse_value(until, zone)?),
+            (Some(until), IcsTime::Date(_)) => Some(IcsTime::parse_value(until, Zone::Local)?),
+        };
+        let is_after_until = |occurrence: IcsTime| match until {
+            None => false,
+            // A date includes the whole day
+            Some(IcsTime::Date(date)) => occurrence.wall().date() > date,
+            Some(until) => occurrence.to_utc() > until.to_utc(),
+        };
+
+        let mut occurrences = Vec::new();
+        for n in 0..MAX_PERIODS {
+            let Some(dates) = self.dates_in_period(start.wall().date(), n) else {
+                break;
+            };
+            for date in dates {
+                let occurrence = start.with_wall(date.and_time(start.wall().time()));
+                if occurrence.wall() < start.wall() {
+                    continue;
+                }
+                if is_after_until(occurrence)
+                    || self.count.is_some_and(|count| occurrences.len() >= count)
+                    || occurrence.to_utc() >= window_end {
+                    return Ok(occurrences);
+                }
+                occurrences.push(occurrence);
+            }
+        }
+        Ok(occurrences)
+    }
+}
+
+/// A VEVENT with the properties we use
+struct ParsedEvent {
+    uid: String,
+    title: String,
+    description: Option<String>,
+    location: Option<String>,
+    start: IcsTime,
+    duration: TimeDelta,
+    rule: Option<RecurrenceRule>,
+    extra_dates: Vec<IcsTime>,
+    excluded: HashSet<DateTime<Utc>>,
+    /// Set if this replaces an occurrence of a recurring event with the same UID
+    recurrence_id: Option<DateTime<Utc>>,
+    cancelled: bool,
+}
+
+impl ParsedEvent {
+    fn parse(event: &IcalEvent) -> Result<Self, String> {
+        let property = |name: &str| event.properties.iter().find(|property| property.name == name);
+        let text = |name: &str| property(name).and_then(|property| property.value.as_deref()).map(unescape);
+        let all = |name: &'static str| event.properties.iter().filter(move |property| property.name == name);
+
+        let start = IcsTime::parse(property("DTSTART").ok_or("Event without DTSTART")?)?;
+        let duration = if let Some(end) = property("DTEND") {
+            let end = IcsTime::parse(end)?;
+            match start {
+                IcsTime::Date(_) => end.wall() - start.wall(),
+                IcsTime::DateTime(..) => end.to_utc() - start.to_utc(),
+            }
+        } else if let Some(duration) = property("DURATION").and_then(|property| property.value.as_deref()) {
+            parse_duration(duration)?
+        } else {
+            // RFC 5545 says an all-day event without an end lasts the day, and other events take no time
+            match start {
+                IcsTime::Date(_) => TimeDelta::days(1),
+                IcsTime::DateTime(..) => TimeDelta::zero(),
+            }
+        };
+
+        let mut extra_dates = Vec::new();
+        for rdate in all("RDATE") {
+            extra_dates.extend(IcsTime::parse_all(rdate)?);
+        }
+        let mut excluded = HashSet::new();
+        for exdate in all("EXDATE") {
+            excluded.extend(IcsTime::parse_all(exdate)?.into_iter().map(IcsTime::to_utc));
+        }
+
+        Ok(ParsedEvent {
+            uid: text("UID").unwrap_or_default(),
+            title: text("SUMMARY").unwrap_or_default(),
+            description: text("DESCRIPTION").filter(|description| !description.is_empty()),
+            location: text("LOCATION").filter(|location| !location.is_empty()),
+            start,
+            duration,
+            rule: text("RRULE").map(|rule| rule.parse()).transpose()?,
+            extra_dates,
+            excluded,
+            recurrence_id: property("RECURRENCE-ID").map(IcsTime::parse).transpose()?.map(IcsTime::to_utc),
+            cancelled: text("STATUS").is_some_and(|status| status == "CANCELLED"),
+        })
+    }
+
+    /// None if the occurrence ends after the last time there is
+    fn occurrence(&self, feed_id: &str, start: IcsTime) -> Option<Event> {
+        let end = match start {
+            IcsTime::Date(_) => start.with_wall(start.wall().checked_add_signed(self.duration)?).to_utc(),
+            IcsTime::DateTime(..) => start.to_utc().checked_add_signed(self.duration)?,
+        };
+        Some(Event {
+            uid: self.uid.clone(),
+            feed_id: feed_id.to_string(),
+            title: self.title.clone(),
+            description: self.description.clone(),
+            location: self.location.clone(),
+            start: start.to_utc(),
+            end,
+            all_day: matches!(start, IcsTime::Date(_)),
+        })
+    }
+}
+
+/// The occurrences of the events in an ICS file that overlap `from..until`, sorted by start.
+/// Events that can't be parsed are skipped, but an invalid file gives an error.
+pub(crate) fn parse_events(ics: &str, feed_id: &str, from: DateTime<Utc>, until: DateTime<Utc>) -> Result<Vec<Event>, String> {
+    let mut events = Vec::new();
+    for calendar in ical::IcalParser::new(ics.as_bytes()) {
+        let calendar = calendar.map_err(|e| format!("Invalid calendar: {e}"))?;
+        let parsed: Vec<_> = calendar.events.iter()
+            .filter_map(|event| ParsedEvent::parse(event)
+                .inspect_err(|e| log::warn!("Skipping event in calendar {feed_id}: {e}"))
+                .ok())
+            .collect();
+
+        // Occurrences that have been moved or cancelled are replaced by their own VEVENT
+        let replaced: HashSet<_> = parsed.iter()
+            .filter_map(|event| Some((event.uid.as_str(), event.recurrence_id?)))
+            .collect();
+
+        for event in &parsed {
+            if event.cancelled {
+                continue;
+            }
+            let mut starts = match &event.rule {
+                Some(rule) => rule.occurrences(event.start, until)?,
+                None => vec![event.start],
+            };
+            starts.extend(event.extra_dates.iter().copied());
+
+            let is_recurring = event.rule.is_some() || !event.extra_dates.is_empty();
+            events.extend(starts.into_iter()
+                .filter(|start| !event.excluded.contains(&start.to_utc()))
+                .filter(|start| !is_recurring || event.recurrence_id.is_some() || !replaced.contains(&(event.uid.as_str(), start.to_utc())))
+                .filter_map(|start| event.occurrence(feed_id, start)
+                    .or_else(|| {
+                        log::warn!("Skipping occurrence of {} in calendar {feed_id}: it ends too far in the future", event.uid);
+                        None
+                    }))
+                .filter(|occurrence| occurrence.end > from && occurrence.start < until));
+        }
+    }
+    events.sort_by(|a, b| a.start.cmp(&b.start).then_with(|| a.title.cmp(&b.title)));
+    Ok(events)
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FeedStatus {
+    pub events: Vec<Event>,
+    pub fetched_at: Option<DateTime<Utc>>,
+    /// Why the last fetch failed, if it did
+    pub error: Option<String>,
+}
+
+/// The events of every feed, shared between workers like `sl::SlClient`
+pub(crate) struct Calendars {
+    http: reqwest::Client,
+    feeds: RwLock<HashMap<String, FeedStatus>>,
+}
+
+impl Calendars {
+    pub(crate) fn new() -> Self {
+        Calendars {
+            http: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .expect("Failed to build HTTP client"),
+            feeds: RwLock::new(HashMap::new()),
+        }
+    }
+
+    async fn fetch(&self, feed: &CalendarFeed, now: DateTime<Utc>) -> Result<Vec<Event>, String> {
+        let response = self.http.get(&feed.url).send().await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("Failed to fetch calendar {}: {e}", feed.name))?;
+        let body = response.text().await
+            .map_err(|e| format!("Failed to read calendar {}: {e}", feed.name))?;
+        // Events that started yesterday might still be going on
+        parse_events(&body, &feed.id, now - TimeDelta::days(1), now + TimeDelta::days(LOOKAHEAD_DAYS))
+            .map_err(|e| format!("Failed to parse calendar {}: {e}", feed.name))
+    }
+
+    /// Fetches every feed again. Feeds that aren't in `feeds` any more are forgotten.
+    pub(crate) async fn refresh(&self, feeds: &[CalendarFeed]) {
+        let now = Utc::now();
+        let results = futures_util::future::join_all(feeds.iter().map(|feed| self.fetch(feed, now))).await;
+
+        let mut statuses = self.feeds.write().unwrap();
+        let mut previous = std::mem::take(&mut *statuses);
+        for (feed, result) in feeds.iter().zip(results) {
+            let mut status = previous.remove(&feed.id).unwrap_or_default();
+            match result {
+                Ok(events) => {
+                    status.events = events;
+                    status.fetched_at = Some(now);
+                    status.error = None;
+                },
+                Err(e) => {
+                    log::warn!("{e}");
+                    status.error = Some(e);
+                },
+            }
+            statuses.insert(feed.id.clone(), status);
+        }
+    }
+
+    pub(crate) fn status(&self, feed_id: &str) -> FeedStatus {
+        self.feeds.read().unwrap().get(feed_id).cloned().unwrap_or_default()
+    }
+
+    /// A slide for the next occurrence of every event that hasn't started yet, in the feeds with
+    /// `slide_days_before` set. Includes slides that won't be shown yet, sorted by start.
+    pub(crate) fn event_slides(&self, feeds: &[CalendarFeed], now: DateTime<Utc>) -> Vec<EventSlide> {
+        let statuses = self.feeds.read().unwrap();
+        let mut slides: Vec<EventSlide> = Vec::new();
+        for feed in feeds {
+            let (Some(days_before), Some(status)) = (feed.slide_days_before, statuses.get(&feed.id)) else {
+                continue;
+            };
+            let mut keys = HashSet::new();
+            // The events are sorted by start, so the first occurrence of each event is the next one
+            for event in status.events.iter().filter(|event| event.start > now) {
+                let key = event.slide_key();
+                if !keys.insert(key.clone()) {
+                    continue;
+                }
+                let day = event.start.with_timezone(&Local).date_naive() - Days::new(days_before.max(0) as u64);
+                slides.push(EventSlide { key, shown_from: IcsTime::Date(day).to_utc(), event: event.clone() });
+            }
+        }
+        slides.sort_by_key(|slide| slide.event.start);
+        slides
+    }
+
+    /// Events from every feed that haven't ended and start before `until`, sorted by start
+    pub(crate) fn upcoming(&self, now: DateTime<Utc>, until: DateTime<Utc>) -> Vec<Event> {
+        let mut events: Vec<_> = self.feeds.read().unwrap().values()
+            .flat_map(|status| status.events.iter())
+            .filter(|event| event.end > now && event.start < until)
+            .cloned()
+            .collect();
+        events.sort_by(|a, b| a.start.cmp(&b.start).then_with(|| a.title.cmp(&b.title)));
+        events
+    }
+}
+
+/// Reads the feeds from the database and fetches them. Returns the feeds.
+pub(crate) async fn refresh_from_db(calendars: &Calendars, pool: DbPool) -> Result<Vec<CalendarFeed>, actions::DbError> {
+    // Use web::block to avoid blocking async
+    let feeds = web::block(move || {
+        let mut conn = pool.get()?;
+
+        actions::get_calendar_feeds(&mut conn)
+    }).await??;
+
+    calendars.refresh(&feeds).await;
+    Ok(feeds)
+}
+
+/// Refreshes the feeds every `REFRESH_INTERVAL`, starting right away.
+/// Event slides start and stop being shown as time passes, so the screens are told to refetch their slides too.
+pub(crate) async fn refresh_periodically(calendars: web::Data<Calendars>, pool: DbPool, broadcaster: Broadcaster) {
+    let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+    loop {
+        interval.tick().await;
+        match refresh_from_db(&calendars, pool.clone()).await {
+            Ok(feeds) if feeds.iter().any(|feed| feed.slide_days_before.is_some()) => broadcaster.send(ScreenEvent::Slides),
+            Ok(_) => {},
+            Err(e) => log::error!("Failed to refresh calendars: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    use actix_web::{get, App, HttpResponse, HttpServer};
+
+    pub(crate) const CALENDAR_FIXTURE: &str = include_str!("../fixtures/calendar.ics");
+
+    #[get("/calendar.ics")]
+    async fn mock_calendar() -> HttpResponse {
+        HttpResponse::Ok().content_type("text/calendar").body(CALENDAR_FIXTURE)
+    }
+
+    /// Serves the fixture calendar on a random port. Returns its URL.
+    pub(crate) fn start_mock_calendar() -> String {
+        let server = HttpServer::new(|| App::new().service(mock_calendar))
+            .workers(1)
+            .bind(("127.0.0.1", 0))
+            .unwrap();
+        let url = format!("http://{}/calendar.ics", server.addrs()[0]);
+        actix_web::rt::spawn(server.run());
+        url
+    }
+
+    fn stockholm(time: &str) -> DateTime<Utc> {
+        resolve(&chrono_tz::Europe::Stockholm, NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap())
+    }
+
+    /// All-day events and event slides use the server's time zone, which isn't always Stockholm's when testing
+    fn local_midnight(date: &str) -> DateTime<Utc> {
+        IcsTime::Date(date.parse().unwrap()).to_utc()
+    }
+
+    fn events_between(from: &str, until: &str) -> Vec<Event> {
+        parse_events(CALENDAR_FIXTURE, "feed", stockholm(from), stockholm(until)).unwrap()
+    }
+
+    #[test]
+    fn test_weekly_with_exdate_and_moved_occurrence() {
+        let pubs: Vec<_> = events_between("2025-10-01 00:00", "2025-11-30 00:00").into_iter()
+            .filter(|event| event.uid == "pub@fysiksektionen.se")
+            .map(|event| (event.start, event.title))
+            .collect();
+        // Every thursday at 17:00 local time, also after the clocks change on October 26th,
+        // except the cancelled 16th and the one moved to friday the 7th of November
+        assert_eq!(pubs, vec![
+            (stockholm("2025-10-02 17:00"), "Pubkväll".to_string()),
+            (stockholm("2025-10-09 17:00"), "Pubkväll".to_string()),
+            (stockholm("2025-10-23 17:00"), "Pubkväll".to_string()),
+            (stockholm("2025-10-30 17:00"), "Pubkväll".to_string()),
+            (stockholm("2025-11-07 18:00"), "Pubkväll (flyttad)".to_string()),
+            (stockholm("2025-11-13 17:00"), "Pubkväll".to_string()),
+            (stockholm("2025-11-20 17:00"), "Pubkväll".to_string()),
+            (stockholm("2025-11-27 17:00"), "Pubkväll".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_single_and_all_day_events() {
+        let events = events_between("2025-11-01 00:00", "2025-11-30 00:00");
+
+        let meeting = events.iter().find(|event| event.uid == "sm@fysiksektionen.se").unwrap();
+        assert_eq!(meeting.title, "Sektionsmöte, del 1");
+        assert_eq!(meeting.description.as_deref(), Some("Kallelse finns på hemsidan.\nVälkomna!"));
+        assert_eq!(meeting.location.as_deref(), Some("F1"));
+        assert_eq!(meeting.start, "2025-11-12T16:15:00Z".parse::<DateTime<Utc>>().unwrap());
+        assert_eq!(meeting.end, meeting.start + TimeDelta::hours(2));
+        assert!(!meeting.all_day);
+
+        let reception = events.iter().find(|event| event.uid == "jubileum@fysiksektionen.se").unwrap();
+        assert!(reception.all_day);
+        assert_eq!(reception.end - reception.start, TimeDelta::days(2));
+
+        // Cancelled events aren't shown
+        assert!(!events.iter().any(|event| event.uid == "cancelled@fysiksektionen.se"));
+    }
+
+    #[test]
+    fn test_monthly_with_count() {
+        let meetings: Vec<_> = events_between("2025-01-01 00:00", "2026-12-31 00:00").into_iter()
+            .filter(|event| event.uid == "styrelse@fysiksektionen.se")
+            .map(|event| event.start)
+            .collect();
+        // The last monday of the month, six times
+        assert_eq!(meetings, vec![
+            stockholm("2025-09-29 12:10"),
+            stockholm("2025-10-27 12:10"),
+            stockholm("2025-11-24 12:10"),
+            stockholm("2025-12-29 12:10"),
+            stockholm("2026-01-26 12:10"),
+            stockholm("2026-02-23 12:10"),
+        ]);
+    }
+
+    #[test]
+    fn test_recurrence_rules() {
+        let rule: RecurrenceRule = "FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=29".parse().unwrap();
+        let start = IcsTime::Date(NaiveDate::from_ymd_opt(2024, 2, 29).unwrap());
+        let leap_days: Vec<_> = rule.occurrences(start, "2033-01-01T00:00:00Z".parse().unwrap()).unwrap()
+            .into_iter().map(|occurrence| occurrence.wall().date().year()).collect();
+        assert_eq!(leap_days, vec![2024, 2028, 2032]);
+
+        let rule: RecurrenceRule = "FREQ=DAILY;INTERVAL=2;BYDAY=MO,WE,FR;UNTIL=20251117".parse().unwrap();
+        let start = IcsTime::DateTime("2025-11-03T08:00:00".parse().unwrap(), Zone::Utc);
+        let days: Vec<_> = rule.occurrences(start, "2026-01-01T00:00:00Z".parse().unwrap()).unwrap()
+            .into_iter().map(|occurrence| occurrence.wall().date().day()).collect();
+        assert_eq!(days, vec![3, 5, 7, 17]);
+
+        // Rules that never match stop at the last date there is instead of overflowing
+        for rule in ["FREQ=YEARLY;INTERVAL=10;BYMONTH=2;BYMONTHDAY=30", "FREQ=DAILY;INTERVAL=100000000;BYDAY=MO", "FREQ=MONTHLY;INTERVAL=4000000000;BYMONTHDAY=31"] {
+            let rule: RecurrenceRule = rule.parse().unwrap();
+            let start = IcsTime::Date(NaiveDate::from_ymd_opt(2025, 11, 4).unwrap());
+            assert!(rule.occurrences(start, DateTime::<Utc>::MAX_UTC).unwrap().len() <= 1);
+        }
+
+        assert!("FREQ=HOURLY".parse::<RecurrenceRule>().is_err());
+        assert!("INTERVAL=2".parse::<RecurrenceRule>().is_err());
+        assert!("FREQ=WEEKLY;BYDAY=Åx".parse::<RecurrenceRule>().is_err());
+        assert_eq!(parse_duration("PT1H30M"), Ok(TimeDelta::minutes(90)));
+        assert_eq!(parse_duration("P1W2D"), Ok(TimeDelta::days(9)));
+        assert!(parse_duration("P99999999999999W").is_err());
+        assert!(parse_duration("P9223372036854775807DT1S").is_err());
+    }
+
+    #[test]
+    fn test_oversized_events() {
+        let event = |uid: &str, properties: &str| format!("BEGIN:VEVENT\r\nUID:{uid}\r\nSUMMARY:{uid}\r\nDTSTART:20251105T120000Z\r\n{properties}\r\nEND:VEVENT\r\n");
+        let ics = format!(
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\n{}{}{}{}END:VCALENDAR\r\n",
+            event("too-long", "DURATION:P99999999999999W"),
+            event("ends-too-late", "DURATION:P100000000D"),
+            event("bad-rule", "DURATION:PT1H\r\nRRULE:FREQ=WEEKLY;BYDAY=Åx"),
+            event("fine", "DURATION:PT1H"),
+        );
+
+        // The broken events are skipped instead of stopping the whole feed
+        let events = parse_events(&ics, "feed", stockholm("2025-11-01 00:00"), stockholm("2025-11-30 00:00")).unwrap();
+        let uids: Vec<_> = events.iter().map(|event| event.uid.as_str()).collect();
+        assert_eq!(uids, vec!["fine"]);
+    }
+
+    #[test]
+    fn test_event_slides() {
+        let now = stockholm("2025-11-05 12:00");
+        let calendars = Calendars::new();
+        calendars.feeds.write().unwrap().insert("feed".to_string(), FeedStatus {
+            events: parse_events(CALENDAR_FIXTURE, "feed", now, now + TimeDelta::days(LOOKAHEAD_DAYS)).unwrap(),
+            fetched_at: Some(now),
+            error: None,
+        });
+        let feed = CalendarFeed {
+            id: "feed".to_string(),
+            name: "Fixture".to_string(),
+            url: String::new(),
+            created_at: now.naive_utc(),
+            slide_days_before: Some(3),
+        };
+
+        let slides = calendars.event_slides(std::slice::from_ref(&feed), now);
+        // Only the next pub, even though it's every week
+        let pubs: Vec<_> = slides.iter().filter(|slide| slide.event.uid == "pub@fysiksektionen.se").collect();
+        assert_eq!(pubs.len(), 1);
+        assert_eq!(pubs[0].event.title, "Pubkväll (flyttad)");
+        assert_eq!(pubs[0].shown_from, local_midnight("2025-11-04"));
+        // The moved occurrence has the same key as the rest
+        assert_eq!(pubs[0].key, slides.iter().find(|slide| slide.event.uid == "pub@fysiksektionen.se").unwrap().event.slide_key());
+
+        let meeting = slides.iter().find(|slide| slide.event.uid == "sm@fysiksektionen.se").unwrap();
+        assert_eq!(meeting.shown_from, local_midnight("2025-11-09"));
+
+        // Feeds without slides get none
+        let feed = CalendarFeed { slide_days_before: None, ..feed };
+        assert!(calendars.event_slides(&[feed], now).is_empty());
+    }
+}
@@ -0,0 +1,129 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use actix_web::{error, http::header, web, FromRequest};
+use chrono::Utc;
+use rand::{distributions::Slice, Rng};
+use sha2::{Digest, Sha256};
+
+use crate::{actions, models::Device};
+
+use super::DbPool;
+
+// Left out 0/O and 1/I so the code is easy to read off the screen and type in
+const PAIRING_CODE_ALPHABET: [char; 32] = [
+    'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'J', 'K', 'L', 'M', 'N', 'P', 'Q', 'R',
+    'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '2', '3', '4', '5', '6', '7', '8', '9',
+];
+const PAIRING_CODE_LENGTH: usize = 6;
+
+/// A new random device token, only ever given to the kiosk
+pub(crate) fn generate_token() -> String {
+    hex::encode(rand::thread_rng().gen::<[u8; 32]>())
+}
+
+/// Only the hash of a device token is stored, so a leaked database doesn't leak working tokens
+pub(crate) fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// A short code shown on the kiosk that an admin types in to approve it
+pub(crate) fn generate_pairing_code() -> String {
+    let alphabet = Slice::new(&PAIRING_CODE_ALPHABET).expect("alphabet is not empty");
+    rand::thread_rng()
+        .sample_iter(alphabet)
+        .take(PAIRING_CODE_LENGTH)
+        .collect()
+}
+
+/// Normalise a pairing code typed in by an admin
+pub(crate) fn normalize_pairing_code(code: &str) -> String {
+    code.trim().to_uppercase()
+}
+
+/// A kiosk that presented a valid device token as `Authorization: Bearer <token>`.
+/// The device can still be pending; use `screen_id` to check that it has been paired.
+/// Revoked devices and expired pairing requests are rejected with 401.
+#[derive(Debug)]
+pub(crate) struct AuthenticatedDevice(pub(crate) Device);
+
+impl AuthenticatedDevice {
+    /// The screen the device is paired to, or None if it's still waiting for approval
+    pub(crate) fn screen_id(&self) -> Option<&str> {
+        self.0.screen_id.as_deref()
+    }
+}
+
+impl FromRequest for AuthenticatedDevice {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &actix_web::HttpRequest, _payload: &mut actix_web::dev::Payload) -> Self::Future {
+        let token = req.headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(hash_token);
+        let pool = req.app_data::<web::Data<DbPool>>().cloned();
+
+        Box::pin(async move {
+            let Some(token_hash) = token else {
+                return Err(error::ErrorUnauthorized("No device token"));
+            };
+            let pool = pool.ok_or_else(|| error::ErrorInternalServerError("No database pool"))?;
+
+            let device = web::block(move || {
+                let mut conn = pool.get()?;
+                actions::get_device_by_token_hash(&mut conn, &token_hash, Utc::now().naive_utc())
+            })
+            .await?
+            .map_err(error::ErrorInternalServerError)?;
+
+            device
+                .map(AuthenticatedDevice)
+                .ok_or_else(|| error::ErrorUnauthorized("Unknown or revoked device token"))
+        })
+    }
+}
+
+/// A kiosk that has been paired to a screen. Screen routes that aren't for one screen in particular
+/// (like `/screen/slides`) only need this. Devices that are still pending are rejected with 403.
+#[derive(Debug)]
+pub(crate) struct PairedDevice;
+
+impl FromRequest for PairedDevice {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &actix_web::HttpRequest, payload: &mut actix_web::dev::Payload) -> Self::Future {
+        let device = AuthenticatedDevice::from_request(req, payload);
+
+        Box::pin(async move {
+            match device.await?.screen_id() {
+                Some(_) => Ok(PairedDevice),
+                None => Err(error::ErrorForbidden("The device hasn't been paired yet")),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pairing_code() {
+        let code = generate_pairing_code();
+        assert_eq!(code.len(), PAIRING_CODE_LENGTH);
+        assert!(code.chars().all(|c| PAIRING_CODE_ALPHABET.contains(&c)));
+        assert_eq!(normalize_pairing_code(&format!(" {} ", code.to_lowercase())), code);
+    }
+
+    #[test]
+    fn test_token_hash() {
+        let token = generate_token();
+        assert_ne!(token, generate_token());
+        assert_eq!(hash_token(&token), hash_token(&token));
+        assert_ne!(hash_token(&token), token);
+    }
+}
@@ -6,6 +6,27 @@ use std::path::PathBuf;
 
 use actix_multipart::form::tempfile::TempFile;
 
+/// The file extension for an uploaded image, or None if it isn't in a format the screens show.
+/// This goes by an allow-list rather than the client's MIME subtype, since e.g. SVGs can contain scripts.
+pub(crate) fn image_filetype(file: &TempFile) -> Option<&'static str> {
+    match file.content_type.as_ref()?.essence_str() {
+        "image/png" => Some("png"),
+        "image/jpeg" => Some("jpeg"),
+        "image/webp" => Some("webp"),
+        "image/gif" => Some("gif"),
+        _ => None,
+    }
+}
+
+/// The path an image for a slide with the given id and filetype is saved at
+pub(crate) fn image_path(filename: &str, file_type: &str) -> PathBuf {
+    // The file path is the SLIDE_IMAGE_DIR + filename. This is colleted into a PathBuf
+    let mut file_path: PathBuf = [SLIDE_IMAGE_DIR, filename].iter().collect();
+    // The file extension is the filetype
+    file_path.set_extension(file_type);
+    file_path
+}
+
 pub(crate) async fn save_image_file(
     temp_file: TempFile,
     filename: &str,
@@ -14,10 +35,7 @@ pub(crate) async fn save_image_file(
     // The filetype can be determined from the data in the TempFile itself, but since we've already had to determine it earlier,
     // we can pass it in as an argument instead
 
-    // The file path is the SLIDE_IMAGE_DIR + filename. This is colleted into a PathBuf
-    let mut file_path: PathBuf = [SLIDE_IMAGE_DIR, filename].iter().collect();
-    // The file extension is the filetype
-    file_path.set_extension(file_type);
+    let file_path = image_path(filename, file_type);
 
     let saved_path = file_path.clone(); // We need to clone the path because we want to return it later
 
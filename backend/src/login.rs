@@ -0,0 +1,412 @@
+//! Checking the ID tokens users log in with. Each identity provider (Google, or any OpenID Connect issuer like
+//! KTH's) turns a token into the email it was issued for, and `verify_token` takes it from there.
+//! Providers are configured with environment variables, and the ones that aren't configured are left out.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use futures_util::future::LocalBoxFuture;
+use google_oauth::AsyncClient;
+use jsonwebtoken::jwk::{Jwk, JwkSet};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde_json::Value;
+
+/// The provider used when a login doesn't say which one
+pub(crate) const GOOGLE: &str = "google";
+pub(crate) const OIDC: &str = "oidc";
+
+// Only asymmetric algorithms, since the keys come from a public JWKS
+const ALGORITHMS: &[Algorithm] = &[
+    Algorithm::RS256, Algorithm::RS384, Algorithm::RS512,
+    Algorithm::PS256, Algorithm::PS384, Algorithm::PS512,
+    Algorithm::ES256, Algorithm::ES384, Algorithm::EdDSA,
+];
+// Issuers rotate their keys rarely, and a token with an unknown key makes us fetch them again anyway
+const JWKS_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+// So tokens with made up key ids can't make us fetch the JWKS on every login
+const JWKS_MIN_REFRESH: Duration = Duration::from_secs(60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Who an ID token was issued for
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Identity {
+    pub email: String,
+    /// Whether the provider has checked that the user owns the email. Only verified emails are added by
+    /// provisioning rules and invites.
+    pub email_verified: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum LoginError {
+    /// The token is invalid, expired, for someone else or lacks an allowed claim
+    Invalid(String),
+    /// The token couldn't be checked, e.g. because the JWKS couldn't be fetched
+    Unavailable(String),
+}
+
+pub(crate) trait IdentityProvider: Send + Sync {
+    /// Checks an ID token and returns who it was issued for
+    fn verify<'a>(&'a self, id_token: &'a str) -> LocalBoxFuture<'a, Result<Identity, LoginError>>;
+}
+
+/// Google Sign-In, with `GOOGLE_ID_TOKEN` as the client ID
+pub(crate) struct GoogleProvider {
+    client: AsyncClient,
+}
+
+impl GoogleProvider {
+    pub(crate) fn new(client_id: &str) -> Self {
+        // One client for all logins, so Google's keys are cached
+        GoogleProvider { client: AsyncClient::new(client_id) }
+    }
+}
+
+impl IdentityProvider for GoogleProvider {
+    fn verify<'a>(&'a self, id_token: &'a str) -> LocalBoxFuture<'a, Result<Identity, LoginError>> {
+        Box::pin(async move {
+            let payload = self.client.validate_id_token(id_token).await
+                .map_err(|e| LoginError::Invalid(e.to_string()))?;
+            let email = payload.email.ok_or_else(|| LoginError::Invalid("No email in the token".to_string()))?;
+            Ok(Identity { email, email_verified: payload.email_verified == Some(true) })
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct OidcConfig {
+    /// Has to match the token's `iss`
+    pub issuer: String,
+    pub jwks_url: String,
+    /// Has to be in the token's `aud`
+    pub client_id: String,
+    /// Claims the token has to have, and the values allowed for each. A claim that is a list has to contain one of them.
+    pub allowed_claims: HashMap<String, Vec<String>>,
+}
+
+/// Parses `OIDC_ALLOWED_CLAIMS`, e.g. `affiliation=student,affiliation=employee,hd=kth.se`
+/// (an `affiliation` that is `student` or `employee`, and `hd` `kth.se`)
+pub(crate) fn parse_allowed_claims(spec: &str) -> Result<HashMap<String, Vec<String>>, String> {
+    let mut allowed: HashMap<String, Vec<String>> = HashMap::new();
+    for pair in spec.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+        let Some((claim, value)) = pair.split_once('=') else {
+            return Err(format!("Expected claim=value, got {pair:?}"));
+        };
+        allowed.entry(claim.trim().to_string()).or_default().push(value.trim().to_string());
+    }
+    Ok(allowed)
+}
+
+/// Checks that every allowed claim is in `claims` with an allowed value
+fn check_allowed_claims(claims: &serde_json::Map<String, Value>, allowed: &HashMap<String, Vec<String>>) -> Result<(), String> {
+    let matches = |value: &Value, values: &[String]| match value {
+        Value::String(s) => values.contains(s),
+        Value::Bool(_) | Value::Number(_) => values.contains(&value.to_string()),
+        _ => false,
+    };
+    for (claim, values) in allowed {
+        let allowed = match claims.get(claim) {
+            Some(Value::Array(items)) => items.iter().any(|item| matches(item, values)),
+            Some(value) => matches(value, values),
+            None => false,
+        };
+        if !allowed {
+            return Err(format!("The {claim} claim is missing or not allowed"));
+        }
+    }
+    Ok(())
+}
+
+/// Any OpenID Connect issuer. ID tokens are checked against the issuer's JWKS, which is cached.
+pub(crate) struct OidcProvider {
+    config: OidcConfig,
+    http: reqwest::Client,
+    jwks: Mutex<Option<(Instant, JwkSet)>>,
+}
+
+impl OidcProvider {
+    pub(crate) fn new(config: OidcConfig) -> Self {
+        OidcProvider {
+            config,
+            http: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .expect("Failed to build HTTP client"),
+            jwks: Mutex::new(None),
+        }
+    }
+
+    async fn fetch_jwks(&self) -> Result<JwkSet, LoginError> {
+        let response = self.http.get(&self.config.jwks_url).send().await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| LoginError::Unavailable(format!("Failed to fetch the JWKS: {e}")))?;
+        let body = response.text().await.map_err(|e| LoginError::Unavailable(format!("Failed to read the JWKS: {e}")))?;
+        serde_json::from_str(&body).map_err(|e| LoginError::Unavailable(format!("Failed to parse the JWKS: {e}")))
+    }
+
+    /// The key with this id, or the only key if the token doesn't say
+    async fn key(&self, kid: Option<&str>) -> Result<Jwk, LoginError> {
+        let find = |jwks: &JwkSet| match kid {
+            Some(kid) => jwks.find(kid).cloned(),
+            None if jwks.keys.len() == 1 => Some(jwks.keys[0].clone()),
+            None => None,
+        };
+
+        if let Some((fetched_at, jwks)) = &*self.jwks.lock().unwrap() {
+            match find(jwks) {
+                Some(jwk) if fetched_at.elapsed() < JWKS_CACHE_TTL => return Ok(jwk),
+                None if fetched_at.elapsed() < JWKS_MIN_REFRESH => return Err(LoginError::Invalid("Unknown key".to_string())),
+                _ => {},
+            }
+        }
+
+        let jwks = self.fetch_jwks().await?;
+        let found = find(&jwks);
+        *self.jwks.lock().unwrap() = Some((Instant::now(), jwks));
+        found.ok_or_else(|| LoginError::Invalid("Unknown key".to_string()))
+    }
+}
+
+impl IdentityProvider for OidcProvider {
+    fn verify<'a>(&'a self, id_token: &'a str) -> LocalBoxFuture<'a, Result<Identity, LoginError>> {
+        Box::pin(async move {
+            let invalid = |e: jsonwebtoken::errors::Error| LoginError::Invalid(e.to_string());
+
+            let header = jsonwebtoken::decode_header(id_token).map_err(invalid)?;
+            if !ALGORITHMS.contains(&header.alg) {
+                return Err(LoginError::Invalid(format!("{:?} is not allowed", header.alg)));
+            }
+            let key = DecodingKey::from_jwk(&self.key(header.kid.as_deref()).await?).map_err(invalid)?;
+
+            let mut validation = Validation::new(header.alg);
+            validation.set_issuer(&[&self.config.issuer]);
+            validation.set_audience(&[&self.config.client_id]);
+            validation.set_required_spec_claims(&["exp", "iss", "aud"]);
+            let claims = jsonwebtoken::decode::<serde_json::Map<String, Value>>(id_token, &key, &validation)
+                .map_err(invalid)?
+                .claims;
+
+            check_allowed_claims(&claims, &self.config.allowed_claims).map_err(LoginError::Invalid)?;
+            let email = claims.get("email").and_then(Value::as_str)
+                .ok_or_else(|| LoginError::Invalid("No email in the token".to_string()))?;
+            // Some issuers send it as a string
+            let email_verified = matches!(claims.get("email_verified"), Some(Value::Bool(true))) || claims.get("email_verified") == Some(&Value::from("true"));
+
+            Ok(Identity { email: email.to_string(), email_verified })
+        })
+    }
+}
+
+/// The configured identity providers, by the name logins use for them
+pub(crate) struct Providers {
+    providers: HashMap<&'static str, Box<dyn IdentityProvider>>,
+}
+
+impl Providers {
+    pub(crate) fn new(google_client_id: Option<&str>, oidc: Option<OidcConfig>) -> Self {
+        let mut providers: HashMap<&'static str, Box<dyn IdentityProvider>> = HashMap::new();
+        if let Some(client_id) = google_client_id {
+            providers.insert(GOOGLE, Box::new(GoogleProvider::new(client_id)));
+        }
+        if let Some(config) = oidc {
+            providers.insert(OIDC, Box::new(OidcProvider::new(config)));
+        }
+        Providers { providers }
+    }
+
+    /// Google if `GOOGLE_ID_TOKEN` is set, and OpenID Connect if `OIDC_ISSUER`, `OIDC_JWKS_URL` and `OIDC_CLIENT_ID` are.
+    /// `OIDC_ALLOWED_CLAIMS` is optional, see `parse_allowed_claims`.
+    pub(crate) fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.trim().is_empty());
+
+        let google_client_id = var("GOOGLE_ID_TOKEN");
+        if google_client_id.is_none() {
+            log::warn!("GOOGLE_ID_TOKEN not set; logging in with Google is disabled");
+        }
+
+        let oidc = match (var("OIDC_ISSUER"), var("OIDC_JWKS_URL"), var("OIDC_CLIENT_ID")) {
+            (Some(issuer), Some(jwks_url), Some(client_id)) => {
+                match parse_allowed_claims(&var("OIDC_ALLOWED_CLAIMS").unwrap_or_default()) {
+                    Ok(allowed_claims) => Some(OidcConfig { issuer, jwks_url, client_id, allowed_claims }),
+                    Err(e) => {
+                        log::error!("Invalid OIDC_ALLOWED_CLAIMS, logging in with OpenID Connect is disabled: {e}");
+                        None
+                    },
+                }
+            },
+            (None, None, None) => None,
+            _ => {
+                log::error!("OIDC_ISSUER, OIDC_JWKS_URL and OIDC_CLIENT_ID have to be set together; logging in with OpenID Connect is disabled");
+                None
+            },
+        };
+
+        let providers = Self::new(google_client_id.as_deref(), oidc);
+        if providers.providers.is_empty() {
+            log::warn!("No identity provider is configured, so nobody can log in");
+        }
+        providers
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&dyn IdentityProvider> {
+        self.providers.get(name).map(|provider| provider.as_ref())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use actix_web::{get, web, App, HttpResponse, HttpServer};
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use jsonwebtoken::{EncodingKey, Header};
+    use ring::rand::SystemRandom;
+    use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING};
+    use serde_json::json;
+
+    use super::*;
+
+    pub(crate) const CLIENT_ID: &str = "konsol";
+
+    /// A signing key, and its public half as a JWK
+    fn generate_key(kid: &str) -> (EncodingKey, Value) {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+        let key_pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng).unwrap();
+        // An uncompressed point: 0x04, then x and y
+        let point = key_pair.public_key().as_ref();
+        let jwk = json!({
+            "kty": "EC",
+            "crv": "P-256",
+            "x": URL_SAFE_NO_PAD.encode(&point[1..33]),
+            "y": URL_SAFE_NO_PAD.encode(&point[33..]),
+            "kid": kid,
+            "use": "sig",
+            "alg": "ES256",
+        });
+        (EncodingKey::from_ec_der(pkcs8.as_ref()), jwk)
+    }
+
+    #[get("/jwks")]
+    async fn fake_jwks(jwks: web::Data<Value>, requests: web::Data<Arc<AtomicUsize>>) -> HttpResponse {
+        requests.fetch_add(1, Ordering::SeqCst);
+        HttpResponse::Ok().json(jwks.get_ref())
+    }
+
+    /// An OpenID Connect issuer serving its JWKS on a random port
+    pub(crate) struct FakeIssuer {
+        pub url: String,
+        pub requests: Arc<AtomicUsize>,
+        key: EncodingKey,
+    }
+
+    impl FakeIssuer {
+        pub(crate) fn start() -> Self {
+            let (key, jwk) = generate_key("test-key");
+            let jwks = json!({ "keys": [jwk] });
+            let requests = Arc::new(AtomicUsize::new(0));
+            let data = requests.clone();
+            let server = HttpServer::new(move || App::new()
+                    .app_data(web::Data::new(jwks.clone()))
+                    .app_data(web::Data::new(data.clone()))
+                    .service(fake_jwks))
+                .workers(1)
+                .bind(("127.0.0.1", 0))
+                .unwrap();
+            let url = format!("http://{}", server.addrs()[0]);
+            actix_web::rt::spawn(server.run());
+            FakeIssuer { url, requests, key }
+        }
+
+        pub(crate) fn config(&self) -> OidcConfig {
+            OidcConfig {
+                issuer: self.url.clone(),
+                jwks_url: format!("{}/jwks", self.url),
+                client_id: CLIENT_ID.to_string(),
+                allowed_claims: HashMap::new(),
+            }
+        }
+
+        /// A valid ID token for `email`, with `extra` claims added or replaced
+        pub(crate) fn id_token(&self, email: &str, extra: Value) -> String {
+            sign(&self.key, "test-key", self.claims(email, extra))
+        }
+
+        fn claims(&self, email: &str, extra: Value) -> Value {
+            let mut claims = json!({
+                "iss": self.url,
+                "aud": CLIENT_ID,
+                "sub": "123",
+                "email": email,
+                "email_verified": true,
+                "iat": chrono::Utc::now().timestamp(),
+                "exp": chrono::Utc::now().timestamp() + 300,
+            });
+            claims.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+            claims
+        }
+    }
+
+    fn sign(key: &EncodingKey, kid: &str, claims: Value) -> String {
+        let mut header = Header::new(Algorithm::ES256);
+        header.kid = Some(kid.to_string());
+        jsonwebtoken::encode(&header, &claims, key).unwrap()
+    }
+
+    fn is_invalid<T: std::fmt::Debug>(result: Result<T, LoginError>) -> bool {
+        matches!(result, Err(LoginError::Invalid(_)))
+    }
+
+    #[actix_web::test]
+    async fn test_oidc_tokens() {
+        let issuer = FakeIssuer::start();
+        let provider = OidcProvider::new(issuer.config());
+
+        let identity = provider.verify(&issuer.id_token("someone@kth.se", json!({}))).await.unwrap();
+        assert_eq!(identity, Identity { email: "someone@kth.se".to_string(), email_verified: true });
+        let unverified = issuer.id_token("someone@kth.se", json!({ "email_verified": "false" }));
+        assert!(!provider.verify(&unverified).await.unwrap().email_verified);
+
+        assert!(is_invalid(provider.verify(&issuer.id_token("someone@kth.se", json!({ "aud": "someone-else" }))).await));
+        assert!(is_invalid(provider.verify(&issuer.id_token("someone@kth.se", json!({ "iss": "https://evil.example.com" }))).await));
+        assert!(is_invalid(provider.verify(&issuer.id_token("someone@kth.se", json!({ "exp": 1_000_000 }))).await));
+        assert!(is_invalid(provider.verify(&issuer.id_token("someone@kth.se", json!({ "email": null }))).await));
+        assert!(is_invalid(provider.verify("not a token").await));
+
+        // Signed with a key that isn't in the JWKS, with or without a key id from it
+        let (other_key, _) = generate_key("other-key");
+        assert!(is_invalid(provider.verify(&sign(&other_key, "test-key", issuer.claims("someone@kth.se", json!({})))).await));
+        assert!(is_invalid(provider.verify(&sign(&other_key, "other-key", issuer.claims("someone@kth.se", json!({})))).await));
+        // The JWKS was fetched once, and a made up key id doesn't make us fetch it again right away
+        assert_eq!(issuer.requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_oidc_allowed_claims() {
+        let issuer = FakeIssuer::start();
+        let provider = OidcProvider::new(OidcConfig {
+            allowed_claims: parse_allowed_claims("affiliation=student, affiliation=employee,hd=kth.se").unwrap(),
+            ..issuer.config()
+        });
+
+        let token = |extra| issuer.id_token("someone@kth.se", extra);
+        assert!(provider.verify(&token(json!({ "affiliation": "student", "hd": "kth.se" }))).await.is_ok());
+        assert!(provider.verify(&token(json!({ "affiliation": ["member", "employee"], "hd": "kth.se" }))).await.is_ok());
+        assert!(is_invalid(provider.verify(&token(json!({ "affiliation": "alum", "hd": "kth.se" }))).await));
+        assert!(is_invalid(provider.verify(&token(json!({ "affiliation": "student" }))).await));
+
+        assert!(parse_allowed_claims("hd").is_err());
+        assert!(parse_allowed_claims("").unwrap().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_oidc_jwks_unavailable() {
+        let issuer = FakeIssuer::start();
+        let provider = OidcProvider::new(OidcConfig { jwks_url: format!("{}/missing", issuer.url), ..issuer.config() });
+
+        let result = provider.verify(&issuer.id_token("someone@kth.se", json!({}))).await;
+        assert!(matches!(result, Err(LoginError::Unavailable(_))));
+    }
+}
@@ -3,8 +3,9 @@ extern crate diesel;
 
 use std::fs;
 
-use actix_session::{storage::CookieSessionStore, SessionMiddleware};
-use actix_web::{cookie::Key, error, middleware, web, App, HttpServer};
+use actix_session::config::{PersistentSession, TtlExtensionPolicy};
+use actix_session::SessionMiddleware;
+use actix_web::{cookie::{time::Duration as CookieDuration, Key}, error, middleware, web, App, HttpServer};
 use actix_multipart::form::{tempfile::TempFile, text::Text, MultipartForm};
 use actix_cors::Cors;
 use chrono::{NaiveDate, NaiveTime};
@@ -17,7 +18,17 @@ mod models;
 mod schema;
 mod fs_helpers;
 mod routes;
+mod audit;
 mod auth;
+mod broadcast;
+mod calendar;
+mod devices;
+mod login;
+mod provisioning;
+mod schedule;
+mod sessions;
+mod settings_registry;
+mod sl;
 
 /// Short-hand for the database pool type to use throughout the app.
 type DbPool = r2d2::Pool<r2d2::ConnectionManager<SqliteConnection>>;
@@ -36,15 +47,25 @@ struct SlideUploadForm {
     visible: Text<bool>,
     #[multipart(rename = "imageFile")]
     image_file: TempFile,
+    /// The group that will own the slide. Left out (or empty) for slides that don't belong to a group.
+    group_id: Option<Text<String>>,
+    /// Keep the slide as a draft instead of submitting it for review
+    draft: Option<Text<bool>>,
+}
+
+/// A poster for a slide generated from a calendar event
+#[derive(Debug, MultipartForm)]
+struct EventPosterForm {
+    #[multipart(rename = "imageFile")]
+    image_file: TempFile,
 }
 
 impl SlideUploadForm {
-    fn parse_form(self, id: Uuid) -> Result<(models::Slide, TempFile), actix_web::Error> {
+    fn parse_form(self, id: Uuid, created_by: &str) -> Result<(models::Slide, TempFile), actix_web::Error> {
         
-        // Get the MIME type of the file. This determines the file extension
-        let mime = match self.image_file.content_type {
-            Some(ref mime) => mime,
-            None => {return Err(error::ErrorInternalServerError("No content type found for uploaded file"))},
+        // Get the file extension from the MIME type of the file
+        let Some(filetype) = fs_helpers::image_filetype(&self.image_file) else {
+            return Err(error::ErrorBadRequest("The image must be a PNG, JPEG, WebP or GIF"));
         };
 
         Ok((
@@ -60,7 +81,14 @@ impl SlideUploadForm {
                 .map_err(error::ErrorInternalServerError)?
                 .and_time(NaiveTime::from_hms_opt(0, 0, 0).unwrap()),
                 active: self.visible.into_inner(),
-                filetype: mime.subtype().to_string(),
+                filetype: filetype.to_string(),
+                created_by: Some(created_by.to_string()),
+                group_id: self.group_id.map(Text::into_inner).filter(|group| !group.is_empty()),
+                status: if self.draft.is_some_and(Text::into_inner) {
+                    models::SlideStatus::Draft
+                } else {
+                    models::SlideStatus::Pending
+                },
             },
         
             self.image_file
@@ -73,7 +101,7 @@ async fn main() -> std::io::Result<()> {
     dotenvy::dotenv().ok();
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
 
-    // Secret key for cookie session store
+    // Secret key for the session cookies
     let secret_key_string = std::env::var("COOKIE_SECRET_KEY")
         .unwrap_or_else(|_| {
             log::warn!("COOKIE_SECRET_KEY not set; using default insecure key mashed key");
@@ -107,6 +135,15 @@ async fn main() -> std::io::Result<()> {
 
     log::info!("saving images at {SLIDE_IMAGE_DIR}");
 
+    // Like the DB pool, the broadcaster is shared across all workers so every screen gets every event
+    let broadcaster = broadcast::Broadcaster::new();
+    // Shared so the departures cache is too
+    let sl_client = web::Data::new(sl::SlClient::from_env());
+    let calendars = web::Data::new(calendar::Calendars::new());
+    // Shared so the providers' keys are only fetched once
+    let providers = web::Data::new(login::Providers::from_env());
+    actix_web::rt::spawn(calendar::refresh_periodically(calendars.clone(), pool.clone(), broadcaster.clone()));
+
     log::info!("starting Actix backend at http://0.0.0.0:8080");
 
 
@@ -120,23 +157,110 @@ async fn main() -> std::io::Result<()> {
         App::new()
             // add DB pool handle to app data; enables use of `web::Data<DbPool>` extractor
             .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::new(broadcaster.clone()))
+            .app_data(sl_client.clone())
+            .app_data(calendars.clone())
+            .app_data(providers.clone())
             // add request logger middleware
             .wrap(middleware::Logger::default())
             .wrap(
-                SessionMiddleware::builder(CookieSessionStore::default(), secret_key.clone())
+                SessionMiddleware::builder(sessions::SqliteSessionStore::new(pool.clone()), secret_key.clone())
                 .cookie_secure(secure_cookies) // Controlled via COOKIE_SECURE env var; should be true in production
+                // Every request moves the expiry forward, so a session only ends after IDLE_TIMEOUT_DAYS without use
+                .session_lifecycle(PersistentSession::default()
+                    .session_ttl(CookieDuration::days(sessions::IDLE_TIMEOUT_DAYS))
+                    .session_ttl_extension_policy(TtlExtensionPolicy::OnEveryRequest))
                 .build()
                 )
             .wrap(cors)
             .service(routes::save_slide)
+            .service(routes::list_pending_slides)
+            .service(routes::get_all_slides)
             .service(routes::get_slides)
+            .service(routes::delete_slide)
+            .service(routes::get_slide_screens)
+            .service(routes::set_slide_screens)
+            .service(routes::submit_slide)
+            .service(routes::review_slide)
+            .service(routes::get_slide_history)
+            .service(routes::set_override)
+            .service(routes::clear_override)
+            .service(routes::get_override)
+            .service(routes::list_overrides)
+            .service(routes::screen_stream)
             .service(routes::verify_token)
             .service(routes::login_status)
             .service(routes::logout)
+            .service(routes::list_own_sessions)
+            .service(routes::list_all_sessions)
+            .service(routes::revoke_session)
             .service(routes::add_user)
             .service(routes::remove_user)
+            .service(routes::update_user)
             .service(routes::list_users)
+            .service(routes::get_provisioning)
+            .service(routes::set_provisioning_domain)
+            .service(routes::remove_provisioning_domain)
+            .service(routes::set_provisioning_email)
+            .service(routes::remove_provisioning_email)
+            .service(routes::create_invite)
+            .service(routes::list_invites)
+            .service(routes::revoke_invite)
+            .service(routes::list_groups)
+            .service(routes::add_group)
+            .service(routes::remove_group)
+            .service(routes::set_group_member)
+            .service(routes::remove_group_member)
             .service(routes::get_settings)
+            .service(routes::update_settings)
+            .service(routes::list_registry_settings)
+            .service(routes::set_registry_setting)
+            .service(routes::reset_registry_setting)
+            .service(routes::list_settings_schedules)
+            .service(routes::add_settings_schedule)
+            .service(routes::update_settings_schedule)
+            .service(routes::remove_settings_schedule)
+            .service(routes::get_quiet_hours)
+            .service(routes::set_quiet_hours)
+            .service(routes::list_screens)
+            .service(routes::add_screen)
+            .service(routes::update_screen)
+            .service(routes::remove_screen)
+            .service(routes::get_screen_slides)
+            .service(routes::get_screen_settings)
+            .service(routes::get_departures)
+            .service(routes::get_screen_departures)
+            .service(routes::get_deviations)
+            .service(routes::get_screen_deviations)
+            .service(routes::list_sl_deviations)
+            .service(routes::mute_sl_deviation)
+            .service(routes::unmute_sl_deviation)
+            .service(routes::get_events)
+            .service(routes::list_calendar_feeds)
+            .service(routes::add_calendar_feed)
+            .service(routes::update_calendar_feed)
+            .service(routes::remove_calendar_feed)
+            .service(routes::list_event_slides)
+            .service(routes::update_event_slide)
+            .service(routes::upload_event_poster)
+            .service(routes::remove_event_poster)
+            .service(routes::list_sl_sites)
+            .service(routes::set_sl_site)
+            .service(routes::remove_sl_site)
+            .service(routes::request_pairing)
+            .service(routes::device_status)
+            .service(routes::approve_device)
+            .service(routes::list_devices)
+            .service(routes::revoke_device)
+            .service(routes::device_heartbeat)
+            .service(routes::devices_health)
+            .service(routes::queue_screen_command)
+            .service(routes::list_screen_commands)
+            .service(routes::get_device_commands)
+            .service(routes::acknowledge_device_command)
+            .service(routes::report_plays)
+            .service(routes::get_impressions)
+            .service(routes::list_audit_events)
             .service(actix_files::Files::new("/slides/images",SLIDE_IMAGE_DIR))
     })
     .bind(("0.0.0.0", 8080))?
@@ -157,30 +281,1031 @@ fn initialize_db_pool() -> DbPool {
 
 #[cfg(test)]
 mod tests {
+    use actix_session::storage::CookieSessionStore;
     use actix_web::{http::StatusCode, test};
 
     use super::*;
 
+    /// A pool with a single in-memory database that has all migrations applied.
+    /// Every connection to `:memory:` is its own database, so the pool can only have one.
+    fn initialize_test_db_pool() -> DbPool {
+        let manager = r2d2::ConnectionManager::<SqliteConnection>::new(":memory:");
+        let pool = r2d2::Pool::builder()
+            .max_size(1)
+            .build(manager)
+            .expect("could not create in-memory test database");
+        pool.get().unwrap().run_pending_migrations(MIGRATIONS).unwrap();
+        pool
+    }
+
     #[actix_web::test]
     async fn test_get_slides_returns_ok() {
-        dotenvy::dotenv().ok();
         env_logger::try_init_from_env(env_logger::Env::new().default_filter_or("info")).ok();
 
-        let pool = initialize_db_pool();
-
-        
+        let pool = initialize_test_db_pool();
+        let device = paired_device_header(&pool);
 
         let app = test::init_service(
             App::new()
                 .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(broadcast::Broadcaster::new()))
+                .app_data(web::Data::new(calendar::Calendars::new()))
                 .wrap(middleware::Logger::default())
                 .service(routes::save_slide)
                 .service(routes::get_slides),
         )
         .await;
 
-        let req1 = test::TestRequest::get().uri("/api/screen/slides").to_request();
+        let req1 = test::TestRequest::get().uri("/screen/slides").insert_header(device.clone()).to_request();
         let res1 = test::call_service(&app, req1).await;
         assert_eq!(res1.status(), StatusCode::OK);
     }
+
+    #[actix_web::test]
+    async fn test_screen_stream_is_event_stream() {
+        let pool = initialize_test_db_pool();
+        let device = paired_device_header(&pool);
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(broadcast::Broadcaster::new()))
+                .service(routes::screen_stream),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/screen/stream").insert_header(device.clone()).to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("content-type").unwrap(), "text/event-stream");
+    }
+
+    #[actix_web::test]
+    async fn test_screen_settings_fall_back_to_defaults() {
+        let pool = initialize_test_db_pool();
+        let screen = actions::insert_screen(&mut pool.get().unwrap(), models::Screen {
+            id: Uuid::new_v4().into(),
+            name: "Entrance".to_string(),
+            layout_type: Some(models::LayoutType::FullscreenSlideshow),
+            color_mode: None,
+        }).unwrap();
+        actions::set_screen_sl_sites(&mut pool.get().unwrap(), &screen.id, &[9204]).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .service(routes::get_screen_settings),
+        )
+        .await;
+
+        let token = pair_test_device(&pool, &screen.id);
+
+        let req = test::TestRequest::get().uri(&format!("/screens/{}/settings", screen.id))
+            .insert_header(("Authorization", format!("Bearer {token}")))
+            .to_request();
+        let res: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res, serde_json::json!({
+            "layout_type": "fullscreen_slideshow",
+            "color_mode": "light_mode",
+            "quiet": false,
+            "next_change": null,
+            "slide_interval_seconds": 3,
+            "sl_refresh_seconds": 5,
+            "show_clock": false,
+            "sl_site_ids": [9204],
+        }));
+
+        // A device can't read the settings of another screen
+        let req = test::TestRequest::get().uri("/screens/unknown/settings")
+            .insert_header(("Authorization", format!("Bearer {token}")))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn test_invalid_stored_settings() {
+        let pool = initialize_test_db_pool();
+        let device = paired_device_header(&pool);
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .service(routes::get_settings),
+        )
+        .await;
+
+        actions::update_settings(&mut pool.get().unwrap(), models::LayoutType::Mixed, models::ColorMode::DarkMode).unwrap();
+        let req = test::TestRequest::get().uri("/screen/settings").insert_header(device.clone()).to_request();
+        let res: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res, serde_json::json!({
+            "layout_type": "mixed",
+            "color_mode": "dark_mode",
+            "quiet": false,
+            "next_change": null,
+            "slide_interval_seconds": 3,
+            "sl_refresh_seconds": 5,
+            "show_clock": false,
+        }));
+
+        // Values that were edited by hand into something the screens don't know
+        diesel::sql_query("UPDATE setting_values SET value = '\"neon\"' WHERE key = 'color_mode'").execute(&mut pool.get().unwrap()).unwrap();
+        let req = test::TestRequest::get().uri("/screen/settings").insert_header(device.clone()).to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[actix_web::test]
+    async fn test_active_settings_schedule() {
+        let pool = initialize_test_db_pool();
+        let device = paired_device_header(&pool);
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .service(routes::get_settings),
+        )
+        .await;
+
+        let now = chrono::Local::now().naive_local();
+        let end = now + chrono::TimeDelta::hours(1);
+        actions::insert_settings_schedule(&mut pool.get().unwrap(), &models::SettingsSchedule {
+            id: Uuid::new_v4().into(),
+            name: "Pub night".to_string(),
+            layout_type: None,
+            color_mode: Some(models::ColorMode::DarkMode),
+            weekdays: None,
+            start_time: None,
+            end_time: None,
+            start_at: Some(now - chrono::TimeDelta::hours(1)),
+            end_at: Some(end),
+            created_at: chrono::Utc::now().naive_utc(),
+        }).unwrap();
+
+        let req = test::TestRequest::get().uri("/screen/settings").insert_header(device.clone()).to_request();
+        let res: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res["layout_type"], "mixed");
+        assert_eq!(res["color_mode"], "dark_mode");
+        let next_change: chrono::DateTime<chrono::Utc> = serde_json::from_value(res["next_change"].clone()).unwrap();
+        assert_eq!(next_change.with_timezone(&chrono::Local).naive_local(), end);
+    }
+
+    #[actix_web::test]
+    async fn test_departures_from_tracked_sites() {
+        let pool = initialize_test_db_pool();
+        let device = paired_device_header(&pool);
+        let (sl_url, _) = sl::tests::start_mock_sl_api();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(sl::SlClient::new(sl_url.clone(), sl_url)))
+                .service(routes::get_departures),
+        )
+        .await;
+
+        // Only the red line towards Mörby from Tekniska högskolan. The mock doesn't know Östra station.
+        actions::set_sl_tracked_site(
+            &mut pool.get().unwrap(),
+            &models::SlTrackedSite { site_id: 9204, name: "Tekniska högskolan".to_string() },
+            &[models::SlTrackedLine { site_id: 9204, transport_mode: "METRO".to_string(), line_id: 14, direction_code: Some(1) }],
+        ).unwrap();
+
+        let req = test::TestRequest::get().uri("/screen/departures").insert_header(device.clone()).to_request();
+        let res: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res["failed_site_ids"], serde_json::json!([9600]));
+        let departures = res["departures"].as_array().unwrap();
+        assert_eq!(departures.len(), 1);
+        assert_eq!(departures[0]["destination"], "Mörby centrum");
+        assert_eq!(departures[0]["expected_time"], "2025-11-07T12:03:30");
+    }
+
+    #[actix_web::test]
+    async fn test_deviations_from_tracked_sites() {
+        let pool = initialize_test_db_pool();
+        let device = paired_device_header(&pool);
+        let (sl_url, _) = sl::tests::start_mock_sl_api();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(sl::SlClient::new(sl_url.clone(), sl_url)))
+                .service(routes::get_deviations),
+        )
+        .await;
+
+        // Both sites are tracked and the mock has no deviations for Östra station
+        actions::set_sl_tracked_site(
+            &mut pool.get().unwrap(),
+            &models::SlTrackedSite { site_id: 9204, name: "Tekniska högskolan".to_string() },
+            &[models::SlTrackedLine { site_id: 9204, transport_mode: "METRO".to_string(), line_id: 14, direction_code: Some(1) }],
+        ).unwrap();
+
+        // The bus deviation isn't about a tracked line and the last one is no longer valid
+        let req = test::TestRequest::get().uri("/screen/deviations").insert_header(device.clone()).to_request();
+        let res: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res["failed_site_ids"], serde_json::json!([]));
+        let deviations = res["deviations"].as_array().unwrap();
+        assert_eq!(deviations.iter().map(|deviation| deviation["id"].clone()).collect::<Vec<_>>(), vec![1001, 1002]);
+        assert_eq!(deviations[0]["severity"], "high");
+        assert_eq!(deviations[0]["valid_until"], "2035-11-30T22:59:00Z");
+
+        actions::mute_sl_deviation(&mut pool.get().unwrap(), &models::SlMutedDeviation {
+            deviation_case_id: 1001,
+            muted_by: "admin@example.com".to_string(),
+            muted_at: chrono::Utc::now().naive_utc(),
+        }).unwrap();
+        let req = test::TestRequest::get().uri("/screen/deviations").insert_header(device.clone()).to_request();
+        let res: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res["deviations"].as_array().unwrap().len(), 1);
+        assert_eq!(res["deviations"][0]["id"], 1002);
+    }
+
+    #[actix_web::test]
+    async fn test_events_from_calendar_feed() {
+        let pool = initialize_test_db_pool();
+        let device = paired_device_header(&pool);
+        let calendars = web::Data::new(calendar::Calendars::new());
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(calendars.clone())
+                .service(routes::get_events),
+        )
+        .await;
+
+        // Only the fixture, not the calendar added by the migration
+        let mut conn = pool.get().unwrap();
+        diesel::delete(schema::calendar_feeds::table).execute(&mut conn).unwrap();
+        actions::insert_calendar_feed(&mut conn, models::CalendarFeed {
+            id: "fixture".to_string(),
+            name: "Fixture".to_string(),
+            url: calendar::tests::start_mock_calendar(),
+            created_at: chrono::Utc::now().naive_utc(),
+            slide_days_before: None,
+        }).unwrap();
+        drop(conn);
+        calendar::refresh_from_db(&calendars, pool.clone()).await.unwrap();
+
+        // Everything else in the fixture is in the past, but the pub is every thursday
+        let req = test::TestRequest::get().uri("/screen/events?days=6").insert_header(device.clone()).to_request();
+        let res: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let events = res.as_array().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["title"], "Pubkväll");
+        assert_eq!(events[0]["feed_id"], "fixture");
+        let start: chrono::DateTime<chrono::Local> = serde_json::from_value(events[0]["start"].clone()).unwrap();
+        assert_eq!(chrono::Datelike::weekday(&start), chrono::Weekday::Thu);
+
+        let req = test::TestRequest::get().uri("/screen/events?days=365").insert_header(device.clone()).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_event_slides() {
+        let pool = initialize_test_db_pool();
+        let device = paired_device_header(&pool);
+        let calendars = web::Data::new(calendar::Calendars::new());
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(calendars.clone())
+                .service(routes::get_slides),
+        )
+        .await;
+
+        let mut conn = pool.get().unwrap();
+        diesel::delete(schema::calendar_feeds::table).execute(&mut conn).unwrap();
+        actions::insert_calendar_feed(&mut conn, models::CalendarFeed {
+            id: "fixture".to_string(),
+            name: "Fixture".to_string(),
+            url: calendar::tests::start_mock_calendar(),
+            created_at: chrono::Utc::now().naive_utc(),
+            slide_days_before: Some(7),
+        }).unwrap();
+        drop(conn);
+        calendar::refresh_from_db(&calendars, pool.clone()).await.unwrap();
+
+        // The next pub is always less than a week away, unless it's going on right now
+        let req = test::TestRequest::get().uri("/screen/slides").insert_header(device.clone()).to_request();
+        let res: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let slides = res.as_array().unwrap();
+        assert_eq!(slides.len(), 1);
+        let key = slides[0]["id"].as_str().unwrap().strip_prefix("event-").unwrap().to_string();
+        assert_eq!(slides[0]["caption"], "Pubkväll");
+        assert_eq!(slides[0]["filetype"], "");
+        assert_eq!(slides[0]["event"]["location"], "Konsulatet");
+
+        actions::set_event_slide_options(&mut pool.get().unwrap(), &models::EventSlideOptions {
+            event_key: key,
+            feed_id: "fixture".to_string(),
+            event_uid: "pub@fysiksektionen.se".to_string(),
+            suppressed: true,
+            poster_filetype: None,
+            updated_by: "admin@example.com".to_string(),
+            updated_at: chrono::Utc::now().naive_utc(),
+        }).unwrap();
+        let req = test::TestRequest::get().uri("/screen/slides").insert_header(device.clone()).to_request();
+        let res: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res, serde_json::json!([]));
+    }
+
+    #[actix_web::test]
+    async fn test_screen_endpoints_need_paired_device() {
+        let pool = initialize_test_db_pool();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .service(routes::request_pairing)
+                .service(routes::device_status)
+                .app_data(web::Data::new(calendar::Calendars::new()))
+                .app_data(web::Data::new(broadcast::Broadcaster::new()))
+                .service(routes::get_slides)
+                .service(routes::get_override)
+                .service(routes::get_events)
+                .service(routes::get_screen_slides),
+        )
+        .await;
+
+        // No token at all, or one that was never handed out
+        for uri in ["/screens/some-screen/slides", "/screen/slides", "/screen/override", "/screen/events"] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED, "{uri}");
+        }
+        let req = test::TestRequest::get().uri("/screen/slides").insert_header(("Authorization", "Bearer made-up")).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+
+        // A pending device only gets to see its pairing code
+        let req = test::TestRequest::post().uri("/devices/pair").to_request();
+        let pairing: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let auth = ("Authorization", format!("Bearer {}", pairing["token"].as_str().unwrap()));
+
+        let req = test::TestRequest::get().uri("/device/status").insert_header(auth.clone()).to_request();
+        let status: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(status["state"], "pending");
+        assert_eq!(status["pairing_code"], pairing["pairing_code"]);
+
+        let req = test::TestRequest::get().uri("/screens/some-screen/slides").insert_header(auth.clone()).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+        let req = test::TestRequest::get().uri("/screen/slides").insert_header(auth).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+        // Once it's paired it gets the content
+        let req = test::TestRequest::get().uri("/screen/slides").insert_header(paired_device_header(&pool)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn test_device_health_stale_after() {
+        let pool = initialize_test_db_pool();
+        pair_test_device(&pool, "test-screen");
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .wrap(SessionMiddleware::new(CookieSessionStore::default(), Key::generate()))
+                .route("/test/login/{email}/{role}", web::post().to(test_login))
+                .service(routes::devices_health),
+        )
+        .await;
+
+        let req = test::TestRequest::post().uri("/test/login/operator@example.com/screen_operator").to_request();
+        let cookie = test::call_service(&app, req).await.response().cookies().next().unwrap().into_owned();
+        let health = |query: &str| test::TestRequest::get().uri(&format!("/devices/health{query}")).cookie(cookie.clone()).to_request();
+
+        // The device never sent a heartbeat
+        let devices: Vec<serde_json::Value> = test::call_and_read_body_json(&app, health("")).await;
+        assert_eq!((devices.len(), &devices[0]["stale"]), (1, &serde_json::json!(true)));
+        assert_eq!(test::call_service(&app, health("?stale_after_seconds=0")).await.status(), StatusCode::OK);
+
+        // Too large for a TimeDelta, or negative
+        assert_eq!(test::call_service(&app, health("?stale_after_seconds=9999999999999999")).await.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(test::call_service(&app, health("?stale_after_seconds=-1")).await.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_duplicate_screen_name() {
+        let pool = initialize_test_db_pool();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(broadcast::Broadcaster::new()))
+                .wrap(SessionMiddleware::new(CookieSessionStore::default(), Key::generate()))
+                .route("/test/login/{email}/{role}", web::post().to(test_login))
+                .service(routes::add_screen)
+                .service(routes::update_screen),
+        )
+        .await;
+
+        let req = test::TestRequest::post().uri("/test/login/admin@example.com/admin").to_request();
+        let cookie = test::call_service(&app, req).await.response().cookies().next().unwrap().into_owned();
+        let add = |name: &str| test::TestRequest::post().uri("/screens").cookie(cookie.clone())
+            .set_json(serde_json::json!({ "name": name, "sl_site_ids": [1] })).to_request();
+
+        let lobby: serde_json::Value = test::call_and_read_body_json(&app, add("Lobby")).await;
+        assert_eq!(test::call_service(&app, add("Lobby")).await.status(), StatusCode::CONFLICT);
+
+        let kitchen: serde_json::Value = test::call_and_read_body_json(&app, add("Kitchen")).await;
+        let req = test::TestRequest::put().uri(&format!("/screens/{}", kitchen["id"].as_str().unwrap())).cookie(cookie.clone())
+            .set_json(serde_json::json!({ "name": "Lobby", "sl_site_ids": [2] })).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CONFLICT);
+
+        // Nothing from the failed requests is left behind
+        let mut conn = pool.get().unwrap();
+        assert_eq!(actions::get_all_screens(&mut conn).unwrap().len(), 2);
+        assert_eq!(actions::get_screen_sl_sites(&mut conn, lobby["id"].as_str().unwrap()).unwrap(), vec![1]);
+        assert_eq!(actions::get_screen_sl_sites(&mut conn, kitchen["id"].as_str().unwrap()).unwrap(), vec![1]);
+    }
+
+    #[actix_web::test]
+    async fn test_impressions_export() {
+        let pool = initialize_test_db_pool();
+        let mut conn = pool.get().unwrap();
+        let started_at = NaiveDate::from_ymd_opt(2025, 11, 5).unwrap().and_hms_opt(12, 0, 0).unwrap();
+        let slide = actions::insert_slide(&mut conn, models::Slide {
+            id: Uuid::new_v4().into(),
+            caption: "=HYPERLINK(\"https://example.com\")".to_string(),
+            start_date: started_at,
+            end_date: started_at,
+            active: true,
+            filetype: "png".to_string(),
+            created_by: None,
+            group_id: None,
+            status: models::SlideStatus::Approved,
+        }).unwrap();
+        actions::insert_play_events(&mut conn, &[models::NewPlayEvent {
+            slide_id: slide.id.clone(),
+            screen_id: "screen".to_string(),
+            device_id: "device".to_string(),
+            started_at,
+            duration_ms: 10_000,
+        }]).unwrap();
+        drop(conn);
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .wrap(SessionMiddleware::new(CookieSessionStore::default(), Key::generate()))
+                .route("/test/login/{email}/{role}", web::post().to(test_login))
+                .service(routes::get_impressions),
+        )
+        .await;
+
+        let req = test::TestRequest::post().uri("/test/login/admin@example.com/admin").to_request();
+        let cookie = test::call_service(&app, req).await.response().cookies().next().unwrap().into_owned();
+        let impressions = |query: &str| test::TestRequest::get().uri(&format!("/stats/impressions{query}")).cookie(cookie.clone()).to_request();
+
+        // Captions are written by slide editors, so they can't become spreadsheet formulas
+        let csv = test::call_and_read_body(&app, impressions("?from=2025-11-05&to=2025-11-05&format=csv")).await;
+        assert_eq!(
+            std::str::from_utf8(&csv).unwrap(),
+            format!("day,slide_id,caption,screen_id,impressions,total_seconds\n2025-11-05,{},\"'=HYPERLINK(\"\"https://example.com\"\")\",screen,1,10\n", slide.id),
+        );
+
+        // The days around `to` don't exist
+        for query in ["?to=%2B262142-12-31", "?to=-262143-01-01"] {
+            assert_eq!(test::call_service(&app, impressions(query)).await.status(), StatusCode::BAD_REQUEST, "{query}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_quiet_hours_exception_dates() {
+        let pool = initialize_test_db_pool();
+        let device = paired_device_header(&pool);
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(broadcast::Broadcaster::new()))
+                .wrap(SessionMiddleware::new(CookieSessionStore::default(), Key::generate()))
+                .route("/test/login/{email}/{role}", web::post().to(test_login))
+                .service(routes::set_quiet_hours)
+                .service(routes::get_settings),
+        )
+        .await;
+
+        let req = test::TestRequest::post().uri("/test/login/admin@example.com/admin").to_request();
+        let cookie = test::call_service(&app, req).await.response().cookies().next().unwrap().into_owned();
+        let set_exception = |date: &str| test::TestRequest::put().uri("/screen/quiet-hours").cookie(cookie.clone())
+            .set_json(serde_json::json!({ "windows": [], "exceptions": [{ "date": date, "quiet": true, "name": "Test" }] }))
+            .to_request();
+
+        assert_eq!(test::call_service(&app, set_exception("+262142-12-31")).await.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(test::call_service(&app, set_exception("1999-12-31")).await.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(test::call_service(&app, set_exception("2025-12-24")).await.status(), StatusCode::OK);
+
+        let req = test::TestRequest::get().uri("/screen/settings").insert_header(device).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn test_upload_rejects_svg() {
+        let pool = initialize_test_db_pool();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(broadcast::Broadcaster::new()))
+                .app_data(web::Data::new(calendar::Calendars::new()))
+                .wrap(SessionMiddleware::new(CookieSessionStore::default(), Key::generate()))
+                .route("/test/login/{email}/{role}", web::post().to(test_login))
+                .service(routes::save_slide)
+                .service(routes::upload_event_poster),
+        )
+        .await;
+
+        let req = test::TestRequest::post().uri("/test/login/admin@example.com/admin").to_request();
+        let cookie = test::call_service(&app, req).await.response().cookies().next().unwrap().into_owned();
+
+        // SVGs can contain scripts, so only the formats the screens show are accepted
+        let svg = "--boundary\r\n\
+            Content-Disposition: form-data; name=\"imageFile\"; filename=\"slide.svg\"\r\n\
+            Content-Type: image/svg+xml\r\n\r\n\
+            <svg xmlns=\"http://www.w3.org/2000/svg\"><script>alert(1)</script></svg>\r\n";
+        let fields = "--boundary\r\nContent-Disposition: form-data; name=\"caption\"\r\n\r\nSVG\r\n\
+            --boundary\r\nContent-Disposition: form-data; name=\"start\"\r\n\r\n2025-11-01\r\n\
+            --boundary\r\nContent-Disposition: form-data; name=\"end\"\r\n\r\n2025-11-30\r\n\
+            --boundary\r\nContent-Disposition: form-data; name=\"visible\"\r\n\r\ntrue\r\n";
+        for (uri, body) in [("/screen/slides/save", format!("{fields}{svg}--boundary--\r\n")), ("/calendar/event-slides/key/poster", format!("{svg}--boundary--\r\n"))] {
+            let req = if uri.ends_with("poster") { test::TestRequest::put() } else { test::TestRequest::post() }
+                .uri(uri)
+                .cookie(cookie.clone())
+                .insert_header(("Content-Type", "multipart/form-data; boundary=boundary"))
+                .set_payload(body)
+                .to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST, "{uri}");
+        }
+        let mut conn = pool.get().unwrap();
+        assert!(actions::get_all_slides(&mut conn).unwrap().is_empty());
+    }
+
+    /// Stands in for `POST /auth/verify`, which needs a Google id token
+    /// Logs in as `email`, adding the user with `role` if needed. Sessions are checked against `users` on every request.
+    async fn test_login(path: web::Path<(String, String)>, session: actix_session::Session, pool: web::Data<DbPool>) -> &'static str {
+        let (email, role) = path.into_inner();
+        let role: models::Role = role.parse().unwrap();
+        let mut conn = pool.get().unwrap();
+        let user = actions::insert_user(&mut conn, models::User { id: Uuid::new_v4().into(), email: email.clone(), role }).unwrap();
+        if user.role != role {
+            actions::set_user_role(&mut conn, &user.id, role).unwrap().unwrap();
+        }
+        session.insert("auth", auth::AuthenticatedUser { email, role }).unwrap();
+        "logged in"
+    }
+
+    #[actix_web::test]
+    async fn test_routes_check_role_permissions() {
+        let pool = initialize_test_db_pool();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .wrap(SessionMiddleware::new(CookieSessionStore::default(), Key::generate()))
+                .route("/test/login/{email}/{role}", web::post().to(test_login))
+                .service(routes::login_status)
+                .service(routes::list_overrides)
+                .service(routes::list_users),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/screen/override/history").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+
+        for (role, expected) in [
+            ("viewer", StatusCode::FORBIDDEN),
+            ("slide_editor", StatusCode::FORBIDDEN),
+            ("screen_operator", StatusCode::OK),
+            ("admin", StatusCode::OK),
+        ] {
+            let req = test::TestRequest::post().uri(&format!("/test/login/someone@example.com/{role}")).to_request();
+            let res = test::call_service(&app, req).await;
+            let cookie = res.response().cookies().next().unwrap().into_owned();
+
+            let req = test::TestRequest::get().uri("/screen/override/history").cookie(cookie.clone()).to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), expected, "{role}");
+
+            let req = test::TestRequest::get().uri("/auth/status").cookie(cookie).to_request();
+            let status: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+            assert_eq!(status["role"], role);
+            assert_eq!(status["permissions"].as_array().unwrap().contains(&"users_manage".into()), role == "admin");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_group_members_manage_group_slides() {
+        let pool = initialize_test_db_pool();
+        let mut conn = pool.get().unwrap();
+        let now = chrono::Utc::now().naive_utc();
+
+        let group = |name: &str| models::Group { id: Uuid::new_v4().into(), name: name.to_string(), created_at: now };
+        let (fkm, styrelsen) = (group("F.dev"), group("Styrelsen"));
+        for group in [&fkm, &styrelsen] {
+            actions::insert_group(&mut conn, group.clone()).unwrap().unwrap();
+        }
+        let member = actions::insert_user(&mut conn, models::User {
+            id: Uuid::new_v4().into(),
+            email: "member@example.com".to_string(),
+            role: models::Role::Viewer,
+        }).unwrap();
+        actions::set_group_member(&mut conn, &models::GroupMember {
+            group_id: fkm.id.clone(),
+            user_id: member.id,
+            role: models::Role::SlideEditor,
+        }).unwrap();
+
+        let mut slide_of = |group: &models::Group| {
+            let slide = actions::insert_slide(&mut conn, models::Slide {
+                id: Uuid::new_v4().into(),
+                caption: group.name.clone(),
+                start_date: now,
+                end_date: now,
+                active: true,
+                filetype: "png".to_string(),
+                created_by: None,
+                group_id: Some(group.id.clone()),
+                status: models::SlideStatus::Approved,
+            }).unwrap();
+            slide.id
+        };
+        let (own_slide, other_slide) = (slide_of(&fkm), slide_of(&styrelsen));
+        let ungrouped_slide = actions::insert_slide(&mut conn, models::Slide {
+            id: Uuid::new_v4().into(),
+            caption: "No group".to_string(),
+            start_date: now,
+            end_date: now,
+            active: true,
+            filetype: "png".to_string(),
+            created_by: None,
+            group_id: None,
+            status: models::SlideStatus::Approved,
+        }).unwrap().id;
+        drop(conn);
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(broadcast::Broadcaster::new()))
+                .wrap(SessionMiddleware::new(CookieSessionStore::default(), Key::generate()))
+                .route("/test/login/{email}/{role}", web::post().to(test_login))
+                .service(routes::set_slide_screens)
+                .service(routes::delete_slide),
+        )
+        .await;
+
+        let login = |user: &str| test::TestRequest::post().uri(&format!("/test/login/{user}")).to_request();
+        let member = test::call_service(&app, login("member@example.com/viewer")).await.response().cookies().next().unwrap().into_owned();
+        let editor = test::call_service(&app, login("editor@example.com/slide_editor")).await.response().cookies().next().unwrap().into_owned();
+        let admin = test::call_service(&app, login("admin@example.com/admin")).await.response().cookies().next().unwrap().into_owned();
+
+        let set_screens = |cookie: &actix_web::cookie::Cookie<'static>, slide: &str| test::TestRequest::put()
+            .uri(&format!("/screen/slides/{slide}/screens"))
+            .cookie(cookie.clone())
+            .set_json(serde_json::json!({ "screen_ids": [] }))
+            .to_request();
+        assert_eq!(test::call_service(&app, set_screens(&member, &own_slide)).await.status(), StatusCode::OK);
+        assert_eq!(test::call_service(&app, set_screens(&member, &other_slide)).await.status(), StatusCode::FORBIDDEN);
+        assert_eq!(test::call_service(&app, set_screens(&member, &ungrouped_slide)).await.status(), StatusCode::FORBIDDEN);
+        assert_eq!(test::call_service(&app, set_screens(&member, &Uuid::new_v4().to_string())).await.status(), StatusCode::NOT_FOUND);
+
+        // Being a slide editor everywhere doesn't make you one in every committee
+        assert_eq!(test::call_service(&app, set_screens(&editor, &ungrouped_slide)).await.status(), StatusCode::OK);
+        assert_eq!(test::call_service(&app, set_screens(&editor, &other_slide)).await.status(), StatusCode::FORBIDDEN);
+        let req = test::TestRequest::delete().uri(&format!("/screen/slides/{other_slide}")).cookie(editor.clone()).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+        assert!(actions::get_slide(&mut pool.get().unwrap(), &other_slide).unwrap().is_some());
+
+        // Admins can manage every slide
+        assert_eq!(test::call_service(&app, set_screens(&admin, &other_slide)).await.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn test_slide_review() {
+        let pool = initialize_test_db_pool();
+        let device = paired_device_header(&pool);
+        let now = chrono::Utc::now().naive_utc();
+        let slide = actions::upload_slide(&mut pool.get().unwrap(), models::Slide {
+            id: Uuid::new_v4().into(),
+            caption: "Pub tonight".to_string(),
+            start_date: now,
+            end_date: now,
+            active: true,
+            filetype: "png".to_string(),
+            created_by: Some("editor@example.com".to_string()),
+            group_id: None,
+            status: models::SlideStatus::Pending,
+        }, "editor@example.com", now).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(broadcast::Broadcaster::new()))
+                .app_data(web::Data::new(calendar::Calendars::new()))
+                .wrap(SessionMiddleware::new(CookieSessionStore::default(), Key::generate()))
+                .route("/test/login/{email}/{role}", web::post().to(test_login))
+                .service(routes::get_slides)
+                .service(routes::list_pending_slides)
+                .service(routes::review_slide)
+                .service(routes::get_slide_history),
+        )
+        .await;
+
+        let login = |role: &str| test::TestRequest::post().uri(&format!("/test/login/{role}@example.com/{role}")).to_request();
+        let editor = test::call_service(&app, login("slide_editor")).await.response().cookies().next().unwrap().into_owned();
+        let publisher = test::call_service(&app, login("slide_publisher")).await.response().cookies().next().unwrap().into_owned();
+
+        let shown = || test::TestRequest::get().uri("/screen/slides").insert_header(device.clone()).to_request();
+        let slides: Vec<serde_json::Value> = test::call_and_read_body_json(&app, shown()).await;
+        assert!(slides.is_empty());
+
+        let pending = |cookie: &actix_web::cookie::Cookie<'static>| test::TestRequest::get()
+            .uri("/screen/slides/pending").cookie(cookie.clone()).to_request();
+        let editor_pending: Vec<serde_json::Value> = test::call_and_read_body_json(&app, pending(&editor)).await;
+        assert!(editor_pending.is_empty());
+        let publisher_pending: Vec<serde_json::Value> = test::call_and_read_body_json(&app, pending(&publisher)).await;
+        assert_eq!(publisher_pending[0]["id"], slide.id);
+
+        let review = |cookie: &actix_web::cookie::Cookie<'static>, body: serde_json::Value| test::TestRequest::post()
+            .uri(&format!("/screen/slides/{}/review", slide.id))
+            .cookie(cookie.clone())
+            .set_json(body)
+            .to_request();
+        let approve = serde_json::json!({ "approve": true });
+        assert_eq!(test::call_service(&app, review(&editor, approve.clone())).await.status(), StatusCode::FORBIDDEN);
+        assert_eq!(test::call_service(&app, review(&publisher, serde_json::json!({ "approve": false }))).await.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(test::call_service(&app, review(&publisher, approve.clone())).await.status(), StatusCode::OK);
+        assert_eq!(test::call_service(&app, review(&publisher, approve)).await.status(), StatusCode::CONFLICT);
+
+        let slides: Vec<serde_json::Value> = test::call_and_read_body_json(&app, shown()).await;
+        assert_eq!(slides[0]["id"], slide.id);
+
+        let req = test::TestRequest::get().uri(&format!("/screen/slides/{}/history", slide.id)).cookie(editor).to_request();
+        let history: Vec<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(history.len(), 2);
+        assert_eq!((&history[0]["from_status"], &history[0]["to_status"]), (&serde_json::Value::Null, &"pending".into()));
+        assert_eq!((&history[1]["to_status"], &history[1]["changed_by"]), (&"approved".into(), &"slide_publisher@example.com".into()));
+    }
+
+    #[actix_web::test]
+    async fn test_audit_log() {
+        let pool = initialize_test_db_pool();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .wrap(SessionMiddleware::new(CookieSessionStore::default(), Key::generate()))
+                .route("/test/login/{email}/{role}", web::post().to(test_login))
+                .service(routes::add_group)
+                .service(routes::remove_group)
+                .service(routes::list_audit_events),
+        )
+        .await;
+
+        let login = |role: &str| test::TestRequest::post().uri(&format!("/test/login/{role}@example.com/{role}")).to_request();
+        let admin = test::call_service(&app, login("admin")).await.response().cookies().next().unwrap().into_owned();
+        let editor = test::call_service(&app, login("slide_editor")).await.response().cookies().next().unwrap().into_owned();
+
+        let req = test::TestRequest::post().uri("/groups").cookie(admin.clone()).set_json(serde_json::json!({ "name": "F.dev" })).to_request();
+        let group: models::Group = test::call_and_read_body_json(&app, req).await;
+        let req = test::TestRequest::delete().uri(&format!("/groups/{}", group.id)).cookie(admin.clone()).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+        let audit = |cookie: &actix_web::cookie::Cookie<'static>, query: &str| test::TestRequest::get()
+            .uri(&format!("/audit?{query}")).cookie(cookie.clone()).to_request();
+        assert_eq!(test::call_service(&app, audit(&editor, "")).await.status(), StatusCode::FORBIDDEN);
+        assert_eq!(test::call_service(&app, audit(&admin, "limit=0")).await.status(), StatusCode::BAD_REQUEST);
+
+        // Newest first, one per page
+        let page: serde_json::Value = test::call_and_read_body_json(&app, audit(&admin, "limit=1")).await;
+        let removed = &page["events"][0];
+        assert_eq!((&removed["action"], &removed["actor"]), (&"group.remove".into(), &"admin@example.com".into()));
+        assert_eq!((&removed["before"]["name"], &removed["after"]), (&"F.dev".into(), &serde_json::Value::Null));
+
+        let next = page["next_before_id"].as_i64().unwrap();
+        let page: serde_json::Value = test::call_and_read_body_json(&app, audit(&admin, &format!("limit=1&before_id={next}"))).await;
+        let created = &page["events"][0];
+        assert_eq!((&created["action"], &created["target_id"]), (&"group.create".into(), &group.id.clone().into()));
+        assert_eq!(page["next_before_id"], serde_json::Value::Null);
+
+        let page: serde_json::Value = test::call_and_read_body_json(&app, audit(&admin, "action=group.create")).await;
+        assert_eq!(page["events"].as_array().unwrap().len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_sessions_follow_user_changes() {
+        let pool = initialize_test_db_pool();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .wrap(SessionMiddleware::new(CookieSessionStore::default(), Key::generate()))
+                .route("/test/login/{email}/{role}", web::post().to(test_login))
+                .service(routes::login_status)
+                .service(routes::list_users)
+                .service(routes::update_user)
+                .service(routes::remove_user),
+        )
+        .await;
+
+        let login = |email: &str| test::TestRequest::post().uri(&format!("/test/login/{email}/admin")).to_request();
+        let first = test::call_service(&app, login("first@example.com")).await.response().cookies().next().unwrap().into_owned();
+        let second = test::call_service(&app, login("second@example.com")).await.response().cookies().next().unwrap().into_owned();
+        let second_id = actions::get_all_users(&mut pool.get().unwrap()).unwrap()
+            .into_iter().find(|user| user.email == "second@example.com").unwrap().id;
+
+        let list_users = || test::TestRequest::get().uri("/auth/list_users").cookie(second.clone()).to_request();
+        assert_eq!(test::call_service(&app, list_users()).await.status(), StatusCode::OK);
+
+        // The second admin's cookie still says admin, but the database doesn't
+        let req = test::TestRequest::patch().uri(&format!("/auth/users/{second_id}")).cookie(first.clone())
+            .set_json(serde_json::json!({ "role": "viewer" })).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        assert_eq!(test::call_service(&app, list_users()).await.status(), StatusCode::FORBIDDEN);
+        let req = test::TestRequest::get().uri("/auth/status").cookie(second.clone()).to_request();
+        let status: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(status["role"], "viewer");
+
+        let req = test::TestRequest::post().uri("/auth/remove_user").cookie(first)
+            .set_json(serde_json::json!({ "id": second_id })).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        let req = test::TestRequest::get().uri("/auth/status").cookie(second).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn test_sessions_can_be_listed_and_revoked() {
+        let pool = initialize_test_db_pool();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .wrap(SessionMiddleware::new(sessions::SqliteSessionStore::new(pool.clone()), Key::generate()))
+                .route("/test/login/{email}/{role}", web::post().to(test_login))
+                .service(routes::login_status)
+                .service(routes::logout)
+                .service(routes::list_own_sessions)
+                .service(routes::list_all_sessions)
+                .service(routes::revoke_session),
+        )
+        .await;
+
+        let login = |email: &str, role: &str| test::TestRequest::post().uri(&format!("/test/login/{email}/{role}")).to_request();
+        let laptop = test::call_service(&app, login("editor@example.com", "slide_editor")).await.response().cookies().next().unwrap().into_owned();
+        let phone = test::call_service(&app, login("editor@example.com", "slide_editor")).await.response().cookies().next().unwrap().into_owned();
+        let admin = test::call_service(&app, login("admin@example.com", "admin")).await.response().cookies().next().unwrap().into_owned();
+
+        let req = test::TestRequest::get().uri("/auth/sessions").cookie(laptop.clone()).to_request();
+        let own: Vec<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(own.len(), 2);
+        assert!(own.iter().all(|session| session["email"] == "editor@example.com" && session.get("key_hash").is_none()));
+
+        let req = test::TestRequest::get().uri("/auth/sessions/all").cookie(laptop.clone()).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+        let req = test::TestRequest::get().uri("/auth/sessions/all").cookie(admin.clone()).to_request();
+        let all: Vec<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(all.len(), 3);
+        let admin_session = all.iter().find(|session| session["email"] == "admin@example.com").unwrap()["id"].as_str().unwrap().to_string();
+
+        // An editor can't revoke someone else's session, but an admin can revoke anyone's
+        let revoke = |id: &str, cookie: &actix_web::cookie::Cookie<'static>| test::TestRequest::delete()
+            .uri(&format!("/auth/sessions/{id}")).cookie(cookie.clone()).to_request();
+        assert_eq!(test::call_service(&app, revoke(&admin_session, &laptop)).await.status(), StatusCode::NOT_FOUND);
+        for session in &own {
+            let id = session["id"].as_str().unwrap();
+            assert_eq!(test::call_service(&app, revoke(id, &admin)).await.status(), StatusCode::OK);
+        }
+        let status = |cookie: &actix_web::cookie::Cookie<'static>| test::TestRequest::get().uri("/auth/status").cookie(cookie.clone()).to_request();
+        assert_eq!(test::call_service(&app, status(&laptop)).await.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(test::call_service(&app, status(&phone)).await.status(), StatusCode::UNAUTHORIZED);
+
+        // Logging out removes the session too
+        let req = test::TestRequest::post().uri("/auth/logout").cookie(admin.clone()).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        assert_eq!(test::call_service(&app, status(&admin)).await.status(), StatusCode::UNAUTHORIZED);
+        assert!(actions::get_active_sessions(&mut pool.get().unwrap(), None, chrono::Utc::now().naive_utc()).unwrap().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_invites() {
+        let pool = initialize_test_db_pool();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .wrap(SessionMiddleware::new(CookieSessionStore::default(), Key::generate()))
+                .route("/test/login/{email}/{role}", web::post().to(test_login))
+                .service(routes::create_invite)
+                .service(routes::list_invites)
+                .service(routes::revoke_invite),
+        )
+        .await;
+
+        let login = |email: &str, role: &str| test::TestRequest::post().uri(&format!("/test/login/{email}/{role}")).to_request();
+        let admin = test::call_service(&app, login("admin@example.com", "admin")).await.response().cookies().next().unwrap().into_owned();
+        let editor = test::call_service(&app, login("editor@example.com", "slide_editor")).await.response().cookies().next().unwrap().into_owned();
+
+        let create = |body: serde_json::Value, cookie: &actix_web::cookie::Cookie<'static>| test::TestRequest::post()
+            .uri("/auth/invites").cookie(cookie.clone()).set_json(body).to_request();
+        assert_eq!(test::call_service(&app, create(serde_json::json!({"role": "viewer"}), &editor)).await.status(), StatusCode::FORBIDDEN);
+        assert_eq!(test::call_service(&app, create(serde_json::json!({"role": "viewer", "valid_days": 0}), &admin)).await.status(), StatusCode::BAD_REQUEST);
+        let used: serde_json::Value = test::call_and_read_body_json(&app, create(serde_json::json!({"role": "slide_editor"}), &admin)).await;
+        let revoked: serde_json::Value = test::call_and_read_body_json(&app, create(serde_json::json!({"role": "viewer", "valid_days": 1}), &admin)).await;
+
+        let list = || test::TestRequest::get().uri("/auth/invites").cookie(admin.clone()).to_request();
+        let pending: Vec<serde_json::Value> = test::call_and_read_body_json(&app, list()).await;
+        assert_eq!(pending.len(), 2);
+        assert!(pending.iter().all(|invite| invite.get("token").is_none() && invite.get("token_hash").is_none()));
+
+        // What verify_token does when someone logs in with the invite. It can only be used once.
+        let now = chrono::Utc::now().naive_utc();
+        let accept = |token: &serde_json::Value, email: &str| {
+            let hash = devices::hash_token(token.as_str().unwrap());
+            actions::accept_invite(&mut pool.get().unwrap(), &hash, email, now).unwrap()
+        };
+        let (user, invite) = accept(&used["token"], "new@example.com").unwrap();
+        assert_eq!((user.role, invite.used_by.as_deref()), (models::Role::SlideEditor, Some("new@example.com")));
+        assert!(accept(&used["token"], "other@example.com").is_none());
+        // Someone who is already a user keeps their role, and the invite stays unused
+        assert_eq!(accept(&revoked["token"], "editor@example.com").unwrap().0.role, models::Role::SlideEditor);
+
+        let pending: Vec<serde_json::Value> = test::call_and_read_body_json(&app, list()).await;
+        assert_eq!(pending.len(), 1);
+
+        let revoke = |invite: &serde_json::Value| test::TestRequest::delete()
+            .uri(&format!("/auth/invites/{}", invite["id"].as_str().unwrap())).cookie(admin.clone()).to_request();
+        assert_eq!(test::call_service(&app, revoke(&used)).await.status(), StatusCode::NOT_FOUND);
+        assert_eq!(test::call_service(&app, revoke(&revoked)).await.status(), StatusCode::OK);
+        assert!(accept(&revoked["token"], "other@example.com").is_none());
+        assert!(test::call_and_read_body_json::<_, _, Vec<serde_json::Value>>(&app, list()).await.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_oidc_login() {
+        let pool = initialize_test_db_pool();
+        let issuer = login::tests::FakeIssuer::start();
+        actions::insert_user(&mut pool.get().unwrap(), models::User {
+            id: Uuid::new_v4().into(),
+            email: "editor@kth.se".to_string(),
+            role: models::Role::SlideEditor,
+        }).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(login::Providers::new(None, Some(issuer.config()))))
+                .wrap(SessionMiddleware::new(CookieSessionStore::default(), Key::generate()))
+                .service(routes::verify_token)
+                .service(routes::login_status),
+        )
+        .await;
+
+        let verify = |body: serde_json::Value| test::TestRequest::post().uri("/auth/verify").set_json(body).to_request();
+        let token = issuer.id_token("editor@kth.se", serde_json::json!({}));
+
+        // Google is the default, and it isn't configured here
+        assert_eq!(test::call_service(&app, verify(serde_json::json!({ "id_token": token }))).await.status(), StatusCode::BAD_REQUEST);
+
+        let res = test::call_service(&app, verify(serde_json::json!({ "id_token": token, "provider": "oidc" }))).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let cookie = res.response().cookies().next().unwrap().into_owned();
+        let req = test::TestRequest::get().uri("/auth/status").cookie(cookie).to_request();
+        let status: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!((status["email"].as_str(), status["role"].as_str()), (Some("editor@kth.se"), Some("slide_editor")));
+
+        // Not a user, and a token for another client
+        let stranger = issuer.id_token("stranger@kth.se", serde_json::json!({}));
+        assert_eq!(test::call_service(&app, verify(serde_json::json!({ "id_token": stranger, "provider": "oidc" }))).await.status(), StatusCode::UNAUTHORIZED);
+        let other_client = issuer.id_token("editor@kth.se", serde_json::json!({ "aud": "other" }));
+        assert_eq!(test::call_service(&app, verify(serde_json::json!({ "id_token": other_client, "provider": "oidc" }))).await.status(), StatusCode::UNAUTHORIZED);
+
+        // Anyone at the issuer could put an admin's address in their unverified email
+        actions::insert_user(&mut pool.get().unwrap(), models::User {
+            id: Uuid::new_v4().into(),
+            email: "admin@kth.se".to_string(),
+            role: models::Role::Admin,
+        }).unwrap();
+        let unverified = issuer.id_token("admin@kth.se", serde_json::json!({ "sub": "456", "email_verified": false }));
+        let res = test::call_service(&app, verify(serde_json::json!({ "id_token": unverified, "provider": "oidc" }))).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert!(res.response().cookies().next().is_none());
+    }
+
+    /// The `Authorization` header of a kiosk that is paired to a screen, for the `/screen/*` routes
+    fn paired_device_header(pool: &DbPool) -> (&'static str, String) {
+        ("Authorization", format!("Bearer {}", pair_test_device(pool, "test-screen")))
+    }
+
+    /// Create a device that is already paired to a screen. Returns its token.
+    fn pair_test_device(pool: &DbPool, screen_id: &str) -> String {
+        let token = devices::generate_token();
+        let now = chrono::Utc::now().naive_utc();
+        let mut conn = pool.get().unwrap();
+
+        actions::insert_device(&mut conn, models::Device {
+            id: Uuid::new_v4().into(),
+            token_hash: devices::hash_token(&token),
+            pairing_code: Some("TEST23".to_string()),
+            pairing_expires_at: Some(now + chrono::TimeDelta::minutes(1)),
+            name: None,
+            screen_id: None,
+            approved_by: None,
+            created_at: now,
+            approved_at: None,
+            revoked_at: None,
+        }, now, 10).unwrap().unwrap();
+        actions::approve_device(&mut conn, "TEST23", screen_id, "Test kiosk", "admin@example.com", now).unwrap().unwrap();
+
+        token
+    }
 }
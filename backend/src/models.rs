@@ -1,7 +1,102 @@
 use serde::{Deserialize, Serialize};
-use chrono::NaiveDateTime;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 
-use crate::schema::{ slides, users, settings };
+use crate::schema::{ slides, users, overrides, screens, screen_sl_sites, slide_screens, devices, device_status, screen_commands, play_events, settings_schedules, quiet_hours_exceptions, sl_tracked_sites, sl_tracked_lines, sl_muted_deviations, calendar_feeds, event_slide_options, groups, group_members, slide_status_changes, audit_events, sessions, provisioning_domains, provisioning_emails, invites };
+
+/// Defines an enum that is stored as text in the database and serialized as snake_case in the API.
+/// Reading a value that isn't one of the variants gives an error instead of passing it on to the screens.
+macro_rules! text_enum {
+    ($(#[$meta:meta])* $name:ident { $($variant:ident => $value:literal),+ $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow)]
+        #[diesel(sql_type = diesel::sql_types::Text)]
+        #[serde(rename_all = "snake_case")]
+        pub enum $name {
+            $($variant),+
+        }
+
+        impl $name {
+            pub const VALUES: &'static [&'static str] = &[$($value),+];
+
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $($name::$variant => $value),+
+                }
+            }
+        }
+
+        impl std::str::FromStr for $name {
+            type Err = String;
+
+            fn from_str(value: &str) -> Result<Self, Self::Err> {
+                match value {
+                    $($value => Ok($name::$variant),)+
+                    other => Err(format!("Invalid {} '{}'", stringify!($name), other)),
+                }
+            }
+        }
+
+        impl diesel::deserialize::FromSql<diesel::sql_types::Text, diesel::sqlite::Sqlite> for $name {
+            fn from_sql(bytes: diesel::sqlite::SqliteValue<'_, '_, '_>) -> diesel::deserialize::Result<Self> {
+                let value = <String as diesel::deserialize::FromSql<diesel::sql_types::Text, diesel::sqlite::Sqlite>>::from_sql(bytes)?;
+                Ok(value.parse()?)
+            }
+        }
+
+        impl diesel::serialize::ToSql<diesel::sql_types::Text, diesel::sqlite::Sqlite> for $name {
+            fn to_sql<'b>(&'b self, out: &mut diesel::serialize::Output<'b, '_, diesel::sqlite::Sqlite>) -> diesel::serialize::Result {
+                out.set_value(self.as_str());
+                Ok(diesel::serialize::IsNull::No)
+            }
+        }
+    };
+}
+
+text_enum! {
+    /// Mirrors `LayoutType` in the screen frontend
+    LayoutType {
+        FullscreenSlideshow => "fullscreen_slideshow",
+        Mixed => "mixed",
+    }
+}
+
+text_enum! {
+    /// What a user is allowed to do, see `Role::permissions`
+    Role {
+        Viewer => "viewer",
+        SlideEditor => "slide_editor",
+        SlidePublisher => "slide_publisher",
+        ScreenOperator => "screen_operator",
+        Admin => "admin",
+    }
+}
+
+text_enum! {
+    /// Where a slide is in the approval workflow. Only approved slides are shown on the screens.
+    SlideStatus {
+        Draft => "draft",
+        Pending => "pending",
+        Approved => "approved",
+        Rejected => "rejected",
+    }
+}
+
+impl SlideStatus {
+    /// Drafts and rejected slides can be submitted for review, and pending slides approved or rejected
+    pub fn can_change_to(&self, to: SlideStatus) -> bool {
+        use SlideStatus::*;
+
+        matches!((self, to), (Draft | Rejected, Pending) | (Pending, Approved | Rejected))
+    }
+}
+
+text_enum! {
+    /// Mirrors `ColorMode` in the screen frontend
+    ColorMode {
+        LightMode => "light_mode",
+        DarkMode => "dark_mode",
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, Queryable, QueryableByName, Insertable)]
 #[diesel(table_name = slides)]
@@ -14,6 +109,27 @@ pub struct Slide {
     pub end_date: NaiveDateTime,
     pub active: bool,
     pub filetype: String,
+    /// Email of the uploader
+    pub created_by: Option<String>,
+    /// The group that owns the slide. Its members may manage it, see `auth::has_slide_permission`
+    pub group_id: Option<String>,
+    pub status: SlideStatus,
+}
+
+/// A change of a slide's status, kept as a history of the approval workflow
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable, Selectable)]
+#[diesel(table_name = slide_status_changes)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct SlideStatusChange {
+    pub id: String,
+    pub slide_id: String,
+    /// None when the slide was uploaded
+    pub from_status: Option<SlideStatus>,
+    pub to_status: SlideStatus,
+    /// Email of the user who changed it
+    pub changed_by: String,
+    pub comment: Option<String>,
+    pub changed_at: NaiveDateTime,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Queryable, QueryableByName, Insertable, Selectable)]
@@ -22,16 +138,238 @@ pub struct Slide {
 pub struct User {
     pub id: String,
     pub email: String,
-    pub admin: bool,
+    pub role: Role,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Queryable, QueryableByName, Insertable, Selectable)]
-#[diesel(table_name = settings)]
+/// A committee or other group that owns slides
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable, Selectable)]
+#[diesel(table_name = groups)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Group {
+    pub id: String,
+    pub name: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable, Selectable)]
+#[diesel(table_name = group_members)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct GroupMember {
+    pub group_id: String,
+    pub user_id: String,
+    pub role: Role,
+}
+
+/// The default layout and colour mode for every screen, read from `settings_registry::SettingValues`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
 pub struct Settings {
-    pub id: i32,
-    pub layout_type: String,
-    pub color_mode: String,
+    pub layout_type: LayoutType,
+    pub color_mode: ColorMode,
+}
+
+/// Settings that apply during a time window, see `schedule::ScheduleRule` for how the rule is stored
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable, Selectable)]
+#[diesel(table_name = settings_schedules)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct SettingsSchedule {
+    pub id: String,
+    pub name: String,
+    pub layout_type: Option<LayoutType>,
+    pub color_mode: Option<ColorMode>,
+    pub weekdays: Option<i32>,
+    pub start_time: Option<NaiveTime>,
+    pub end_time: Option<NaiveTime>,
+    pub start_at: Option<NaiveDateTime>,
+    pub end_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+/// A day that doesn't follow the weekly quiet hours
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable, Selectable, PartialEq)]
+#[diesel(table_name = quiet_hours_exceptions)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct QuietHoursException {
+    pub date: NaiveDate,
+    /// Quiet the whole day, or never quiet that day
+    pub quiet: bool,
+    pub name: String,
+}
+
+/// A physical screen. Its layout and colour mode fall back to `Settings` when they are None.
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, QueryableByName, Insertable, Selectable, AsChangeset)]
+#[diesel(table_name = screens)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+// Setting a column back to None should store NULL, not skip the column
+#[diesel(treat_none_as_null = true)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct Screen {
+    pub id: String,
+    pub name: String,
+    pub layout_type: Option<LayoutType>,
+    pub color_mode: Option<ColorMode>,
+}
+
+#[derive(Debug, Clone, Queryable, Insertable, Selectable)]
+#[diesel(table_name = screen_sl_sites)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct ScreenSlSite {
+    pub screen_id: String,
+    pub site_id: i32,
+}
+
+/// An SL site shown on screens without their own sites
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable, Selectable)]
+#[diesel(table_name = sl_tracked_sites)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct SlTrackedSite {
+    pub site_id: i32,
+    pub name: String,
+}
+
+/// A line shown from a tracked site, see `sl::Departure::is_tracked`
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable)]
+#[diesel(table_name = sl_tracked_lines)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct SlTrackedLine {
+    #[serde(skip)]
+    pub site_id: i32,
+    pub transport_mode: String,
+    pub line_id: i32,
+    pub direction_code: Option<i32>,
+}
+
+/// An SL deviation that isn't shown on the screens
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable, Selectable)]
+#[diesel(table_name = sl_muted_deviations)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct SlMutedDeviation {
+    pub deviation_case_id: i64,
+    pub muted_by: String,
+    pub muted_at: NaiveDateTime,
+}
+
+/// An ICS feed whose events are shown on the screens, see `calendar::Calendars`
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable, Selectable)]
+#[diesel(table_name = calendar_feeds)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct CalendarFeed {
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    pub created_at: NaiveDateTime,
+    /// Slides are generated for the feed's events if set, see `calendar::Calendars::event_slides`
+    pub slide_days_before: Option<i32>,
+}
+
+/// How the slide generated for a calendar event differs from the default
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable, Selectable)]
+#[diesel(table_name = event_slide_options)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+// Removing the poster should store NULL, not skip the column
+#[diesel(treat_none_as_null = true)]
+pub struct EventSlideOptions {
+    pub event_key: String,
+    pub feed_id: String,
+    pub event_uid: String,
+    pub suppressed: bool,
+    pub poster_filetype: Option<String>,
+    pub updated_by: String,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Queryable, Insertable, Selectable)]
+#[diesel(table_name = slide_screens)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct SlideScreen {
+    pub slide_id: String,
+    pub screen_id: String,
+}
+
+/// A kiosk showing a screen. It is pending (has a `pairing_code`) until an admin approves it
+/// and assigns it a `screen_id`. Times are stored in UTC.
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, QueryableByName, Insertable, Selectable)]
+#[diesel(table_name = devices)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct Device {
+    pub id: String,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub pairing_code: Option<String>,
+    pub pairing_expires_at: Option<NaiveDateTime>,
+    pub name: Option<String>,
+    pub screen_id: Option<String>,
+    pub approved_by: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub approved_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+/// The latest heartbeat from a device. `client_errors` is a JSON array of strings.
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, QueryableByName, Insertable, Selectable, AsChangeset)]
+#[diesel(table_name = device_status)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(treat_none_as_null = true)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct DeviceStatus {
+    pub device_id: String,
+    pub last_seen_at: NaiveDateTime,
+    pub current_slide_id: Option<String>,
+    pub manifest_version: Option<String>,
+    pub uptime_seconds: i64,
+    pub client_errors: String,
+}
+
+/// A command queued for the devices showing a screen. `command` is the snake_case name
+/// of a `routes::Command` variant, and `slide_id` is only set for `jump_to_slide`. Times are stored in UTC.
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, QueryableByName, Insertable, Selectable)]
+#[diesel(table_name = screen_commands)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct ScreenCommand {
+    pub id: String,
+    pub screen_id: String,
+    pub command: String,
+    pub slide_id: Option<String>,
+    pub issued_by: String,
+    pub issued_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub acknowledged_at: Option<NaiveDateTime>,
+    pub acknowledged_by: Option<String>,
+    pub success: Option<bool>,
+    pub result_message: Option<String>,
+}
+
+/// A slide being shown on a screen, as reported by the screen. Times are stored in UTC.
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = play_events)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct NewPlayEvent {
+    pub slide_id: String,
+    pub screen_id: String,
+    pub device_id: String,
+    pub started_at: NaiveDateTime,
+    pub duration_ms: i64,
+}
+
+/// Play events aggregated per slide, day (in UTC) and screen
+#[derive(Debug, Clone, Serialize, Deserialize, QueryableByName)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct Impressions {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub slide_id: String,
+    /// None if the slide has been removed since
+    #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::Text>)]
+    pub caption: Option<String>,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub day: String,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub screen_id: String,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub impressions: i64,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub total_seconds: i64,
 }
 
 #[derive(Debug, AsChangeset)]
@@ -43,4 +381,122 @@ pub struct UpdateSlide<'a> {
     pub end_date: Option<NaiveDateTime>,
     pub active: Option<bool>,
     pub filetype: Option<&'a str>,
-}
\ No newline at end of file
+}
+
+/// An emergency message or slide that replaces all other content on the screens until it expires or is cleared.
+/// Times are stored in UTC.
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, QueryableByName, Insertable, Selectable)]
+#[diesel(table_name = overrides)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct Override {
+    pub id: String,
+    pub message: Option<String>,
+    pub slide_id: Option<String>,
+    pub created_by: String,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub cleared_by: Option<String>,
+    pub cleared_at: Option<NaiveDateTime>,
+}
+
+/// The public part of an `Override`, as shown to the screens
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScreenOverride {
+    pub id: String,
+    pub message: Option<String>,
+    pub slide_id: Option<String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl From<Override> for ScreenOverride {
+    fn from(o: Override) -> Self {
+        ScreenOverride {
+            id: o.id,
+            message: o.message,
+            slide_id: o.slide_id,
+            expires_at: o.expires_at.and_utc(),
+        }
+    }
+}
+
+/// A change made through the admin API, see `audit::record`. `before` and `after` are JSON.
+#[derive(Debug, Clone, Queryable, Selectable)]
+#[diesel(table_name = audit_events)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct AuditEvent {
+    pub id: i32,
+    pub actor: String,
+    pub action: String,
+    pub target_id: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = audit_events)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct NewAuditEvent {
+    pub actor: String,
+    pub action: String,
+    pub target_id: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+/// A login session, see `sessions::SqliteSessionStore`. `state` is a JSON object.
+#[derive(Debug, Clone, Serialize, Queryable, Insertable, Selectable)]
+#[diesel(table_name = sessions)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Session {
+    pub id: String,
+    #[serde(skip_serializing)]
+    pub key_hash: String,
+    #[serde(skip_serializing)]
+    pub state: String,
+    pub email: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub last_seen_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+/// Users with a verified email in `domain` get `role` when they first log in, see `provisioning`
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable, Selectable)]
+#[diesel(table_name = provisioning_domains)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct ProvisioningDomain {
+    pub domain: String,
+    pub role: Role,
+    pub created_by: String,
+    pub created_at: NaiveDateTime,
+}
+
+/// An exception to the provisioning domains for one email. None as `role` means it is never added automatically.
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable, Selectable)]
+#[diesel(table_name = provisioning_emails)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct ProvisioningEmail {
+    pub email: String,
+    pub role: Option<Role>,
+    pub created_by: String,
+    pub created_at: NaiveDateTime,
+}
+
+/// A single-use link for adding a user, see `POST /auth/invites`. Only the hash of the token is stored.
+#[derive(Debug, Clone, Serialize, Queryable, Insertable, Selectable)]
+#[diesel(table_name = invites)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Invite {
+    pub id: String,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub role: Role,
+    pub created_by: String,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub used_by: Option<String>,
+    pub used_at: Option<NaiveDateTime>,
+}
@@ -0,0 +1,68 @@
+//! Adding users automatically the first time they log in, so members don't have to be added one by one.
+//! Anyone with a verified email in a provisioning domain (e.g. `f.kth.se`) gets the domain's role.
+//! Single emails can be exceptions: allowed with a role even outside the domains, or never added.
+//! Admins are never added automatically.
+
+use crate::models::{ProvisioningDomain, ProvisioningEmail, Role};
+
+/// `@F.KTH.se ` and `f.kth.se` are the same domain
+pub(crate) fn normalize_domain(domain: &str) -> String {
+    domain.trim().trim_start_matches('@').to_lowercase()
+}
+
+pub(crate) fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// Checks that a role can be given automatically
+pub(crate) fn validate_role(role: Role) -> Result<(), &'static str> {
+    if role == Role::Admin {
+        return Err("Admins have to be added by hand");
+    }
+    Ok(())
+}
+
+/// The role a user who isn't in `users` yet should get when they log in with a verified `email`,
+/// or None if they shouldn't be added. `exception` is the provisioning email for `email`, if there is one.
+pub(crate) fn role_for(email: &str, domains: &[ProvisioningDomain], exception: Option<&ProvisioningEmail>) -> Option<Role> {
+    if let Some(exception) = exception {
+        return exception.role;
+    }
+    let (_, domain) = email.rsplit_once('@')?;
+    let domain = normalize_domain(domain);
+    domains.iter().find(|rule| rule.domain == domain).map(|rule| rule.role)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    #[test]
+    fn test_role_for() {
+        let now = Utc::now().naive_utc();
+        let domains = [ProvisioningDomain {
+            domain: "f.kth.se".to_string(),
+            role: Role::Viewer,
+            created_by: "admin@example.com".to_string(),
+            created_at: now,
+        }];
+        let exception = |role| ProvisioningEmail {
+            email: "someone@f.kth.se".to_string(),
+            role,
+            created_by: "admin@example.com".to_string(),
+            created_at: now,
+        };
+
+        assert_eq!(role_for("someone@F.kth.se", &domains, None), Some(Role::Viewer));
+        // Only that domain, not its subdomains or other domains ending the same way
+        assert_eq!(role_for("someone@sub.f.kth.se", &domains, None), None);
+        assert_eq!(role_for("someone@kth.se", &domains, None), None);
+        assert_eq!(role_for("not an email", &domains, None), None);
+
+        assert_eq!(role_for("someone@f.kth.se", &domains, Some(&exception(None))), None);
+        assert_eq!(role_for("someone@f.kth.se", &domains, Some(&exception(Some(Role::SlideEditor)))), Some(Role::SlideEditor));
+        assert_eq!(role_for("someone@gmail.com", &[], Some(&exception(Some(Role::Viewer)))), Some(Role::Viewer));
+    }
+}
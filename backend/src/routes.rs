@@ -1,23 +1,40 @@
+use std::collections::{BTreeMap, HashMap};
+
 use crate::actions;
-use crate::auth;
-use crate::auth::PermissionLevel;
+use crate::audit::{self, Change};
+use crate::auth::{self, AuditRead, Permission, RequirePermission, ScreensManage, ScreensOperate, SlidesRead, SlidesWrite, UsersManage};
+use crate::broadcast::{Broadcaster, ScreenEvent};
+use crate::calendar::{self, Calendars, EventSlide};
+use crate::devices::{self, AuthenticatedDevice, PairedDevice};
 use crate::fs_helpers;
-use crate::models::{User, Settings};
+use crate::login::{self, Identity, LoginError};
+use crate::models::{self, User, Role, SlideStatus, Settings, Screen, ScreenOverride, LayoutType, ColorMode};
+use crate::provisioning;
+use crate::schedule::{self, QuietHours, ResolvedSettings, ScheduleRule};
+use crate::sessions;
+use crate::settings_registry::{self, SettingDefinition, SettingValues};
+use crate::sl::{self, Departure, Deviation, SlClient};
 
-use super::auth::check_user_permission;
+use super::auth::check_user_role;
 
+use actix_web::delete;
 use actix_web::error;
 use actix_web::get;
+use actix_web::http::header;
+use actix_web::patch;
 use actix_web::post;
+use actix_web::put;
 use actix_web::web;
-use google_oauth::AsyncClient;
 
 use actix_session::Session;
+use diesel::Connection;
+use chrono::{DateTime, Datelike, Days, Local, NaiveDate, NaiveDateTime, TimeDelta, TimeZone, Utc};
 use serde::Deserialize;
 use serde::Serialize;
 
 use super::auth::AuthenticatedUser;
 
+use actix_web::HttpRequest;
 use actix_web::HttpResponse;
 
 use actix_web::error::ErrorInternalServerError;
@@ -26,7 +43,7 @@ use uuid::Uuid;
 
 use actix_web::Responder;
 
-use super::SlideUploadForm;
+use super::{EventPosterForm, SlideUploadForm};
 
 use actix_multipart::form::MultipartForm;
 
@@ -34,19 +51,84 @@ use super::DbPool;
 
 // For documentation, see endpoints.md
 
-// A route is protected (needs auth) if it has a parameter of type AuthenticatedUser
+// A route is protected (needs auth) if it has a parameter of type AuthenticatedUser,
+// or RequirePermission<P> if it needs a permission that not every role has (see auth::Permission)
+// Screen routes that need a kiosk have a parameter of type AuthenticatedDevice (for one screen) or PairedDevice instead
 
 // --- Slides ---
 
+/// Why a slide can't be changed by the caller
+enum SlideDenied {
+    NotFound,
+    Forbidden,
+}
+
+impl From<SlideDenied> for HttpResponse {
+    fn from(denied: SlideDenied) -> Self {
+        match denied {
+            SlideDenied::NotFound => HttpResponse::NotFound().finish(),
+            SlideDenied::Forbidden => HttpResponse::Forbidden().finish(),
+        }
+    }
+}
+
+/// Load a slide if the caller has `permission` for it, see `auth::has_slide_permission`
+fn check_slide_permission(conn: &mut diesel::SqliteConnection, caller: &AuthenticatedUser, permission: Permission, slide_id: &str)
+-> Result<Result<models::Slide, SlideDenied>, actions::DbError> {
+    let Some(slide) = actions::get_slide(conn, slide_id)? else {
+        return Ok(Err(SlideDenied::NotFound));
+    };
+    if !auth::has_slide_permission(conn, caller, permission, slide.group_id.as_deref())? {
+        return Ok(Err(SlideDenied::Forbidden));
+    }
+    Ok(Ok(slide))
+}
+
 #[post("/screen/slides/save")]
 pub(crate) async fn save_slide(
+    caller: AuthenticatedUser,
     pool: web::Data<DbPool>,
+    broadcaster: web::Data<Broadcaster>,
     form: MultipartForm<SlideUploadForm>,
 ) -> actix_web::Result<impl Responder> {
     let id = Uuid::new_v4();
 
     // Parse the form into a Slide and a TempFile (the image)
-    let (slide, image_file) = form.into_inner().parse_form(id).map_err(ErrorInternalServerError)?;
+    let (mut slide, image_file) = form.into_inner().parse_form(id, &caller.email)?;
+
+    enum Check {
+        UnknownGroup,
+        Forbidden,
+        Allowed { can_publish: bool },
+    }
+
+    // Check that the caller may add slides to the group before saving the image
+    let group = slide.group_id.clone();
+    let check_pool = pool.clone();
+    let email = caller.email.clone();
+    let check = web::block(move || {
+        let mut conn = check_pool.get()?;
+
+        if let Some(ref group) = group {
+            if !actions::group_exists(&mut conn, group)? {
+                return Ok(Check::UnknownGroup);
+            }
+        }
+        if !auth::has_slide_permission(&mut conn, &caller, Permission::SlidesWrite, group.as_deref())? {
+            return Ok(Check::Forbidden);
+        }
+        let can_publish = auth::has_slide_permission(&mut conn, &caller, Permission::SlidesPublish, group.as_deref())?;
+
+        Ok::<_, actions::DbError>(Check::Allowed { can_publish })
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    match check {
+        Check::UnknownGroup => return Err(error::ErrorBadRequest("No group with that group_id")),
+        Check::Forbidden => return Ok(HttpResponse::Forbidden().finish()),
+        // Publishers don't need to review their own slides
+        Check::Allowed { can_publish: true } if slide.status == SlideStatus::Pending => slide.status = SlideStatus::Approved,
+        Check::Allowed { .. } => {},
+    }
 
     // Save file to disk
     let image_path = fs_helpers::save_image_file(image_file, &String::from(id), &slide.filetype).await?;
@@ -55,13 +137,21 @@ pub(crate) async fn save_slide(
     let db_result = web::block(move || {
         let mut conn = pool.get()?;
 
-        actions::insert_slide(&mut conn, slide)
+        let slide = actions::upload_slide(&mut conn, slide, &email, Utc::now().naive_utc())?;
+        audit::record(&mut conn, &email, "slide.create", &slide.id, Change::created(&slide))?;
+
+        Ok::<_, actions::DbError>(slide)
     })
     .await?;
 
     // Return different responses depending on if the database succeeded or not
     match db_result {
-        Ok(added_slide) => Ok(HttpResponse::Created().json(added_slide)),
+        Ok(added_slide) => {
+            if added_slide.status == SlideStatus::Approved {
+                broadcaster.send(ScreenEvent::Slides);
+            }
+            Ok(HttpResponse::Created().json(added_slide))
+        },
         Err(e) => {
             // If the database failed, remove the file from disk
             fs_helpers::remove_file(image_path).await?;
@@ -71,8 +161,89 @@ pub(crate) async fn save_slide(
     }
 }
 
+/// A slide as sent to the screens
+#[derive(Debug, Serialize)]
+struct ScreenSlide {
+    #[serde(flatten)]
+    slide: models::Slide,
+    /// Set for slides generated from calendar events. These have no image (and an empty `filetype`)
+    /// unless a poster has been uploaded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    event: Option<calendar::Event>,
+}
+
+type EventSlideSources = (Vec<models::CalendarFeed>, HashMap<String, models::EventSlideOptions>);
+
+/// The current slides of a screen, or of every screen if `screen` is None. Also reads what's needed to
+/// generate event slides, unless an override is active or it's quiet hours.
+fn get_current_slides(conn: &mut diesel::SqliteConnection, screen: Option<&str>, now: NaiveDateTime)
+-> Result<(Vec<models::Slide>, Option<EventSlideSources>), actions::DbError> {
+    let slides = actions::get_current_slides_for_screen(conn, screen, now)?;
+    // Quiet hours are in local time
+    let shows_regular_slides = actions::get_active_override(conn, now)?.is_none()
+        && !actions::get_quiet_hours(conn)?.is_quiet(now.and_utc().with_timezone(&Local).naive_local());
+    let sources = if shows_regular_slides {
+        Some((actions::get_calendar_feeds(conn)?, actions::get_event_slide_options(conn)?))
+    } else {
+        None
+    };
+    Ok((slides, sources))
+}
+
+/// Adds the event slides that should be shown right now after the regular slides
+fn with_event_slides(slides: Vec<models::Slide>, sources: Option<EventSlideSources>, calendars: &Calendars, now: DateTime<Utc>)
+-> Vec<ScreenSlide> {
+    let mut screen_slides: Vec<_> = slides.into_iter().map(|slide| ScreenSlide { slide, event: None }).collect();
+    let Some((feeds, options)) = sources else {
+        return screen_slides;
+    };
+
+    for EventSlide { key, shown_from, event } in calendars.event_slides(&feeds, now) {
+        let options = options.get(&key);
+        if shown_from > now || options.is_some_and(|options| options.suppressed) {
+            continue;
+        }
+        screen_slides.push(ScreenSlide {
+            slide: models::Slide {
+                id: format!("event-{key}"),
+                caption: event.title.clone(),
+                start_date: shown_from.naive_utc(),
+                end_date: event.start.naive_utc(),
+                active: true,
+                filetype: options.and_then(|options| options.poster_filetype.clone()).unwrap_or_default(),
+                created_by: None,
+                group_id: None,
+                status: SlideStatus::Approved,
+            },
+            event: Some(event),
+        });
+    }
+    screen_slides
+}
+
+/// The slides shown on every screen. While an override is active, this is only the override's slide (if any).
 #[get("/screen/slides")]
 pub(crate) async fn get_slides(
+    _: PairedDevice,
+    pool: web::Data<DbPool>,
+    calendars: web::Data<Calendars>,
+) -> actix_web::Result<impl Responder> {
+    let now = Utc::now();
+
+    let (slides, sources) = web::block(move || {
+        let mut conn = pool.get()?;
+        get_current_slides(&mut conn, None, now.naive_utc())
+    }).await?
+    // map diesel query errors to a 500 error response
+    .map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().json(with_event_slides(slides, sources, &calendars, now)))
+}
+
+/// Every slide, regardless of any override. Used by the admin page.
+#[get("/screen/slides/all")]
+pub(crate) async fn get_all_slides(
+    _: RequirePermission<SlidesRead>,
     pool: web::Data<DbPool>,
 ) -> actix_web::Result<impl Responder> {
 
@@ -86,45 +257,504 @@ pub(crate) async fn get_slides(
     Ok(HttpResponse::Ok().json(all_slides))
 }
 
+#[delete("/screen/slides/{id}")]
+pub(crate) async fn delete_slide(
+    path: web::Path<Uuid>,
+    caller: AuthenticatedUser,
+    pool: web::Data<DbPool>,
+    broadcaster: web::Data<Broadcaster>,
+) -> actix_web::Result<HttpResponse> {
+    let id = path.into_inner();
+
+    let slide = web::block(move || {
+        let mut conn = pool.get()?;
+
+        if let Err(denied) = check_slide_permission(&mut conn, &caller, Permission::SlidesWrite, &id.to_string())? {
+            return Ok(Err(denied));
+        }
+        let Some(slide) = actions::pop_slide(&mut conn, &id)? else {
+            return Ok(Err(SlideDenied::NotFound));
+        };
+        audit::record(&mut conn, &caller.email, "slide.remove", &slide.id, Change::removed(&slide))?;
+
+        Ok::<_, actions::DbError>(Ok(slide))
+    }).await?
+    .map_err(error::ErrorInternalServerError)?;
+
+    let slide = match slide {
+        Ok(slide) => slide,
+        Err(denied) => return Ok(denied.into()),
+    };
+
+    // The slide is already gone from the database, so a leftover image is only logged
+    let image_path = fs_helpers::image_path(&slide.id, &slide.filetype);
+    if let Err(e) = fs_helpers::remove_file(image_path).await {
+        log::warn!("Could not remove image for deleted slide {}: {e}", slide.id);
+    }
+
+    broadcaster.send(ScreenEvent::Slides);
+
+    Ok(HttpResponse::Ok().json(slide))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SlideScreensRequest {
+    screen_ids: Vec<String>,
+}
+
+/// The screens a slide is shown on. Empty means every screen.
+#[get("/screen/slides/{id}/screens")]
+pub(crate) async fn get_slide_screens(
+    path: web::Path<Uuid>,
+    _: RequirePermission<SlidesRead>,
+    pool: web::Data<DbPool>,
+) -> actix_web::Result<HttpResponse> {
+    let id = path.into_inner().to_string();
+
+    let screen_ids = web::block(move || {
+        let mut conn = pool.get()?;
+
+        if !actions::slide_exists(&mut conn, &id)? {
+            return Ok(None);
+        }
+        actions::get_slide_screens(&mut conn, &id).map(Some)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(match screen_ids {
+        Some(screen_ids) => HttpResponse::Ok().json(SlideScreensRequest { screen_ids }),
+        None => HttpResponse::NotFound().finish(),
+    })
+}
+
+#[put("/screen/slides/{id}/screens")]
+pub(crate) async fn set_slide_screens(
+    path: web::Path<Uuid>,
+    screens_req: web::Json<SlideScreensRequest>,
+    caller: AuthenticatedUser,
+    pool: web::Data<DbPool>,
+    broadcaster: web::Data<Broadcaster>,
+) -> actix_web::Result<HttpResponse> {
+    let id = path.into_inner().to_string();
+    let SlideScreensRequest { mut screen_ids } = screens_req.into_inner();
+    screen_ids.sort();
+    screen_ids.dedup();
+
+    enum Outcome {
+        Updated(SlideScreensRequest),
+        Denied(SlideDenied),
+        UnknownScreen,
+    }
+
+    let outcome = web::block(move || {
+        let mut conn = pool.get()?;
+
+        if let Err(denied) = check_slide_permission(&mut conn, &caller, Permission::SlidesWrite, &id)? {
+            return Ok(Outcome::Denied(denied));
+        }
+        if !actions::screens_exist(&mut conn, &screen_ids)? {
+            return Ok(Outcome::UnknownScreen);
+        }
+        let before = SlideScreensRequest { screen_ids: actions::get_slide_screens(&mut conn, &id)? };
+        actions::set_slide_screens(&mut conn, &id, &screen_ids)?;
+        let after = SlideScreensRequest { screen_ids };
+        audit::record(&mut conn, &caller.email, "slide.screens", &id, Change::changed(&before, &after))?;
+
+        Ok::<_, actions::DbError>(Outcome::Updated(after))
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    match outcome {
+        Outcome::Updated(body) => {
+            broadcaster.send(ScreenEvent::Slides);
+            Ok(HttpResponse::Ok().json(body))
+        },
+        Outcome::Denied(denied) => Ok(denied.into()),
+        Outcome::UnknownScreen => Err(error::ErrorBadRequest("Unknown screen id")),
+    }
+}
+
+// --- Slide approval ---
+
+/// Slides waiting for review that the caller may approve or reject, oldest submission first
+#[get("/screen/slides/pending")]
+pub(crate) async fn list_pending_slides(caller: AuthenticatedUser, pool: web::Data<DbPool>) -> actix_web::Result<HttpResponse> {
+    let pending = web::block(move || {
+        let mut conn = pool.get()?;
+
+        let mut reviewable = Vec::new();
+        for slide in actions::get_pending_slides(&mut conn)? {
+            if auth::has_slide_permission(&mut conn, &caller, Permission::SlidesPublish, slide.group_id.as_deref())? {
+                reviewable.push(slide);
+            }
+        }
+        Ok::<_, actions::DbError>(reviewable)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().json(pending))
+}
+
+enum StatusOutcome {
+    Changed(models::SlideStatusChange),
+    Denied(SlideDenied),
+    /// The slide's status doesn't allow the change
+    Conflict(SlideStatus),
+}
+
+/// Move a slide to `to` if the caller has `permission` for it and the workflow allows it
+fn change_slide_status(
+    conn: &mut diesel::SqliteConnection,
+    caller: &AuthenticatedUser,
+    permission: Permission,
+    slide_id: &str,
+    to: SlideStatus,
+    comment: Option<String>,
+) -> Result<StatusOutcome, actions::DbError> {
+    let slide = match check_slide_permission(conn, caller, permission, slide_id)? {
+        Ok(slide) => slide,
+        Err(denied) => return Ok(StatusOutcome::Denied(denied)),
+    };
+    if !slide.status.can_change_to(to) {
+        return Ok(StatusOutcome::Conflict(slide.status));
+    }
+
+    let change = models::SlideStatusChange {
+        id: Uuid::new_v4().into(),
+        slide_id: slide.id,
+        from_status: Some(slide.status),
+        to_status: to,
+        changed_by: caller.email.clone(),
+        comment,
+        changed_at: Utc::now().naive_utc(),
+    };
+    // Someone else may have changed it since it was read
+    if !actions::change_slide_status(conn, &change)? {
+        return Ok(StatusOutcome::Conflict(slide.status));
+    }
+    audit::record(conn, &caller.email, "slide.status", &change.slide_id, Change::changed(
+        &serde_json::json!({ "status": slide.status }),
+        &serde_json::json!({ "status": to, "comment": change.comment }),
+    ))?;
+    Ok(StatusOutcome::Changed(change))
+}
+
+fn status_change_response(outcome: StatusOutcome, broadcaster: &Broadcaster) -> actix_web::Result<HttpResponse> {
+    match outcome {
+        StatusOutcome::Changed(change) => {
+            if change.to_status == SlideStatus::Approved {
+                broadcaster.send(ScreenEvent::Slides);
+            }
+            Ok(HttpResponse::Ok().json(change))
+        },
+        StatusOutcome::Denied(denied) => Ok(denied.into()),
+        StatusOutcome::Conflict(status) => Err(error::ErrorConflict(format!("The slide is {}", status.as_str()))),
+    }
+}
+
+/// Submits a draft or rejected slide for review
+#[post("/screen/slides/{id}/submit")]
+pub(crate) async fn submit_slide(
+    path: web::Path<Uuid>,
+    caller: AuthenticatedUser,
+    pool: web::Data<DbPool>,
+    broadcaster: web::Data<Broadcaster>,
+) -> actix_web::Result<HttpResponse> {
+    let id = path.into_inner().to_string();
+
+    let outcome = web::block(move || {
+        let mut conn = pool.get()?;
+
+        change_slide_status(&mut conn, &caller, Permission::SlidesWrite, &id, SlideStatus::Pending, None)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    status_change_response(outcome, &broadcaster)
+}
+
+#[derive(Debug, Deserialize)]
+struct ReviewRequest {
+    approve: bool,
+    /// Required when rejecting, so the uploader knows what to change
+    comment: Option<String>,
+}
+
+/// Approves or rejects a pending slide
+#[post("/screen/slides/{id}/review")]
+pub(crate) async fn review_slide(
+    path: web::Path<Uuid>,
+    review_req: web::Json<ReviewRequest>,
+    caller: AuthenticatedUser,
+    pool: web::Data<DbPool>,
+    broadcaster: web::Data<Broadcaster>,
+) -> actix_web::Result<HttpResponse> {
+    let id = path.into_inner().to_string();
+    let ReviewRequest { approve, comment } = review_req.into_inner();
+    let comment = comment.filter(|c| !c.trim().is_empty());
+    if !approve && comment.is_none() {
+        return Err(error::ErrorBadRequest("A rejection needs a comment"));
+    }
+    let to = if approve { SlideStatus::Approved } else { SlideStatus::Rejected };
+
+    let outcome = web::block(move || {
+        let mut conn = pool.get()?;
+
+        change_slide_status(&mut conn, &caller, Permission::SlidesPublish, &id, to, comment)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    status_change_response(outcome, &broadcaster)
+}
+
+/// Every status change of a slide, oldest first. Also works for removed slides.
+#[get("/screen/slides/{id}/history")]
+pub(crate) async fn get_slide_history(path: web::Path<Uuid>, _: RequirePermission<SlidesRead>, pool: web::Data<DbPool>)
+-> actix_web::Result<HttpResponse> {
+    let id = path.into_inner().to_string();
+
+    let changes = web::block(move || {
+        let mut conn = pool.get()?;
+
+        actions::get_slide_status_changes(&mut conn, &id)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().json(changes))
+}
+
+// --- Override ---
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OverrideRequest {
+    message: Option<String>,
+    slide_id: Option<String>,
+    duration_minutes: i64,
+}
+
+// An override is meant for short notices, not to replace the normal content for days
+const MAX_OVERRIDE_MINUTES: i64 = 7 * 24 * 60;
+
+#[post("/screen/override")]
+pub(crate) async fn set_override(
+    override_req: web::Json<OverrideRequest>,
+    caller: RequirePermission<ScreensOperate>,
+    pool: web::Data<DbPool>,
+    broadcaster: web::Data<Broadcaster>,
+) -> actix_web::Result<HttpResponse> {
+    let email = caller.into_inner().email;
+
+    let OverrideRequest { message, slide_id, duration_minutes } = override_req.into_inner();
+    // An empty message is the same as no message
+    let message = message.filter(|m| !m.trim().is_empty());
+    if message.is_none() && slide_id.is_none() {
+        return Err(error::ErrorBadRequest("An override needs a message or a slide_id"));
+    }
+    if !(1..=MAX_OVERRIDE_MINUTES).contains(&duration_minutes) {
+        return Err(error::ErrorBadRequest(format!("duration_minutes must be between 1 and {MAX_OVERRIDE_MINUTES}")));
+    }
+
+    let now = Utc::now().naive_utc();
+    let new_override = models::Override {
+        id: Uuid::new_v4().into(),
+        message,
+        slide_id,
+        created_by: email,
+        created_at: now,
+        expires_at: now + TimeDelta::minutes(duration_minutes),
+        cleared_by: None,
+        cleared_at: None,
+    };
+
+    // Use web::block to avoid blocking async
+    let new_override = web::block(move || {
+        let mut conn = pool.get()?;
+
+        if let Some(ref slide) = new_override.slide_id {
+            if !actions::slide_exists(&mut conn, slide)? {
+                return Ok(None);
+            }
+        }
+
+        let new_override = actions::insert_override(&mut conn, new_override)?;
+        audit::record(&mut conn, &new_override.created_by, "override.set", &new_override.id, Change::created(&new_override))?;
+
+        Ok::<_, actions::DbError>(Some(new_override))
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    let Some(new_override) = new_override else {
+        return Err(error::ErrorBadRequest("No slide with that slide_id"));
+    };
+
+    log::warn!("Override {} activated by {} until {}: {:?}",
+        new_override.id, new_override.created_by, new_override.expires_at, new_override.message);
+
+    broadcaster.send(ScreenEvent::Override { active: Some(new_override.clone().into()) });
+
+    Ok(HttpResponse::Created().json(new_override))
+}
+
+#[delete("/screen/override")]
+pub(crate) async fn clear_override(
+    caller: RequirePermission<ScreensOperate>,
+    pool: web::Data<DbPool>,
+    broadcaster: web::Data<Broadcaster>,
+) -> actix_web::Result<HttpResponse> {
+    let email = caller.into_inner().email;
+
+    let cleared = web::block(move || {
+        let mut conn = pool.get()?;
+
+        let Some(cleared) = actions::clear_override(&mut conn, &email, Utc::now().naive_utc())? else {
+            return Ok(None);
+        };
+        let before = models::Override { cleared_by: None, cleared_at: None, ..cleared.clone() };
+        audit::record(&mut conn, &email, "override.clear", &cleared.id, Change::changed(&before, &cleared))?;
+
+        Ok::<_, actions::DbError>(Some((cleared, email)))
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    let Some((cleared, email)) = cleared else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    log::warn!("Override {} cleared by {}", cleared.id, email);
+
+    broadcaster.send(ScreenEvent::Override { active: None });
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// The override currently shown on the screens. 204 if there is none.
+#[get("/screen/override")]
+pub(crate) async fn get_override(_: PairedDevice, pool: web::Data<DbPool>) -> actix_web::Result<HttpResponse> {
+    let active = web::block(move || {
+        let mut conn = pool.get()?;
+
+        actions::get_active_override(&mut conn, Utc::now().naive_utc())
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(match active {
+        Some(active) => HttpResponse::Ok().json(ScreenOverride::from(active)),
+        None => HttpResponse::NoContent().finish(),
+    })
+}
+
+#[get("/screen/override/history")]
+pub(crate) async fn list_overrides(_: RequirePermission<ScreensOperate>, pool: web::Data<DbPool>) -> actix_web::Result<HttpResponse> {
+    let all_overrides = web::block(move || {
+        let mut conn = pool.get()?;
+
+        actions::get_all_overrides(&mut conn)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().json(all_overrides))
+}
+
+// --- Push ---
+
+/// Server-Sent Events stream that tells screens when to refetch slides or settings
+#[get("/screen/stream")]
+pub(crate) async fn screen_stream(_: PairedDevice, broadcaster: web::Data<Broadcaster>) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header((header::CONTENT_TYPE, "text/event-stream"))
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        // Stops nginx from buffering the stream
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(broadcaster.subscribe())
+}
+
 // --- Authentication ---
 
 #[derive(Serialize, Deserialize, Debug)]
 struct AuthRequest {
     id_token: String,
+    /// Which identity provider issued `id_token`, see `login::Providers`. Google if not set.
+    #[serde(default)]
+    provider: Option<String>,
+    /// An invite token from `POST /auth/invites`, for users who aren't added yet
+    #[serde(default)]
+    invite: Option<String>,
 }
 
-#[post("/auth/verify")]
-pub(crate) async fn verify_token(req: web::Json<AuthRequest>, session: Session, pool: web::Data<DbPool>) -> HttpResponse {
+/// The logged in user, and what their role lets them do so the admin page can hide the rest
+#[derive(Debug, Serialize)]
+struct LoginStatus {
+    #[serde(flatten)]
+    user: AuthenticatedUser,
+    permissions: &'static [Permission],
+}
+
+impl From<AuthenticatedUser> for LoginStatus {
+    fn from(user: AuthenticatedUser) -> Self {
+        let permissions = user.role.permissions();
+        LoginStatus { user, permissions }
+    }
+}
 
-    let client_id = std::env::var("GOOGLE_ID_TOKEN").expect("GOOGLE_ID_TOKEN should be set");
-    
-    let client = AsyncClient::new(&client_id);
-    let payload = match client.validate_id_token(&req.id_token).await {
-        Ok(g) => g,
-        Err(_) => return HttpResponse::Unauthorized().finish(),
+#[post("/auth/verify")]
+pub(crate) async fn verify_token(
+    req: web::Json<AuthRequest>,
+    http_req: HttpRequest,
+    session: Session,
+    pool: web::Data<DbPool>,
+    providers: web::Data<login::Providers>,
+) -> HttpResponse {
+    let provider_name = req.provider.as_deref().unwrap_or(login::GOOGLE);
+    let Some(provider) = providers.get(provider_name) else {
+        return HttpResponse::BadRequest().body(format!("Logging in with {provider_name} is not configured"));
     };
 
-    let Some(email) = payload.email else { 
-        log::error!("No email in Google payload");
-        return HttpResponse::InternalServerError().finish();
+    let Identity { email, email_verified } = match provider.verify(&req.id_token).await {
+        Ok(identity) => identity,
+        Err(LoginError::Invalid(e)) => {
+            log::info!("Rejected {provider_name} login: {e}");
+            return HttpResponse::Unauthorized().finish();
+        },
+        Err(LoginError::Unavailable(e)) => {
+            log::error!("Couldn't check {provider_name} login: {e}");
+            return HttpResponse::InternalServerError().finish();
+        },
     };
 
-    let permission = match check_user_permission(email.clone(), pool).await {
-        Ok(Some(permission)) => {permission},
-        Ok(None) => {return HttpResponse::Unauthorized().finish()},
+    // Users are found by their email, so anyone who can get a token with someone else's unverified
+    // address from the provider could otherwise log in as them
+    if !email_verified {
+        log::info!("Rejected {provider_name} login for {email}: the email isn't verified");
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let role = match check_user_role(email.clone(), pool.clone()).await {
+        Ok(Some(role)) => {role},
+        // An invite adds whoever uses it, whatever the provisioning rules say
+        Ok(None) if req.invite.is_some() => {
+            let token = req.invite.clone().unwrap_or_default();
+            match accept_invite(token, email.clone(), pool).await {
+                Ok(Some(role)) => role,
+                Ok(None) => return HttpResponse::Unauthorized().body("The invite is invalid, used or expired"),
+                Err(e) => {
+                    log::error!("accept_invite error: {e}");
+                    return HttpResponse::InternalServerError().finish()
+                },
+            }
+        },
+        // Unknown users may be added by the provisioning rules
+        Ok(None) => match provision_user(email.clone(), pool).await {
+            Ok(Some(role)) => role,
+            Ok(None) => return HttpResponse::Unauthorized().finish(),
+            Err(e) => {
+                log::error!("provision_user error: {e}");
+                return HttpResponse::InternalServerError().finish()
+            },
+        },
         Err(e) => {
-            log::error!("check_user_permission error: {e}");
+            log::error!("check_user_role error: {e}");
             return HttpResponse::InternalServerError().finish()
         },
     };
 
-    let user = AuthenticatedUser { email, permission };
+    let user = AuthenticatedUser { email, role };
+    // Shown when listing sessions, so users can tell them apart
+    let user_agent = http_req.headers().get(header::USER_AGENT).and_then(|value| value.to_str().ok()).map(str::to_string);
 
-    match session.insert("auth", &user) {
+    match session.insert(sessions::AUTH_KEY, &user).and_then(|()| session.insert(sessions::USER_AGENT_KEY, user_agent)) {
         Ok(()) => {
             log::info!("User {} authenticated", user.email);
             session.renew();
-            HttpResponse::Ok().json(user)
+            HttpResponse::Ok().json(LoginStatus::from(user))
         },
         Err(e) => {
             log::error!("{}", e);
@@ -133,118 +763,2664 @@ pub(crate) async fn verify_token(req: web::Json<AuthRequest>, session: Session,
     }
 }
 
+/// Adds a user who logs in for the first time if the provisioning rules allow it, and returns their role
+async fn provision_user(email: String, pool: web::Data<DbPool>) -> Result<Option<Role>, actions::DbError> {
+    web::block(move || {
+        let mut conn = pool.get()?;
+
+        let Some(user) = actions::provision_user(&mut conn, &email)? else {
+            return Ok(None);
+        };
+        log::info!("Added {} as {} on their first login", user.email, user.role.as_str());
+        audit::record(&mut conn, &user.email, "user.provision", &user.id, Change::created(&user))?;
+
+        Ok(Some(user.role))
+    }).await?
+}
+
+/// Adds a user who logs in with an invite, and returns their role
+async fn accept_invite(token: String, email: String, pool: web::Data<DbPool>) -> Result<Option<Role>, actions::DbError> {
+    web::block(move || {
+        let mut conn = pool.get()?;
+
+        let Some((user, invite)) = actions::accept_invite(&mut conn, &devices::hash_token(&token), &email, Utc::now().naive_utc())? else {
+            return Ok(None);
+        };
+        if invite.used_by.as_deref() == Some(user.email.as_str()) {
+            log::info!("Added {} as {} with invite {}", user.email, user.role.as_str(), invite.id);
+            audit::record(&mut conn, &user.email, "user.invite", &user.id, Change::created(&user))?;
+        }
+
+        Ok(Some(user.role))
+    }).await?
+}
+
 #[get("/auth/status")]
 pub(crate) async fn login_status(user: AuthenticatedUser) -> HttpResponse {
     // This only runs if the user is authenticated, see AuthenticatedUser
-    HttpResponse::Ok().json(user)
+    HttpResponse::Ok().json(LoginStatus::from(user))
 }
 
 #[post("/auth/logout")]
 pub(crate) async fn logout(_: AuthenticatedUser, session: Session) -> HttpResponse {
-    session.clear();
+    // Also removes the session from the database
+    session.purge();
     HttpResponse::Ok().finish()
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct AddUserRequest {
-    email: String,
-    permission: PermissionLevel,
-}
+/// The caller's own sessions, most recently used first
+#[get("/auth/sessions")]
+pub(crate) async fn list_own_sessions(caller: AuthenticatedUser, pool: web::Data<DbPool>) -> actix_web::Result<HttpResponse> {
+    // Use web::block to avoid blocking async
+    let active = web::block(move || {
+        let mut conn = pool.get()?;
 
-#[post("/auth/add_user")]
-pub(crate) async fn add_user(user_req: web::Json<AddUserRequest>, caller: AuthenticatedUser, pool: web::Data<DbPool>) 
--> actix_web::Result<HttpResponse> {
-    // Check if caller has admin permissions
-    if let AuthenticatedUser { permission: PermissionLevel::Admin, .. } = caller {
-        // Use web::block to avoid blocking async
-        let user = web::block(move || {
-            let mut conn = pool.get()?;
+        actions::get_active_sessions(&mut conn, Some(&caller.email), Utc::now().naive_utc())
+    }).await?.map_err(error::ErrorInternalServerError)?;
 
-            // Parse AddUserRequest to User
-            let user = User {
-                id: Uuid::new_v4().into(),
-                email: user_req.email.clone(),
-                admin: match user_req.permission {
-                    PermissionLevel::User => false,
-                    PermissionLevel::Admin => true,
-                },
-            };
+    Ok(HttpResponse::Ok().json(active))
+}
 
-            actions::insert_user(&mut conn, user)
-        }).await?.map_err(error::ErrorInternalServerError)?;
+/// Every user's sessions, most recently used first
+#[get("/auth/sessions/all")]
+pub(crate) async fn list_all_sessions(_: RequirePermission<UsersManage>, pool: web::Data<DbPool>) -> actix_web::Result<HttpResponse> {
+    // Use web::block to avoid blocking async
+    let active = web::block(move || {
+        let mut conn = pool.get()?;
 
-        Ok(HttpResponse::Ok().json(user))
-    } else {
-        Ok(HttpResponse::Forbidden().finish())
-    }
-}
+        actions::get_active_sessions(&mut conn, None, Utc::now().naive_utc())
+    }).await?.map_err(error::ErrorInternalServerError)?;
 
-#[derive(Debug, Serialize, Deserialize)]
-struct RemoveUserRequest {
-    id: String,
+    Ok(HttpResponse::Ok().json(active))
 }
 
-#[post("/auth/remove_user")]
-pub(crate) async fn remove_user(user_req: web::Json<RemoveUserRequest>, caller: AuthenticatedUser, pool: web::Data<DbPool>)
+/// Logs a session out. Users can revoke their own sessions, and users with `users_manage` anyone's.
+#[delete("/auth/sessions/{id}")]
+pub(crate) async fn revoke_session(path: web::Path<String>, caller: AuthenticatedUser, pool: web::Data<DbPool>)
 -> actix_web::Result<HttpResponse> {
-    // Check if caller has admin permissions
-    if let AuthenticatedUser { permission: PermissionLevel::Admin, .. } = caller {
-        // Use web::block to avoid blocking async
-        web::block(move || {
-            let mut conn = pool.get()?;
-
-            actions::remove_user(&mut conn, &user_req.id)
-        }).await?.map_err(error::ErrorInternalServerError)?;
+    let id = path.into_inner();
 
-        Ok(HttpResponse::Ok().finish())
-    } else {
-        Ok(HttpResponse::Forbidden().finish())
-    }
-}
+    // Use web::block to avoid blocking async
+    let revoked = web::block(move || {
+        let mut conn = pool.get()?;
 
-#[get("/auth/list_users")]
-pub(crate) async fn list_users(caller: AuthenticatedUser, pool: web::Data<DbPool>) -> actix_web::Result<HttpResponse> {
-    // Check if caller has admin permissions
-    if let AuthenticatedUser { permission: PermissionLevel::Admin, .. } = caller {
-        // Use web::block to avoid blocking async
-        let users = web::block(move || {
-            let mut conn = pool.get()?;
+        let Some(session) = actions::get_session(&mut conn, &id)? else {
+            return Ok(false);
+        };
+        // Other users' sessions look like they don't exist
+        if session.email.as_deref() != Some(caller.email.as_str()) && !caller.role.has(Permission::UsersManage) {
+            return Ok(false);
+        }
+        actions::remove_session(&mut conn, &id)?;
+        audit::record(&mut conn, &caller.email, "session.revoke", &id, Change::removed(&session))?;
 
-            actions::get_all_users(&mut conn)
-        }).await?.map_err(error::ErrorInternalServerError)?;
+        Ok::<_, actions::DbError>(true)
+    }).await?.map_err(error::ErrorInternalServerError)?;
 
-        Ok(HttpResponse::Ok().json(users))
+    Ok(if revoked {
+        HttpResponse::Ok().finish()
     } else {
-        Ok(HttpResponse::Forbidden().finish())
-    }
+        HttpResponse::NotFound().finish()
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AddUserRequest {
+    email: String,
+    role: Role,
+}
+
+#[post("/auth/add_user")]
+pub(crate) async fn add_user(user_req: web::Json<AddUserRequest>, caller: RequirePermission<UsersManage>, pool: web::Data<DbPool>) 
+-> actix_web::Result<HttpResponse> {
+    let caller = caller.into_inner();
+    // Use web::block to avoid blocking async
+    let user = web::block(move || {
+        let mut conn = pool.get()?;
+
+        // Parse AddUserRequest to User
+        let user = User {
+            id: Uuid::new_v4().into(),
+            email: user_req.email.clone(),
+            role: user_req.role,
+        };
+
+        let new_id = user.id.clone();
+        let user = actions::insert_user(&mut conn, user)?;
+        // Adding a user that already exists changes nothing
+        if user.id == new_id {
+            audit::record(&mut conn, &caller.email, "user.create", &user.id, Change::created(&user))?;
+        }
+
+        Ok::<_, actions::DbError>(user)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().json(user))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RemoveUserRequest {
+    id: String,
+}
+
+#[post("/auth/remove_user")]
+pub(crate) async fn remove_user(user_req: web::Json<RemoveUserRequest>, caller: RequirePermission<UsersManage>, pool: web::Data<DbPool>)
+-> actix_web::Result<HttpResponse> {
+    let caller = caller.into_inner();
+    // Use web::block to avoid blocking async
+    let removed = web::block(move || {
+        let mut conn = pool.get()?;
+
+        let removed = actions::remove_user(&mut conn, &user_req.id)?;
+        if let Ok(ref user) = removed {
+            audit::record(&mut conn, &caller.email, "user.remove", &user.id, Change::removed(user))?;
+        }
+
+        Ok::<_, actions::DbError>(removed)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    match removed {
+        Ok(_) => Ok(HttpResponse::Ok().finish()),
+        Err(denied) => user_change_denied(denied),
+    }
+}
+
+fn user_change_denied(denied: actions::UserChangeDenied) -> actix_web::Result<HttpResponse> {
+    match denied {
+        actions::UserChangeDenied::NotFound => Ok(HttpResponse::NotFound().finish()),
+        actions::UserChangeDenied::LastAdmin => Err(error::ErrorConflict("There has to be at least one admin")),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct UpdateUserRequest {
+    role: Role,
+}
+
+/// Changes a user's role
+#[patch("/auth/users/{id}")]
+pub(crate) async fn update_user(
+    path: web::Path<String>,
+    user_req: web::Json<UpdateUserRequest>,
+    caller: RequirePermission<UsersManage>,
+    pool: web::Data<DbPool>,
+) -> actix_web::Result<HttpResponse> {
+    let caller = caller.into_inner();
+    let id = path.into_inner();
+    let UpdateUserRequest { role } = user_req.into_inner();
+
+    // Use web::block to avoid blocking async
+    let updated = web::block(move || {
+        let mut conn = pool.get()?;
+
+        let updated = actions::set_user_role(&mut conn, &id, role)?
+            .map(|before| (User { role, ..before.clone() }, before));
+        if let Ok((ref after, ref before)) = updated {
+            audit::record(&mut conn, &caller.email, "user.update", &after.id, Change::changed(before, after))?;
+        }
+
+        Ok::<_, actions::DbError>(updated.map(|(after, _)| after))
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    match updated {
+        Ok(user) => Ok(HttpResponse::Ok().json(user)),
+        Err(denied) => user_change_denied(denied),
+    }
+}
+
+#[get("/auth/list_users")]
+pub(crate) async fn list_users(_: RequirePermission<UsersManage>, pool: web::Data<DbPool>) -> actix_web::Result<HttpResponse> {
+    // Use web::block to avoid blocking async
+    let users = web::block(move || {
+        let mut conn = pool.get()?;
+
+        actions::get_all_users(&mut conn)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().json(users))
+}
+
+
+// --- Provisioning ---
+
+#[derive(Debug, Serialize)]
+struct ProvisioningResponse {
+    domains: Vec<models::ProvisioningDomain>,
+    emails: Vec<models::ProvisioningEmail>,
+}
+
+/// The domains whose users are added when they first log in, and the exceptions for single emails
+#[get("/auth/provisioning")]
+pub(crate) async fn get_provisioning(_: RequirePermission<UsersManage>, pool: web::Data<DbPool>) -> actix_web::Result<HttpResponse> {
+    // Use web::block to avoid blocking async
+    let provisioning = web::block(move || {
+        let mut conn = pool.get()?;
+
+        Ok::<_, actions::DbError>(ProvisioningResponse {
+            domains: actions::get_provisioning_domains(&mut conn)?,
+            emails: actions::get_provisioning_emails(&mut conn)?,
+        })
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().json(provisioning))
+}
+
+#[derive(Debug, Deserialize)]
+struct ProvisioningDomainRequest {
+    role: Role,
+}
+
+/// Adds a provisioning domain, or changes its role
+#[put("/auth/provisioning/domains/{domain}")]
+pub(crate) async fn set_provisioning_domain(
+    path: web::Path<String>,
+    domain_req: web::Json<ProvisioningDomainRequest>,
+    caller: RequirePermission<UsersManage>,
+    pool: web::Data<DbPool>,
+) -> actix_web::Result<HttpResponse> {
+    let caller = caller.into_inner();
+    let domain = provisioning::normalize_domain(&path);
+    if domain.is_empty() || domain.contains('@') {
+        return Err(error::ErrorBadRequest("Invalid domain"));
+    }
+    let ProvisioningDomainRequest { role } = domain_req.into_inner();
+    provisioning::validate_role(role).map_err(error::ErrorBadRequest)?;
+
+    let rule = models::ProvisioningDomain { domain, role, created_by: caller.email.clone(), created_at: Utc::now().naive_utc() };
+
+    // Use web::block to avoid blocking async
+    let rule = web::block(move || {
+        let mut conn = pool.get()?;
+
+        let before = actions::get_provisioning_domain(&mut conn, &rule.domain)?;
+        actions::set_provisioning_domain(&mut conn, &rule)?;
+        audit::record(&mut conn, &caller.email, "provisioning.domain", &rule.domain, Change::changed(&before, &rule))?;
+
+        Ok::<_, actions::DbError>(rule)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().json(rule))
+}
+
+#[delete("/auth/provisioning/domains/{domain}")]
+pub(crate) async fn remove_provisioning_domain(path: web::Path<String>, caller: RequirePermission<UsersManage>, pool: web::Data<DbPool>)
+-> actix_web::Result<HttpResponse> {
+    let caller = caller.into_inner();
+    let domain = provisioning::normalize_domain(&path);
+
+    // Use web::block to avoid blocking async
+    let removed = web::block(move || {
+        let mut conn = pool.get()?;
+
+        let Some(rule) = actions::get_provisioning_domain(&mut conn, &domain)? else {
+            return Ok(false);
+        };
+        actions::remove_provisioning_domain(&mut conn, &domain)?;
+        audit::record(&mut conn, &caller.email, "provisioning.domain", &domain, Change::removed(&rule))?;
+
+        Ok::<_, actions::DbError>(true)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(if removed {
+        HttpResponse::Ok().finish()
+    } else {
+        HttpResponse::NotFound().finish()
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct ProvisioningEmailRequest {
+    /// None (null) means the email is never added automatically
+    role: Option<Role>,
+}
+
+/// Allows an email with a role regardless of the domains, or keeps it from being added automatically
+#[put("/auth/provisioning/emails/{email}")]
+pub(crate) async fn set_provisioning_email(
+    path: web::Path<String>,
+    email_req: web::Json<ProvisioningEmailRequest>,
+    caller: RequirePermission<UsersManage>,
+    pool: web::Data<DbPool>,
+) -> actix_web::Result<HttpResponse> {
+    let caller = caller.into_inner();
+    let email = provisioning::normalize_email(&path);
+    if !email.contains('@') {
+        return Err(error::ErrorBadRequest("Invalid email"));
+    }
+    let ProvisioningEmailRequest { role } = email_req.into_inner();
+    if let Some(role) = role {
+        provisioning::validate_role(role).map_err(error::ErrorBadRequest)?;
+    }
+
+    let exception = models::ProvisioningEmail { email, role, created_by: caller.email.clone(), created_at: Utc::now().naive_utc() };
+
+    // Use web::block to avoid blocking async
+    let exception = web::block(move || {
+        let mut conn = pool.get()?;
+
+        let before = actions::get_provisioning_email(&mut conn, &exception.email)?;
+        actions::set_provisioning_email(&mut conn, &exception)?;
+        audit::record(&mut conn, &caller.email, "provisioning.email", &exception.email, Change::changed(&before, &exception))?;
+
+        Ok::<_, actions::DbError>(exception)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().json(exception))
+}
+
+#[delete("/auth/provisioning/emails/{email}")]
+pub(crate) async fn remove_provisioning_email(path: web::Path<String>, caller: RequirePermission<UsersManage>, pool: web::Data<DbPool>)
+-> actix_web::Result<HttpResponse> {
+    let caller = caller.into_inner();
+    let email = provisioning::normalize_email(&path);
+
+    // Use web::block to avoid blocking async
+    let removed = web::block(move || {
+        let mut conn = pool.get()?;
+
+        let Some(exception) = actions::get_provisioning_email(&mut conn, &email)? else {
+            return Ok(false);
+        };
+        actions::remove_provisioning_email(&mut conn, &email)?;
+        audit::record(&mut conn, &caller.email, "provisioning.email", &email, Change::removed(&exception))?;
+
+        Ok::<_, actions::DbError>(true)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(if removed {
+        HttpResponse::Ok().finish()
+    } else {
+        HttpResponse::NotFound().finish()
+    })
+}
+
+// --- Invites ---
+
+const DEFAULT_INVITE_DAYS: i64 = 7;
+// Unused invites are a way in, so they shouldn't stay valid for long
+const MAX_INVITE_DAYS: i64 = 30;
+
+#[derive(Debug, Deserialize)]
+struct InviteRequest {
+    role: Role,
+    valid_days: Option<i64>,
+}
+
+/// A new invite, with the token that is only shown this once
+#[derive(Debug, Serialize)]
+struct CreatedInvite {
+    #[serde(flatten)]
+    invite: models::Invite,
+    token: String,
+}
+
+#[post("/auth/invites")]
+pub(crate) async fn create_invite(
+    invite_req: web::Json<InviteRequest>,
+    caller: RequirePermission<UsersManage>,
+    pool: web::Data<DbPool>,
+) -> actix_web::Result<HttpResponse> {
+    let caller = caller.into_inner();
+    let InviteRequest { role, valid_days } = invite_req.into_inner();
+    let valid_days = valid_days.unwrap_or(DEFAULT_INVITE_DAYS);
+    if !(1..=MAX_INVITE_DAYS).contains(&valid_days) {
+        return Err(error::ErrorBadRequest(format!("valid_days must be between 1 and {MAX_INVITE_DAYS}")));
+    }
+
+    let token = devices::generate_token();
+    let now = Utc::now().naive_utc();
+    let invite = models::Invite {
+        id: Uuid::new_v4().into(),
+        token_hash: devices::hash_token(&token),
+        role,
+        created_by: caller.email,
+        created_at: now,
+        expires_at: now + TimeDelta::days(valid_days),
+        used_by: None,
+        used_at: None,
+    };
+
+    // Use web::block to avoid blocking async
+    let invite = web::block(move || {
+        let mut conn = pool.get()?;
+
+        actions::insert_invite(&mut conn, &invite)?;
+        audit::record(&mut conn, &invite.created_by, "invite.create", &invite.id, Change::created(&invite))?;
+
+        Ok::<_, actions::DbError>(invite)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Created().json(CreatedInvite { invite, token }))
+}
+
+/// Invites that haven't been used or expired yet
+#[get("/auth/invites")]
+pub(crate) async fn list_invites(_: RequirePermission<UsersManage>, pool: web::Data<DbPool>) -> actix_web::Result<HttpResponse> {
+    // Use web::block to avoid blocking async
+    let invites = web::block(move || {
+        let mut conn = pool.get()?;
+        actions::get_pending_invites(&mut conn, Utc::now().naive_utc())
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().json(invites))
+}
+
+#[delete("/auth/invites/{id}")]
+pub(crate) async fn revoke_invite(path: web::Path<String>, caller: RequirePermission<UsersManage>, pool: web::Data<DbPool>)
+-> actix_web::Result<HttpResponse> {
+    let caller = caller.into_inner();
+    let invite_id = path.into_inner();
+
+    // Use web::block to avoid blocking async
+    let revoked = web::block(move || {
+        let mut conn = pool.get()?;
+
+        // Used invites are kept, so it can be seen who was added with which
+        let Some(invite) = actions::get_pending_invite(&mut conn, &invite_id, Utc::now().naive_utc())? else {
+            return Ok(false);
+        };
+        actions::remove_invite(&mut conn, &invite.id)?;
+        audit::record(&mut conn, &caller.email, "invite.revoke", &invite.id, Change::removed(&invite))?;
+
+        Ok::<_, actions::DbError>(true)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(if revoked {
+        HttpResponse::Ok().finish()
+    } else {
+        HttpResponse::NotFound().finish()
+    })
+}
+
+// --- Groups ---
+
+#[derive(Debug, Serialize)]
+struct GroupMemberResponse {
+    user_id: String,
+    email: String,
+    role: Role,
+}
+
+#[derive(Debug, Serialize)]
+struct GroupResponse {
+    #[serde(flatten)]
+    group: models::Group,
+    members: Vec<GroupMemberResponse>,
+}
+
+/// Every group with its members. Any logged in user may see them, so the admin page can offer the
+/// groups when uploading a slide.
+#[get("/groups")]
+pub(crate) async fn list_groups(_: AuthenticatedUser, pool: web::Data<DbPool>) -> actix_web::Result<HttpResponse> {
+    let (groups, members) = web::block(move || {
+        let mut conn = pool.get()?;
+
+        Ok::<_, actions::DbError>((actions::get_groups(&mut conn)?, actions::get_group_members(&mut conn)?))
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    let mut members_by_group: HashMap<String, Vec<GroupMemberResponse>> = HashMap::new();
+    for (member, email) in members {
+        members_by_group.entry(member.group_id).or_default()
+            .push(GroupMemberResponse { user_id: member.user_id, email, role: member.role });
+    }
+
+    let groups: Vec<_> = groups.into_iter()
+        .map(|group| GroupResponse { members: members_by_group.remove(&group.id).unwrap_or_default(), group })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(groups))
+}
+
+#[derive(Debug, Deserialize)]
+struct GroupRequest {
+    name: String,
+}
+
+#[post("/groups")]
+pub(crate) async fn add_group(group_req: web::Json<GroupRequest>, caller: RequirePermission<UsersManage>, pool: web::Data<DbPool>)
+-> actix_web::Result<HttpResponse> {
+    let caller = caller.into_inner();
+    let name = group_req.into_inner().name.trim().to_string();
+    if name.is_empty() {
+        return Err(error::ErrorBadRequest("A group needs a name"));
+    }
+
+    let group = models::Group {
+        id: Uuid::new_v4().into(),
+        name,
+        created_at: Utc::now().naive_utc(),
+    };
+
+    // Use web::block to avoid blocking async
+    let group = web::block(move || {
+        let mut conn = pool.get()?;
+
+        let group = actions::insert_group(&mut conn, group)?;
+        if let Some(ref group) = group {
+            audit::record(&mut conn, &caller.email, "group.create", &group.id, Change::created(group))?;
+        }
+
+        Ok::<_, actions::DbError>(group)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    match group {
+        Some(group) => Ok(HttpResponse::Created().json(group)),
+        None => Err(error::ErrorConflict("There already is a group with that name")),
+    }
+}
+
+/// Removes a group. Its slides are kept, but no longer belong to a group.
+#[delete("/groups/{id}")]
+pub(crate) async fn remove_group(path: web::Path<String>, caller: RequirePermission<UsersManage>, pool: web::Data<DbPool>)
+-> actix_web::Result<HttpResponse> {
+    let caller = caller.into_inner();
+    let id = path.into_inner();
+
+    let removed = web::block(move || {
+        let mut conn = pool.get()?;
+
+        let Some(group) = actions::get_group(&mut conn, &id)? else {
+            return Ok(false);
+        };
+        actions::remove_group(&mut conn, &id)?;
+        audit::record(&mut conn, &caller.email, "group.remove", &id, Change::removed(&group))?;
+
+        Ok::<_, actions::DbError>(true)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(if removed {
+        HttpResponse::Ok().finish()
+    } else {
+        HttpResponse::NotFound().finish()
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct GroupMemberRequest {
+    role: Role,
+}
+
+/// The roles a user can have in a group. Only slide permissions apply in a group, see `auth::has_slide_permission`.
+const GROUP_ROLES: &[Role] = &[Role::Viewer, Role::SlideEditor, Role::SlidePublisher];
+
+/// Adds a user to a group, or changes their role in it
+#[put("/groups/{id}/members/{user_id}")]
+pub(crate) async fn set_group_member(
+    path: web::Path<(String, String)>,
+    member_req: web::Json<GroupMemberRequest>,
+    caller: RequirePermission<UsersManage>,
+    pool: web::Data<DbPool>,
+) -> actix_web::Result<HttpResponse> {
+    let (group_id, user_id) = path.into_inner();
+    let GroupMemberRequest { role } = member_req.into_inner();
+    if !GROUP_ROLES.contains(&role) {
+        return Err(error::ErrorBadRequest(format!("The role in a group can't be {}", role.as_str())));
+    }
+
+    let member = web::block(move || {
+        let mut conn = pool.get()?;
+
+        if !actions::group_exists(&mut conn, &group_id)? || !actions::user_exists(&mut conn, &user_id)? {
+            return Ok(None);
+        }
+        let before = actions::get_group_member(&mut conn, &group_id, &user_id)?;
+        let member = models::GroupMember { group_id, user_id, role };
+        actions::set_group_member(&mut conn, &member)?;
+        audit::record(&mut conn, &caller.email, "group.member", &format!("{}/{}", member.group_id, member.user_id),
+            Change::changed(&before, &member))?;
+
+        Ok::<_, actions::DbError>(Some(member))
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(match member {
+        Some(member) => HttpResponse::Ok().json(member),
+        None => HttpResponse::NotFound().finish(),
+    })
+}
+
+#[delete("/groups/{id}/members/{user_id}")]
+pub(crate) async fn remove_group_member(path: web::Path<(String, String)>, caller: RequirePermission<UsersManage>, pool: web::Data<DbPool>)
+-> actix_web::Result<HttpResponse> {
+    let caller = caller.into_inner();
+    let (group_id, user_id) = path.into_inner();
+
+    let removed = web::block(move || {
+        let mut conn = pool.get()?;
+
+        let Some(member) = actions::get_group_member(&mut conn, &group_id, &user_id)? else {
+            return Ok(false);
+        };
+        actions::remove_group_member(&mut conn, &group_id, &user_id)?;
+        audit::record(&mut conn, &caller.email, "group.member", &format!("{group_id}/{user_id}"), Change::removed(&member))?;
+
+        Ok::<_, actions::DbError>(true)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(if removed {
+        HttpResponse::Ok().finish()
+    } else {
+        HttpResponse::NotFound().finish()
+    })
 }
 
-
 // --- Settings ---
 #[derive(Debug, Serialize, Deserialize)]
+struct SettingsRequest {
+    pub layout_type: LayoutType,
+    pub color_mode: ColorMode,
+}
+
+/// The settings that apply right now, with any active schedules applied
+#[derive(Debug, Serialize)]
 struct SettingsResponse {
-    pub layout_type: String,
-    pub color_mode: String,
+    pub layout_type: LayoutType,
+    pub color_mode: ColorMode,
+    /// During quiet hours the screen shows a clock instead of its content
+    pub quiet: bool,
+    /// When a schedule or quiet hours next change the settings, so the screen knows when to refetch
+    pub next_change: Option<DateTime<Utc>>,
+    /// Every other setting from the settings registry
+    #[serde(flatten)]
+    pub other: BTreeMap<&'static str, serde_json::Value>,
 }
-impl From<Settings> for SettingsResponse {
-    fn from(settings: Settings) -> Self {
+
+impl SettingsResponse {
+    fn resolve(base: ResolvedSettings, values: &SettingValues, schedules: &[models::SettingsSchedule], quiet_hours: &QuietHours) -> Self {
+        let now = Local::now().naive_local();
+        let resolved = schedule::resolve(base, schedules, now);
+        let next_change = [schedule::next_change(base, schedules, now), quiet_hours.next_change(now)]
+            .into_iter()
+            .flatten()
+            .min();
         SettingsResponse {
-            layout_type: settings.layout_type,
-            color_mode: settings.color_mode,
+            layout_type: resolved.layout_type,
+            color_mode: resolved.color_mode,
+            quiet: quiet_hours.is_quiet(now),
+            next_change: next_change.and_then(local_to_utc),
+            other: values.other(),
         }
     }
 }
 
+/// None if the time doesn't exist, e.g. when the clocks are moved forward
+fn local_to_utc(time: NaiveDateTime) -> Option<DateTime<Utc>> {
+    Local.from_local_datetime(&time).earliest().map(|time| time.with_timezone(&Utc))
+}
+
+type SettingsWithSchedules = (SettingValues, Vec<models::SettingsSchedule>, QuietHours);
+
+/// Reads the default settings and the schedules and quiet hours that apply on top of them
+fn get_settings_with_schedules(pool: &DbPool) -> Result<SettingsWithSchedules, actions::DbError> {
+    let mut conn = pool.get()?;
+
+    let settings = actions::get_setting_values(&mut conn)?;
+    let schedules = actions::get_settings_schedules(&mut conn)?;
+    let quiet_hours = actions::get_quiet_hours(&mut conn)?;
+    Ok((settings, schedules, quiet_hours))
+}
+
 #[get("/screen/settings")]
-pub(crate) async fn get_settings(pool: web::Data<DbPool>) -> actix_web::Result<HttpResponse> {
+pub(crate) async fn get_settings(_: PairedDevice, pool: web::Data<DbPool>) -> actix_web::Result<HttpResponse> {
+    // Use web::block to avoid blocking async
+    let (settings, schedules, quiet_hours) = web::block(move || get_settings_with_schedules(&pool))
+        .await?.map_err(|e| {
+            // Most likely a stored value that doesn't match its setting's type
+            log::error!("Failed to read settings: {e}");
+            error::ErrorInternalServerError("Stored settings are invalid")
+        })?;
+
+    let Settings { layout_type, color_mode } = settings.settings();
+    let base = ResolvedSettings { layout_type, color_mode };
+    let settings_response = SettingsResponse::resolve(base, &settings, &schedules, &quiet_hours);
+
+    Ok(HttpResponse::Ok().json(web::Json(settings_response)))
+}
+
+#[put("/screen/settings")]
+pub(crate) async fn update_settings(
+    settings_req: web::Json<SettingsRequest>,
+    caller: RequirePermission<ScreensManage>,
+    pool: web::Data<DbPool>,
+    broadcaster: web::Data<Broadcaster>,
+) -> actix_web::Result<HttpResponse> {
+    let SettingsRequest { layout_type, color_mode } = settings_req.into_inner();
+
     // Use web::block to avoid blocking async
     let settings = web::block(move || {
         let mut conn = pool.get()?;
 
-        actions::get_settings(&mut conn)
+        let before = actions::get_settings(&mut conn)?;
+        let settings = actions::update_settings(&mut conn, layout_type, color_mode)?;
+        audit::record(&mut conn, &caller.email, "settings.update", "settings", Change::changed(&before, &settings))?;
+
+        Ok::<_, actions::DbError>(settings)
     }).await?.map_err(error::ErrorInternalServerError)?;
 
-    let settings_response: SettingsResponse = settings.into();
+    broadcaster.send(ScreenEvent::Settings);
+    // The stored defaults, not what the schedules make of them
+    Ok(HttpResponse::Ok().json(SettingsRequest { layout_type: settings.layout_type, color_mode: settings.color_mode }))
+}
+
+// --- Settings registry ---
 
-    Ok(HttpResponse::Ok().json(web::Json(settings_response)))
-}
\ No newline at end of file
+/// A setting with its type and current value, so the admin page can render a form for it
+#[derive(Debug, Serialize)]
+struct SettingResponse {
+    #[serde(flatten)]
+    definition: &'static SettingDefinition,
+    value: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct SettingValueRequest {
+    value: serde_json::Value,
+}
+
+#[get("/settings")]
+pub(crate) async fn list_registry_settings(_: RequirePermission<ScreensManage>, pool: web::Data<DbPool>) -> actix_web::Result<HttpResponse> {
+    // Use web::block to avoid blocking async
+    let values = web::block(move || {
+        let mut conn = pool.get()?;
+
+        actions::get_setting_values(&mut conn)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    let settings: Vec<_> = settings_registry::SETTINGS.iter()
+        .map(|definition| SettingResponse { definition, value: values.get(definition.key).clone() })
+        .collect();
+    Ok(HttpResponse::Ok().json(settings))
+}
+
+#[put("/settings/{key}")]
+pub(crate) async fn set_registry_setting(
+    path: web::Path<String>,
+    value_req: web::Json<SettingValueRequest>,
+    caller: RequirePermission<ScreensManage>,
+    pool: web::Data<DbPool>,
+    broadcaster: web::Data<Broadcaster>,
+) -> actix_web::Result<HttpResponse> {
+    let Some(definition) = settings_registry::definition(&path) else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let value = value_req.into_inner().value;
+    definition.validate(&value).map_err(error::ErrorBadRequest)?;
+
+    // Use web::block to avoid blocking async
+    web::block(move || {
+        let mut conn = pool.get()?;
+
+        let before = actions::get_setting_values(&mut conn)?.get(definition.key).clone();
+        actions::set_setting_value(&mut conn, definition.key, &value)?;
+        audit::record(&mut conn, &caller.email, "setting.set", definition.key, Change::changed(&before, &value))
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    broadcaster.send(ScreenEvent::Settings);
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Resets a setting to its default
+#[delete("/settings/{key}")]
+pub(crate) async fn reset_registry_setting(
+    path: web::Path<String>,
+    caller: RequirePermission<ScreensManage>,
+    pool: web::Data<DbPool>,
+    broadcaster: web::Data<Broadcaster>,
+) -> actix_web::Result<HttpResponse> {
+    let Some(definition) = settings_registry::definition(&path) else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    // Use web::block to avoid blocking async
+    web::block(move || {
+        let mut conn = pool.get()?;
+
+        let before = actions::get_setting_values(&mut conn)?.get(definition.key).clone();
+        actions::remove_setting_value(&mut conn, definition.key)?;
+        audit::record(&mut conn, &caller.email, "setting.reset", definition.key, Change::changed(&before, &definition.default_value()))
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    broadcaster.send(ScreenEvent::Settings);
+    Ok(HttpResponse::Ok().finish())
+}
+
+// --- Settings schedules ---
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SettingsScheduleRequest {
+    name: String,
+    /// Left out to keep the setting as it is
+    layout_type: Option<LayoutType>,
+    color_mode: Option<ColorMode>,
+    rule: ScheduleRule,
+}
+
+impl SettingsScheduleRequest {
+    fn validate(&self) -> Result<(), &'static str> {
+        if self.layout_type.is_none() && self.color_mode.is_none() {
+            return Err("A schedule has to change at least one setting");
+        }
+        match &self.rule {
+            ScheduleRule::Weekly(window) if window.weekdays.is_empty() => Err("A weekly schedule needs at least one weekday"),
+            ScheduleRule::Weekly(window) if window.start_time == window.end_time => Err("start_time and end_time can't be the same"),
+            ScheduleRule::DateRange { start, end } if start >= end => Err("start must be before end"),
+            _ => Ok(()),
+        }
+    }
+
+    fn into_schedule(self, id: String, created_at: NaiveDateTime) -> models::SettingsSchedule {
+        let parts = self.rule.into_parts();
+        models::SettingsSchedule {
+            id,
+            name: self.name,
+            layout_type: self.layout_type,
+            color_mode: self.color_mode,
+            weekdays: parts.weekdays,
+            start_time: parts.start_time,
+            end_time: parts.end_time,
+            start_at: parts.start_at,
+            end_at: parts.end_at,
+            created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct SettingsScheduleResponse {
+    id: String,
+    name: String,
+    layout_type: Option<LayoutType>,
+    color_mode: Option<ColorMode>,
+    rule: Option<ScheduleRule>,
+    created_at: DateTime<Utc>,
+}
+
+impl From<models::SettingsSchedule> for SettingsScheduleResponse {
+    fn from(s: models::SettingsSchedule) -> Self {
+        SettingsScheduleResponse {
+            rule: ScheduleRule::from_parts((&s).into()),
+            id: s.id,
+            name: s.name,
+            layout_type: s.layout_type,
+            color_mode: s.color_mode,
+            created_at: s.created_at.and_utc(),
+        }
+    }
+}
+
+#[get("/screen/settings/schedules")]
+pub(crate) async fn list_settings_schedules(_: RequirePermission<ScreensManage>, pool: web::Data<DbPool>) -> actix_web::Result<HttpResponse> {
+    // Use web::block to avoid blocking async
+    let schedules = web::block(move || {
+        let mut conn = pool.get()?;
+
+        actions::get_settings_schedules(&mut conn)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    let schedules: Vec<SettingsScheduleResponse> = schedules.into_iter().map(Into::into).collect();
+    Ok(HttpResponse::Ok().json(schedules))
+}
+
+#[post("/screen/settings/schedules")]
+pub(crate) async fn add_settings_schedule(
+    schedule_req: web::Json<SettingsScheduleRequest>,
+    caller: RequirePermission<ScreensManage>,
+    pool: web::Data<DbPool>,
+    broadcaster: web::Data<Broadcaster>,
+) -> actix_web::Result<HttpResponse> {
+    let schedule_req = schedule_req.into_inner();
+    schedule_req.validate().map_err(error::ErrorBadRequest)?;
+    let schedule = schedule_req.into_schedule(Uuid::new_v4().into(), Utc::now().naive_utc());
+
+    // Use web::block to avoid blocking async
+    let schedule = web::block(move || {
+        let mut conn = pool.get()?;
+
+        actions::insert_settings_schedule(&mut conn, &schedule)?;
+        audit::record(&mut conn, &caller.email, "schedule.create", &schedule.id, Change::created(&schedule))?;
+        Ok::<_, actions::DbError>(schedule)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    broadcaster.send(ScreenEvent::Settings);
+    Ok(HttpResponse::Created().json(SettingsScheduleResponse::from(schedule)))
+}
+
+#[put("/screen/settings/schedules/{id}")]
+pub(crate) async fn update_settings_schedule(
+    path: web::Path<String>,
+    schedule_req: web::Json<SettingsScheduleRequest>,
+    caller: RequirePermission<ScreensManage>,
+    pool: web::Data<DbPool>,
+    broadcaster: web::Data<Broadcaster>,
+) -> actix_web::Result<HttpResponse> {
+    let schedule_req = schedule_req.into_inner();
+    schedule_req.validate().map_err(error::ErrorBadRequest)?;
+    // created_at isn't updated, so it doesn't matter what it is here
+    let schedule = schedule_req.into_schedule(path.into_inner(), Utc::now().naive_utc());
+
+    // Use web::block to avoid blocking async
+    let updated = web::block(move || {
+        let mut conn = pool.get()?;
+
+        let Some(before) = actions::get_settings_schedule(&mut conn, &schedule.id)? else {
+            return Ok(false);
+        };
+        actions::update_settings_schedule(&mut conn, &schedule)?;
+        let after = models::SettingsSchedule { created_at: before.created_at, ..schedule };
+        audit::record(&mut conn, &caller.email, "schedule.update", &after.id, Change::changed(&before, &after))?;
+
+        Ok::<_, actions::DbError>(true)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    if updated {
+        broadcaster.send(ScreenEvent::Settings);
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+#[delete("/screen/settings/schedules/{id}")]
+pub(crate) async fn remove_settings_schedule(
+    path: web::Path<String>,
+    caller: RequirePermission<ScreensManage>,
+    pool: web::Data<DbPool>,
+    broadcaster: web::Data<Broadcaster>,
+) -> actix_web::Result<HttpResponse> {
+    let id = path.into_inner();
+
+    // Use web::block to avoid blocking async
+    let removed = web::block(move || {
+        let mut conn = pool.get()?;
+
+        let Some(schedule) = actions::get_settings_schedule(&mut conn, &id)? else {
+            return Ok(false);
+        };
+        actions::remove_settings_schedule(&mut conn, &id)?;
+        audit::record(&mut conn, &caller.email, "schedule.remove", &id, Change::removed(&schedule))?;
+
+        Ok::<_, actions::DbError>(true)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    if removed {
+        broadcaster.send(ScreenEvent::Settings);
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+// --- Quiet hours ---
+
+#[get("/screen/quiet-hours")]
+pub(crate) async fn get_quiet_hours(_: RequirePermission<ScreensManage>, pool: web::Data<DbPool>) -> actix_web::Result<HttpResponse> {
+    // Use web::block to avoid blocking async
+    let quiet_hours = web::block(move || {
+        let mut conn = pool.get()?;
+
+        actions::get_quiet_hours(&mut conn)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().json(quiet_hours))
+}
+
+fn validate_quiet_hours(quiet_hours: &QuietHours) -> Result<(), &'static str> {
+    for window in &quiet_hours.windows {
+        if window.weekdays.is_empty() {
+            return Err("Every window needs at least one weekday");
+        }
+        if window.start_time == window.end_time {
+            return Err("start_time and end_time can't be the same");
+        }
+    }
+    let mut dates: Vec<_> = quiet_hours.exceptions.iter().map(|exception| exception.date).collect();
+    // Anything else is a typo, and dates near the edge of what can be represented overflow when the next day is worked out
+    if dates.iter().any(|date| !(2000..=9999).contains(&date.year())) {
+        return Err("Exception dates must be between the years 2000 and 9999");
+    }
+    dates.sort();
+    if dates.windows(2).any(|pair| pair[0] == pair[1]) {
+        return Err("There can only be one exception per date");
+    }
+    Ok(())
+}
+
+/// Replaces all quiet hours and exceptions
+#[put("/screen/quiet-hours")]
+pub(crate) async fn set_quiet_hours(
+    quiet_hours: web::Json<QuietHours>,
+    caller: RequirePermission<ScreensManage>,
+    pool: web::Data<DbPool>,
+    broadcaster: web::Data<Broadcaster>,
+) -> actix_web::Result<HttpResponse> {
+    let quiet_hours = quiet_hours.into_inner();
+    validate_quiet_hours(&quiet_hours).map_err(error::ErrorBadRequest)?;
+
+    // Use web::block to avoid blocking async
+    web::block(move || {
+        let mut conn = pool.get()?;
+
+        let before = actions::get_quiet_hours(&mut conn)?;
+        actions::set_quiet_hours(&mut conn, &quiet_hours)?;
+        audit::record(&mut conn, &caller.email, "quiet_hours.set", "quiet_hours", Change::changed(&before, &quiet_hours))
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    // Screens that are quiet right now should stop showing slides, or the other way around
+    broadcaster.send(ScreenEvent::Settings);
+    broadcaster.send(ScreenEvent::Slides);
+    Ok(HttpResponse::Ok().finish())
+}
+
+// --- Screens ---
+
+/// The settings a screen uses, with the defaults from `Settings` filled in and active schedules applied
+#[derive(Debug, Serialize)]
+struct ScreenSettingsResponse {
+    #[serde(flatten)]
+    pub settings: SettingsResponse,
+    pub sl_site_ids: Vec<i32>,
+}
+
+impl ScreenSettingsResponse {
+    fn resolve(
+        (values, schedules, quiet_hours): SettingsWithSchedules,
+        screen: Screen,
+        sl_site_ids: Vec<i32>,
+    ) -> Self {
+        let defaults = values.settings();
+        let base = ResolvedSettings {
+            layout_type: screen.layout_type.unwrap_or(defaults.layout_type),
+            color_mode: screen.color_mode.unwrap_or(defaults.color_mode),
+        };
+        ScreenSettingsResponse {
+            settings: SettingsResponse::resolve(base, &values, &schedules, &quiet_hours),
+            sl_site_ids,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ScreenResponse {
+    id: String,
+    name: String,
+    layout_type: Option<LayoutType>,
+    color_mode: Option<ColorMode>,
+    sl_site_ids: Vec<i32>,
+}
+
+impl ScreenResponse {
+    fn new(screen: Screen, sl_site_ids: Vec<i32>) -> Self {
+        ScreenResponse {
+            id: screen.id,
+            name: screen.name,
+            layout_type: screen.layout_type,
+            color_mode: screen.color_mode,
+            sl_site_ids,
+        }
+    }
+}
+
+/// Used both to add and to update a screen. Leaving `layout_type` or `color_mode` out
+/// makes the screen use the default from `/screen/settings`.
+#[derive(Debug, Serialize, Deserialize)]
+struct ScreenRequest {
+    name: String,
+    layout_type: Option<LayoutType>,
+    color_mode: Option<ColorMode>,
+    #[serde(default)]
+    sl_site_ids: Vec<i32>,
+}
+
+#[get("/screens")]
+pub(crate) async fn list_screens(_: RequirePermission<SlidesRead>, pool: web::Data<DbPool>) -> actix_web::Result<HttpResponse> {
+    // Use web::block to avoid blocking async
+    let screens = web::block(move || {
+        let mut conn = pool.get()?;
+
+        actions::get_all_screens(&mut conn)?
+            .into_iter()
+            .map(|screen| {
+                let sites = actions::get_screen_sl_sites(&mut conn, &screen.id)?;
+                Ok(ScreenResponse::new(screen, sites))
+            })
+            .collect::<Result<Vec<_>, actions::DbError>>()
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().json(screens))
+}
+
+/// Screen names are unique, so a taken name is the caller's mistake
+fn screen_write_error(e: actions::DbError) -> actix_web::Error {
+    if actions::is_unique_violation(&e) {
+        error::ErrorConflict("There already is a screen with that name")
+    } else {
+        error::ErrorInternalServerError(e)
+    }
+}
+
+#[post("/screens")]
+pub(crate) async fn add_screen(screen_req: web::Json<ScreenRequest>, caller: RequirePermission<ScreensManage>, pool: web::Data<DbPool>)
+-> actix_web::Result<HttpResponse> {
+    let caller = caller.into_inner();
+    let ScreenRequest { name, layout_type, color_mode, sl_site_ids } = screen_req.into_inner();
+
+    // Use web::block to avoid blocking async
+    let screen = web::block(move || {
+        let mut conn = pool.get()?;
+
+        // The screen and its sites are written together, so a failure doesn't leave half a screen behind
+        conn.transaction(|conn| {
+            let screen = actions::insert_screen(conn, Screen {
+                id: Uuid::new_v4().into(),
+                name,
+                layout_type,
+                color_mode,
+            })?;
+            actions::set_screen_sl_sites(conn, &screen.id, &sl_site_ids)?;
+            let sites = actions::get_screen_sl_sites(conn, &screen.id)?;
+            let screen = ScreenResponse::new(screen, sites);
+            audit::record(conn, &caller.email, "screen.create", &screen.id, Change::created(&screen))?;
+
+            Ok::<_, actions::DbError>(screen)
+        })
+    }).await?.map_err(screen_write_error)?;
+
+    Ok(HttpResponse::Created().json(screen))
+}
+
+#[put("/screens/{id}")]
+pub(crate) async fn update_screen(
+    path: web::Path<String>,
+    screen_req: web::Json<ScreenRequest>,
+    caller: RequirePermission<ScreensManage>,
+    pool: web::Data<DbPool>,
+    broadcaster: web::Data<Broadcaster>,
+) -> actix_web::Result<HttpResponse> {
+    let ScreenRequest { name, layout_type, color_mode, sl_site_ids } = screen_req.into_inner();
+    let screen = Screen { id: path.into_inner(), name, layout_type, color_mode };
+
+    // Use web::block to avoid blocking async
+    let screen = web::block(move || {
+        let mut conn = pool.get()?;
+
+        conn.transaction(|conn| {
+            let Some(before) = actions::get_screen(conn, &screen.id)? else {
+                return Ok(None);
+            };
+            let before = ScreenResponse::new(before, actions::get_screen_sl_sites(conn, &screen.id)?);
+            actions::update_screen(conn, &screen)?;
+            actions::set_screen_sl_sites(conn, &screen.id, &sl_site_ids)?;
+            let sites = actions::get_screen_sl_sites(conn, &screen.id)?;
+            let screen = ScreenResponse::new(screen, sites);
+            audit::record(conn, &caller.email, "screen.update", &screen.id, Change::changed(&before, &screen))?;
+
+            Ok::<_, actions::DbError>(Some(screen))
+        })
+    }).await?.map_err(screen_write_error)?;
+
+    match screen {
+        Some(screen) => {
+            broadcaster.send(ScreenEvent::Settings);
+            Ok(HttpResponse::Ok().json(screen))
+        },
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+#[delete("/screens/{id}")]
+pub(crate) async fn remove_screen(path: web::Path<String>, caller: RequirePermission<ScreensManage>, pool: web::Data<DbPool>)
+-> actix_web::Result<HttpResponse> {
+    let caller = caller.into_inner();
+    let id = path.into_inner();
+
+    // Use web::block to avoid blocking async
+    let removed = web::block(move || {
+        let mut conn = pool.get()?;
+
+        let Some(screen) = actions::get_screen(&mut conn, &id)? else {
+            return Ok(false);
+        };
+        let screen = ScreenResponse::new(screen, actions::get_screen_sl_sites(&mut conn, &id)?);
+        actions::remove_screen(&mut conn, &id)?;
+        audit::record(&mut conn, &caller.email, "screen.remove", &id, Change::removed(&screen))?;
+
+        Ok::<_, actions::DbError>(true)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    if removed {
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+/// The slides shown on one screen. While an override is active, this is only the override's slide (if any).
+#[get("/screens/{id}/slides")]
+pub(crate) async fn get_screen_slides(
+    path: web::Path<String>,
+    device: AuthenticatedDevice,
+    pool: web::Data<DbPool>,
+    calendars: web::Data<Calendars>,
+) -> actix_web::Result<HttpResponse> {
+    let id = path.into_inner();
+    // A device can only see the screen it's paired to
+    if device.screen_id() != Some(id.as_str()) {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+    let now = Utc::now();
+
+    let slides = web::block(move || {
+        let mut conn = pool.get()?;
+
+        if actions::get_screen(&mut conn, &id)?.is_none() {
+            return Ok(None);
+        }
+        get_current_slides(&mut conn, Some(&id), now.naive_utc()).map(Some)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(match slides {
+        Some((slides, sources)) => HttpResponse::Ok().json(with_event_slides(slides, sources, &calendars, now)),
+        None => HttpResponse::NotFound().finish(),
+    })
+}
+
+#[get("/screens/{id}/settings")]
+pub(crate) async fn get_screen_settings(path: web::Path<String>, device: AuthenticatedDevice, pool: web::Data<DbPool>)
+-> actix_web::Result<HttpResponse> {
+    let id = path.into_inner();
+    // A device can only see the screen it's paired to
+    if device.screen_id() != Some(id.as_str()) {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    // Use web::block to avoid blocking async
+    let settings = web::block(move || {
+        let mut conn = pool.get()?;
+
+        let Some(screen) = actions::get_screen(&mut conn, &id)? else {
+            return Ok(None);
+        };
+        let sites = actions::get_screen_sl_sites(&mut conn, &id)?;
+        drop(conn);
+        let settings = get_settings_with_schedules(&pool)?;
+
+        Ok::<_, actions::DbError>(Some(ScreenSettingsResponse::resolve(settings, screen, sites)))
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(match settings {
+        Some(settings) => HttpResponse::Ok().json(settings),
+        None => HttpResponse::NotFound().finish(),
+    })
+}
+
+// --- SL departures ---
+
+#[derive(Debug, Serialize)]
+struct DeparturesResponse {
+    departures: Vec<Departure>,
+    /// Sites whose departures couldn't be fetched from SL
+    failed_site_ids: Vec<i32>,
+}
+
+/// Fetches the departures of every site at once, keeping only the tracked lines
+async fn fetch_departures(sl: &SlClient, sites: Vec<i32>, tracked_lines: HashMap<i32, Vec<models::SlTrackedLine>>) -> DeparturesResponse {
+    let results = futures_util::future::join_all(sites.iter().map(|&site| sl.departures(site))).await;
+
+    let mut response = DeparturesResponse { departures: Vec::new(), failed_site_ids: Vec::new() };
+    for (site, result) in sites.into_iter().zip(results) {
+        match result {
+            Ok(departures) => {
+                let lines = tracked_lines.get(&site).map(Vec::as_slice).unwrap_or_default();
+                response.departures.extend(departures.into_iter().filter(|departure| departure.is_tracked(lines)));
+            },
+            Err(e) => {
+                log::warn!("{e}");
+                response.failed_site_ids.push(site);
+            },
+        }
+    }
+    response.departures.sort_by_key(|departure| departure.expected_time);
+    response
+}
+
+/// Departures from the tracked sites, for screens that aren't configured in the backend
+#[get("/screen/departures")]
+pub(crate) async fn get_departures(_: PairedDevice, pool: web::Data<DbPool>, sl: web::Data<SlClient>) -> actix_web::Result<HttpResponse> {
+    // Use web::block to avoid blocking async
+    let (sites, lines) = web::block(move || {
+        let mut conn = pool.get()?;
+
+        let sites = actions::get_sl_tracked_sites(&mut conn)?.into_iter().map(|site| site.site_id).collect();
+        let lines = actions::get_sl_tracked_lines(&mut conn)?;
+        Ok::<_, actions::DbError>((sites, lines))
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().json(fetch_departures(&sl, sites, lines).await))
+}
+
+/// Departures from a screen's own sites, or the tracked sites if it has none
+#[get("/screens/{id}/departures")]
+pub(crate) async fn get_screen_departures(
+    path: web::Path<String>,
+    device: AuthenticatedDevice,
+    pool: web::Data<DbPool>,
+    sl: web::Data<SlClient>,
+) -> actix_web::Result<HttpResponse> {
+    let id = path.into_inner();
+    // A device can only see the screen it's paired to
+    if device.screen_id() != Some(id.as_str()) {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    // Use web::block to avoid blocking async
+    let sites_and_lines = web::block(move || {
+        let mut conn = pool.get()?;
+
+        if actions::get_screen(&mut conn, &id)?.is_none() {
+            return Ok(None);
+        }
+        let mut sites = actions::get_screen_sl_sites(&mut conn, &id)?;
+        if sites.is_empty() {
+            sites = actions::get_sl_tracked_sites(&mut conn)?.into_iter().map(|site| site.site_id).collect();
+        }
+        let lines = actions::get_sl_tracked_lines(&mut conn)?;
+        Ok::<_, actions::DbError>(Some((sites, lines)))
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    match sites_and_lines {
+        Some((sites, lines)) => Ok(HttpResponse::Ok().json(fetch_departures(&sl, sites, lines).await)),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct DeviationsResponse<T> {
+    deviations: Vec<T>,
+    /// Sites whose deviations couldn't be fetched from SL
+    failed_site_ids: Vec<i32>,
+}
+
+/// Fetches the current deviations of every site at once, keeping only those about the tracked lines.
+/// A deviation that affects several of the sites is only included once. The most severe come first.
+async fn fetch_deviations(sl: &SlClient, sites: Vec<i32>, tracked_lines: HashMap<i32, Vec<models::SlTrackedLine>>)
+-> DeviationsResponse<Deviation> {
+    let results = futures_util::future::join_all(sites.iter().map(|&site| sl.deviations(site))).await;
+    let now = Utc::now();
+
+    let mut response = DeviationsResponse { deviations: Vec::<Deviation>::new(), failed_site_ids: Vec::new() };
+    for (site, result) in sites.into_iter().zip(results) {
+        match result {
+            Ok(deviations) => {
+                let lines = tracked_lines.get(&site).map(Vec::as_slice).unwrap_or_default();
+                for deviation in deviations {
+                    if deviation.is_valid_at(now) && deviation.is_tracked(lines)
+                        && !response.deviations.iter().any(|seen| seen.id == deviation.id) {
+                        response.deviations.push(deviation);
+                    }
+                }
+            },
+            Err(e) => {
+                log::warn!("{e}");
+                response.failed_site_ids.push(site);
+            },
+        }
+    }
+    response.deviations.sort_by_key(|deviation| (std::cmp::Reverse(deviation.importance_level), deviation.valid_from));
+    response
+}
+
+/// Removes the muted deviations before they're sent to a screen
+fn without_muted(mut response: DeviationsResponse<Deviation>, muted: &[i64]) -> DeviationsResponse<Deviation> {
+    response.deviations.retain(|deviation| !muted.contains(&deviation.id));
+    response
+}
+
+/// Deviations at the tracked sites, for screens that aren't configured in the backend
+#[get("/screen/deviations")]
+pub(crate) async fn get_deviations(_: PairedDevice, pool: web::Data<DbPool>, sl: web::Data<SlClient>) -> actix_web::Result<HttpResponse> {
+    // Use web::block to avoid blocking async
+    let (sites, lines, muted) = web::block(move || {
+        let mut conn = pool.get()?;
+
+        let sites = actions::get_sl_tracked_sites(&mut conn)?.into_iter().map(|site| site.site_id).collect();
+        let lines = actions::get_sl_tracked_lines(&mut conn)?;
+        let muted = actions::get_sl_muted_deviations(&mut conn)?;
+        Ok::<_, actions::DbError>((sites, lines, muted))
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().json(without_muted(fetch_deviations(&sl, sites, lines).await, &muted)))
+}
+
+/// Deviations at a screen's own sites, or the tracked sites if it has none
+#[get("/screens/{id}/deviations")]
+pub(crate) async fn get_screen_deviations(
+    path: web::Path<String>,
+    device: AuthenticatedDevice,
+    pool: web::Data<DbPool>,
+    sl: web::Data<SlClient>,
+) -> actix_web::Result<HttpResponse> {
+    let id = path.into_inner();
+    // A device can only see the screen it's paired to
+    if device.screen_id() != Some(id.as_str()) {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    // Use web::block to avoid blocking async
+    let sites_and_lines = web::block(move || {
+        let mut conn = pool.get()?;
+
+        if actions::get_screen(&mut conn, &id)?.is_none() {
+            return Ok(None);
+        }
+        let mut sites = actions::get_screen_sl_sites(&mut conn, &id)?;
+        if sites.is_empty() {
+            sites = actions::get_sl_tracked_sites(&mut conn)?.into_iter().map(|site| site.site_id).collect();
+        }
+        let lines = actions::get_sl_tracked_lines(&mut conn)?;
+        let muted = actions::get_sl_muted_deviations(&mut conn)?;
+        Ok::<_, actions::DbError>(Some((sites, lines, muted)))
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    match sites_and_lines {
+        Some((sites, lines, muted)) => Ok(HttpResponse::Ok().json(without_muted(fetch_deviations(&sl, sites, lines).await, &muted))),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct AdminDeviation {
+    #[serde(flatten)]
+    deviation: Deviation,
+    muted: bool,
+}
+
+/// Current deviations at the tracked sites, including the muted ones
+#[get("/sl/deviations")]
+pub(crate) async fn list_sl_deviations(
+    _: RequirePermission<ScreensOperate>,
+    pool: web::Data<DbPool>,
+    sl: web::Data<SlClient>,
+) -> actix_web::Result<HttpResponse> {
+    // Use web::block to avoid blocking async
+    let (sites, lines, muted) = web::block(move || {
+        let mut conn = pool.get()?;
+
+        let sites = actions::get_sl_tracked_sites(&mut conn)?.into_iter().map(|site| site.site_id).collect();
+        let lines = actions::get_sl_tracked_lines(&mut conn)?;
+        let muted = actions::get_sl_muted_deviations(&mut conn)?;
+        Ok::<_, actions::DbError>((sites, lines, muted))
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    let DeviationsResponse { deviations, failed_site_ids } = fetch_deviations(&sl, sites, lines).await;
+    Ok(HttpResponse::Ok().json(DeviationsResponse {
+        deviations: deviations.into_iter()
+            .map(|deviation| AdminDeviation { muted: muted.contains(&deviation.id), deviation })
+            .collect(),
+        failed_site_ids,
+    }))
+}
+
+/// Stops showing a deviation on the screens. Any id can be muted, so a deviation can be muted before it's published.
+#[post("/sl/deviations/{id}/mute")]
+pub(crate) async fn mute_sl_deviation(path: web::Path<i64>, caller: RequirePermission<ScreensOperate>, pool: web::Data<DbPool>)
+-> actix_web::Result<HttpResponse> {
+    let email = caller.into_inner().email;
+    let muted = models::SlMutedDeviation {
+        deviation_case_id: path.into_inner(),
+        muted_by: email,
+        muted_at: Utc::now().naive_utc(),
+    };
+
+    // Use web::block to avoid blocking async
+    web::block(move || {
+        let mut conn = pool.get()?;
+
+        actions::mute_sl_deviation(&mut conn, &muted)?;
+        audit::record(&mut conn, &muted.muted_by, "sl_deviation.mute", &muted.deviation_case_id.to_string(), Change::created(&muted))
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[delete("/sl/deviations/{id}/mute")]
+pub(crate) async fn unmute_sl_deviation(path: web::Path<i64>, caller: RequirePermission<ScreensOperate>, pool: web::Data<DbPool>)
+-> actix_web::Result<HttpResponse> {
+    let caller = caller.into_inner();
+    let case_id = path.into_inner();
+
+    // Use web::block to avoid blocking async
+    let removed = web::block(move || {
+        let mut conn = pool.get()?;
+
+        if !actions::unmute_sl_deviation(&mut conn, case_id)? {
+            return Ok(false);
+        }
+        let before = serde_json::json!({ "deviation_case_id": case_id });
+        audit::record(&mut conn, &caller.email, "sl_deviation.unmute", &case_id.to_string(), Change::removed(&before))?;
+
+        Ok::<_, actions::DbError>(true)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    if removed {
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SlSiteRequest {
+    name: String,
+    /// Empty shows every departure from the site
+    #[serde(default)]
+    lines: Vec<models::SlTrackedLine>,
+}
+
+#[derive(Debug, Serialize)]
+struct SlSiteResponse {
+    site_id: i32,
+    name: String,
+    lines: Vec<models::SlTrackedLine>,
+}
+
+/// One tracked site with its lines, None if it isn't tracked
+fn get_sl_site(conn: &mut diesel::SqliteConnection, site_id: i32) -> Result<Option<SlSiteResponse>, actions::DbError> {
+    let Some(site) = actions::get_sl_tracked_sites(conn)?.into_iter().find(|site| site.site_id == site_id) else {
+        return Ok(None);
+    };
+    let lines = actions::get_sl_tracked_lines(conn)?.remove(&site_id).unwrap_or_default();
+    Ok(Some(SlSiteResponse { site_id, name: site.name, lines }))
+}
+
+#[get("/sl/sites")]
+pub(crate) async fn list_sl_sites(_: RequirePermission<ScreensManage>, pool: web::Data<DbPool>) -> actix_web::Result<HttpResponse> {
+    // Use web::block to avoid blocking async
+    let sites = web::block(move || {
+        let mut conn = pool.get()?;
+
+        let mut lines = actions::get_sl_tracked_lines(&mut conn)?;
+        let sites: Vec<_> = actions::get_sl_tracked_sites(&mut conn)?.into_iter()
+            .map(|site| SlSiteResponse {
+                lines: lines.remove(&site.site_id).unwrap_or_default(),
+                site_id: site.site_id,
+                name: site.name,
+            })
+            .collect();
+        Ok::<_, actions::DbError>(sites)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().json(sites))
+}
+
+/// Adds a tracked site, or replaces its name and lines
+#[put("/sl/sites/{site_id}")]
+pub(crate) async fn set_sl_site(
+    path: web::Path<i32>,
+    site_req: web::Json<SlSiteRequest>,
+    caller: RequirePermission<ScreensManage>,
+    pool: web::Data<DbPool>,
+) -> actix_web::Result<HttpResponse> {
+    let SlSiteRequest { name, lines } = site_req.into_inner();
+    if let Some(line) = lines.iter().find(|line| !sl::TRANSPORT_MODES.contains(&line.transport_mode.as_str())) {
+        return Err(error::ErrorBadRequest(format!("Unknown transport mode {}", line.transport_mode)));
+    }
+    let site = models::SlTrackedSite { site_id: path.into_inner(), name };
+
+    // Use web::block to avoid blocking async
+    web::block(move || {
+        let mut conn = pool.get()?;
+
+        let before = get_sl_site(&mut conn, site.site_id)?;
+        actions::set_sl_tracked_site(&mut conn, &site, &lines)?;
+        let after = SlSiteResponse { site_id: site.site_id, name: site.name, lines };
+        audit::record(&mut conn, &caller.email, "sl_site.set", &after.site_id.to_string(), Change::changed(&before, &after))
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[delete("/sl/sites/{site_id}")]
+pub(crate) async fn remove_sl_site(path: web::Path<i32>, caller: RequirePermission<ScreensManage>, pool: web::Data<DbPool>)
+-> actix_web::Result<HttpResponse> {
+    let caller = caller.into_inner();
+    let site_id = path.into_inner();
+
+    // Use web::block to avoid blocking async
+    let removed = web::block(move || {
+        let mut conn = pool.get()?;
+
+        let Some(site) = get_sl_site(&mut conn, site_id)? else {
+            return Ok(false);
+        };
+        actions::remove_sl_tracked_site(&mut conn, site_id)?;
+        audit::record(&mut conn, &caller.email, "sl_site.remove", &site_id.to_string(), Change::removed(&site))?;
+
+        Ok::<_, actions::DbError>(true)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    if removed {
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+// --- Calendar ---
+
+#[derive(Debug, Deserialize)]
+struct EventsQuery {
+    /// How many days ahead to include
+    days: Option<i64>,
+}
+
+/// Upcoming events from every calendar feed, including those that are going on right now
+#[get("/screen/events")]
+pub(crate) async fn get_events(_: PairedDevice, query: web::Query<EventsQuery>, calendars: web::Data<Calendars>) -> actix_web::Result<HttpResponse> {
+    let days = query.days.unwrap_or(14);
+    if !(1..=calendar::LOOKAHEAD_DAYS).contains(&days) {
+        return Err(error::ErrorBadRequest(format!("days must be between 1 and {}", calendar::LOOKAHEAD_DAYS)));
+    }
+
+    let now = Utc::now();
+    Ok(HttpResponse::Ok().json(calendars.upcoming(now, now + TimeDelta::days(days))))
+}
+
+// Posters are usually put up a week or two before an event
+const MAX_SLIDE_DAYS_BEFORE: i32 = 30;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CalendarFeedRequest {
+    name: String,
+    url: String,
+    /// Generate slides for the feed's events, shown from this many days before each event
+    #[serde(default)]
+    slide_days_before: Option<i32>,
+}
+
+impl CalendarFeedRequest {
+    /// Checks the request and turns webcal:// URLs, which calendar apps link to feeds with, into https://
+    fn validate(self) -> actix_web::Result<Self> {
+        let url = match self.url.trim().strip_prefix("webcal://") {
+            Some(rest) => format!("https://{rest}"),
+            None => self.url.trim().to_string(),
+        };
+        if !url.starts_with("https://") && !url.starts_with("http://") {
+            return Err(error::ErrorBadRequest("The URL must start with https://, http:// or webcal://"));
+        }
+        if self.slide_days_before.is_some_and(|days| !(0..=MAX_SLIDE_DAYS_BEFORE).contains(&days)) {
+            return Err(error::ErrorBadRequest(format!("slide_days_before must be between 0 and {MAX_SLIDE_DAYS_BEFORE}")));
+        }
+        Ok(CalendarFeedRequest { url, ..self })
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct CalendarFeedResponse {
+    #[serde(flatten)]
+    feed: models::CalendarFeed,
+    /// When the feed was last fetched successfully, if it has been since the backend started
+    fetched_at: Option<DateTime<Utc>>,
+    /// Why the last fetch failed, if it did
+    error: Option<String>,
+    event_count: usize,
+}
+
+impl CalendarFeedResponse {
+    fn new(feed: models::CalendarFeed, calendars: &Calendars) -> Self {
+        let status = calendars.status(&feed.id);
+        CalendarFeedResponse { feed, fetched_at: status.fetched_at, error: status.error, event_count: status.events.len() }
+    }
+}
+
+#[get("/calendar/feeds")]
+pub(crate) async fn list_calendar_feeds(_: RequirePermission<ScreensManage>, pool: web::Data<DbPool>, calendars: web::Data<Calendars>)
+-> actix_web::Result<HttpResponse> {
+    // Use web::block to avoid blocking async
+    let feeds = web::block(move || {
+        let mut conn = pool.get()?;
+
+        actions::get_calendar_feeds(&mut conn)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    let feeds: Vec<_> = feeds.into_iter().map(|feed| CalendarFeedResponse::new(feed, &calendars)).collect();
+    Ok(HttpResponse::Ok().json(feeds))
+}
+
+/// Adds a feed and fetches it right away, so the response says whether it worked
+#[post("/calendar/feeds")]
+pub(crate) async fn add_calendar_feed(
+    feed_req: web::Json<CalendarFeedRequest>,
+    caller: RequirePermission<ScreensManage>,
+    pool: web::Data<DbPool>,
+    calendars: web::Data<Calendars>,
+    broadcaster: web::Data<Broadcaster>,
+) -> actix_web::Result<HttpResponse> {
+    let CalendarFeedRequest { name, url, slide_days_before } = feed_req.into_inner().validate()?;
+    let feed = models::CalendarFeed {
+        id: Uuid::new_v4().into(),
+        name,
+        url,
+        created_at: Utc::now().naive_utc(),
+        slide_days_before,
+    };
+
+    // Use web::block to avoid blocking async
+    let feeds_pool = pool.clone();
+    let feed = web::block(move || {
+        let mut conn = pool.get()?;
+
+        let feed = actions::insert_calendar_feed(&mut conn, feed)?;
+        audit::record(&mut conn, &caller.email, "calendar_feed.create", &feed.id, Change::created(&feed))?;
+
+        Ok::<_, actions::DbError>(feed)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    calendar::refresh_from_db(&calendars, feeds_pool.get_ref().clone()).await.map_err(error::ErrorInternalServerError)?;
+    if feed.slide_days_before.is_some() {
+        broadcaster.send(ScreenEvent::Slides);
+    }
+
+    Ok(HttpResponse::Created().json(CalendarFeedResponse::new(feed, &calendars)))
+}
+
+/// Replaces the name, URL and slide option of a feed, and fetches it again
+#[put("/calendar/feeds/{id}")]
+pub(crate) async fn update_calendar_feed(
+    path: web::Path<String>,
+    feed_req: web::Json<CalendarFeedRequest>,
+    caller: RequirePermission<ScreensManage>,
+    pool: web::Data<DbPool>,
+    calendars: web::Data<Calendars>,
+    broadcaster: web::Data<Broadcaster>,
+) -> actix_web::Result<HttpResponse> {
+    let CalendarFeedRequest { name, url, slide_days_before } = feed_req.into_inner().validate()?;
+    let id = path.into_inner();
+
+    // Use web::block to avoid blocking async
+    let feeds_pool = pool.clone();
+    let feed = web::block(move || {
+        let mut conn = pool.get()?;
+
+        let Some(before) = actions::get_calendar_feeds(&mut conn)?.into_iter().find(|feed| feed.id == id) else {
+            return Ok(None);
+        };
+        let feed = models::CalendarFeed { name, url, slide_days_before, ..before.clone() };
+        actions::update_calendar_feed(&mut conn, &feed)?;
+        audit::record(&mut conn, &caller.email, "calendar_feed.update", &feed.id, Change::changed(&before, &feed))?;
+        Ok::<_, actions::DbError>(Some(feed))
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    let Some(feed) = feed else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    calendar::refresh_from_db(&calendars, feeds_pool.get_ref().clone()).await.map_err(error::ErrorInternalServerError)?;
+    broadcaster.send(ScreenEvent::Slides);
+
+    Ok(HttpResponse::Ok().json(CalendarFeedResponse::new(feed, &calendars)))
+}
+
+#[delete("/calendar/feeds/{id}")]
+pub(crate) async fn remove_calendar_feed(
+    path: web::Path<String>,
+    caller: RequirePermission<ScreensManage>,
+    pool: web::Data<DbPool>,
+    calendars: web::Data<Calendars>,
+    broadcaster: web::Data<Broadcaster>,
+) -> actix_web::Result<HttpResponse> {
+    let id = path.into_inner();
+
+    // Use web::block to avoid blocking async
+    let feeds_pool = pool.clone();
+    let removed = web::block(move || {
+        let mut conn = pool.get()?;
+
+        let Some(feed) = actions::get_calendar_feeds(&mut conn)?.into_iter().find(|feed| feed.id == id) else {
+            return Ok(false);
+        };
+        actions::remove_calendar_feed(&mut conn, &id)?;
+        audit::record(&mut conn, &caller.email, "calendar_feed.remove", &id, Change::removed(&feed))?;
+
+        Ok::<_, actions::DbError>(true)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    if removed {
+        // Forgets the events (and event slides) of the removed feed
+        calendar::refresh_from_db(&calendars, feeds_pool.get_ref().clone()).await.map_err(error::ErrorInternalServerError)?;
+        broadcaster.send(ScreenEvent::Slides);
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct EventSlideResponse {
+    #[serde(flatten)]
+    slide: EventSlide,
+    /// The id the slide has on the screens, and in impressions
+    slide_id: String,
+    suppressed: bool,
+    poster_filetype: Option<String>,
+}
+
+/// Finds an upcoming event slide and its options by key. None if there's no such event in a feed with slides enabled.
+async fn find_event_slide(pool: web::Data<DbPool>, calendars: &Calendars, key: String)
+-> actix_web::Result<Option<(EventSlide, Option<models::EventSlideOptions>)>> {
+    // Use web::block to avoid blocking async
+    let (feeds, mut options) = web::block(move || {
+        let mut conn = pool.get()?;
+
+        Ok::<_, actions::DbError>((actions::get_calendar_feeds(&mut conn)?, actions::get_event_slide_options(&mut conn)?))
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(calendars.event_slides(&feeds, Utc::now()).into_iter()
+        .find(|slide| slide.key == key)
+        .map(|slide| (slide, options.remove(&key))))
+}
+
+/// Upcoming events in the feeds with slides enabled, including those whose slides aren't shown yet
+#[get("/calendar/event-slides")]
+pub(crate) async fn list_event_slides(_: RequirePermission<SlidesRead>, pool: web::Data<DbPool>, calendars: web::Data<Calendars>)
+-> actix_web::Result<HttpResponse> {
+    // Use web::block to avoid blocking async
+    let (feeds, mut options) = web::block(move || {
+        let mut conn = pool.get()?;
+
+        Ok::<_, actions::DbError>((actions::get_calendar_feeds(&mut conn)?, actions::get_event_slide_options(&mut conn)?))
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    let slides: Vec<_> = calendars.event_slides(&feeds, Utc::now()).into_iter()
+        .map(|slide| {
+            let options = options.remove(&slide.key);
+            EventSlideResponse {
+                slide_id: format!("event-{}", slide.key),
+                suppressed: options.as_ref().is_some_and(|options| options.suppressed),
+                poster_filetype: options.and_then(|options| options.poster_filetype),
+                slide,
+            }
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(slides))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct EventSlideRequest {
+    suppressed: bool,
+}
+
+/// Stops (or starts again) generating a slide for an event. Applies to every occurrence of a recurring event.
+#[put("/calendar/event-slides/{key}")]
+pub(crate) async fn update_event_slide(
+    path: web::Path<String>,
+    slide_req: web::Json<EventSlideRequest>,
+    caller: RequirePermission<SlidesWrite>,
+    pool: web::Data<DbPool>,
+    calendars: web::Data<Calendars>,
+    broadcaster: web::Data<Broadcaster>,
+) -> actix_web::Result<HttpResponse> {
+    let email = caller.into_inner().email;
+    let Some((slide, before)) = find_event_slide(pool.clone(), &calendars, path.into_inner()).await? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let options = models::EventSlideOptions {
+        event_key: slide.key,
+        feed_id: slide.event.feed_id,
+        event_uid: slide.event.uid,
+        suppressed: slide_req.suppressed,
+        poster_filetype: before.as_ref().and_then(|before| before.poster_filetype.clone()),
+        updated_by: email,
+        updated_at: Utc::now().naive_utc(),
+    };
+
+    // Use web::block to avoid blocking async
+    web::block(move || {
+        let mut conn = pool.get()?;
+
+        actions::set_event_slide_options(&mut conn, &options)?;
+        audit::record(&mut conn, &options.updated_by, "event_slide.update", &options.event_key, Change::changed(&before, &options))
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    broadcaster.send(ScreenEvent::Slides);
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Shows an uploaded poster instead of the generated text. Replaces any earlier poster.
+#[put("/calendar/event-slides/{key}/poster")]
+pub(crate) async fn upload_event_poster(
+    path: web::Path<String>,
+    form: MultipartForm<EventPosterForm>,
+    caller: RequirePermission<SlidesWrite>,
+    pool: web::Data<DbPool>,
+    calendars: web::Data<Calendars>,
+    broadcaster: web::Data<Broadcaster>,
+) -> actix_web::Result<HttpResponse> {
+    let email = caller.into_inner().email;
+    let image_file = form.into_inner().image_file;
+    let Some(filetype) = fs_helpers::image_filetype(&image_file).map(str::to_string) else {
+        return Err(error::ErrorBadRequest("The poster must be a PNG, JPEG, WebP or GIF"));
+    };
+    let Some((slide, before)) = find_event_slide(pool.clone(), &calendars, path.into_inner()).await? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let filename = format!("event-{}", slide.key);
+    let image_path = fs_helpers::save_image_file(image_file, &filename, &filetype).await?;
+    // The old poster is only removed once the new one is in the database. With the same filetype it was just overwritten.
+    let old_filetype = before.as_ref().and_then(|before| before.poster_filetype.clone());
+    let overwrote_old = old_filetype.as_ref() == Some(&filetype);
+    let old_filetype = old_filetype.filter(|_| !overwrote_old);
+
+    let options = models::EventSlideOptions {
+        event_key: slide.key,
+        feed_id: slide.event.feed_id,
+        event_uid: slide.event.uid,
+        suppressed: before.as_ref().is_some_and(|before| before.suppressed),
+        poster_filetype: Some(filetype),
+        updated_by: email,
+        updated_at: Utc::now().naive_utc(),
+    };
+
+    // Use web::block to avoid blocking async
+    let db_result = web::block(move || {
+        let mut conn = pool.get()?;
+
+        actions::set_event_slide_options(&mut conn, &options)?;
+        audit::record(&mut conn, &options.updated_by, "event_slide.poster", &options.event_key, Change::changed(&before, &options))
+    }).await?;
+
+    match db_result {
+        Ok(()) => if let Some(old_filetype) = old_filetype {
+            fs_helpers::remove_file(fs_helpers::image_path(&filename, &old_filetype)).await?;
+        },
+        Err(e) => {
+            // If the database failed, remove the new file unless it took the place of the old poster
+            if !overwrote_old {
+                fs_helpers::remove_file(image_path).await?;
+            }
+            return Err(ErrorInternalServerError(e));
+        },
+    }
+
+    broadcaster.send(ScreenEvent::Slides);
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Goes back to the generated text
+#[delete("/calendar/event-slides/{key}/poster")]
+pub(crate) async fn remove_event_poster(
+    path: web::Path<String>,
+    caller: RequirePermission<SlidesWrite>,
+    pool: web::Data<DbPool>,
+    calendars: web::Data<Calendars>,
+    broadcaster: web::Data<Broadcaster>,
+) -> actix_web::Result<HttpResponse> {
+    let email = caller.into_inner().email;
+    let Some((slide, Some(before))) = find_event_slide(pool.clone(), &calendars, path.into_inner()).await? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let Some(filetype) = before.poster_filetype.clone() else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let options = models::EventSlideOptions { poster_filetype: None, updated_by: email, updated_at: Utc::now().naive_utc(), ..before.clone() };
+
+    // Use web::block to avoid blocking async
+    web::block(move || {
+        let mut conn = pool.get()?;
+
+        actions::set_event_slide_options(&mut conn, &options)?;
+        audit::record(&mut conn, &options.updated_by, "event_slide.poster", &options.event_key, Change::changed(&before, &options))
+    }).await?.map_err(error::ErrorInternalServerError)?;
+    fs_helpers::remove_file(fs_helpers::image_path(&format!("event-{}", slide.key), &filetype)).await?;
+
+    broadcaster.send(ScreenEvent::Slides);
+    Ok(HttpResponse::Ok().finish())
+}
+
+// --- Devices ---
+
+// How long a kiosk's pairing code can be approved before it has to ask for a new one
+const PAIRING_CODE_LIFETIME_MINUTES: i64 = 15;
+// Anyone can ask to be paired, so there's a limit on how many can wait at once. Expired requests don't count.
+const MAX_PENDING_DEVICES: i64 = 20;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PairingResponse {
+    token: String,
+    pairing_code: String,
+    pairing_expires_at: DateTime<Utc>,
+}
+
+/// Called by a kiosk that isn't paired yet. The token is only returned here, so the kiosk has to keep it.
+#[post("/devices/pair")]
+pub(crate) async fn request_pairing(pool: web::Data<DbPool>) -> actix_web::Result<HttpResponse> {
+    let token = devices::generate_token();
+    let now = Utc::now().naive_utc();
+    let device = models::Device {
+        id: Uuid::new_v4().into(),
+        token_hash: devices::hash_token(&token),
+        pairing_code: Some(devices::generate_pairing_code()),
+        pairing_expires_at: Some(now + TimeDelta::minutes(PAIRING_CODE_LIFETIME_MINUTES)),
+        name: None,
+        screen_id: None,
+        approved_by: None,
+        created_at: now,
+        approved_at: None,
+        revoked_at: None,
+    };
+
+    // Use web::block to avoid blocking async
+    let device = web::block(move || {
+        let mut conn = pool.get()?;
+
+        actions::insert_device(&mut conn, device, now, MAX_PENDING_DEVICES)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    let Some(device) = device else {
+        log::warn!("Too many devices waiting to be paired, rejected a pairing request");
+        return Err(error::ErrorTooManyRequests("Too many devices are waiting to be paired, try again later"));
+    };
+
+    log::info!("Device {} requested pairing", device.id);
+
+    Ok(HttpResponse::Created().json(PairingResponse {
+        token,
+        pairing_code: device.pairing_code.unwrap_or_default(),
+        pairing_expires_at: device.pairing_expires_at.unwrap_or(now).and_utc(),
+    }))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+enum DeviceStatusResponse {
+    Pending { pairing_code: String, pairing_expires_at: DateTime<Utc> },
+    Paired { screen_id: String, name: String },
+}
+
+/// Polled by a kiosk to find out if it has been paired yet
+#[get("/device/status")]
+pub(crate) async fn device_status(device: AuthenticatedDevice) -> HttpResponse {
+    let AuthenticatedDevice(device) = device;
+
+    match device.screen_id {
+        Some(screen_id) => HttpResponse::Ok().json(DeviceStatusResponse::Paired {
+            screen_id,
+            name: device.name.unwrap_or_default(),
+        }),
+        None => HttpResponse::Ok().json(DeviceStatusResponse::Pending {
+            pairing_code: device.pairing_code.unwrap_or_default(),
+            pairing_expires_at: device.pairing_expires_at.unwrap_or_default().and_utc(),
+        }),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ApproveDeviceRequest {
+    pairing_code: String,
+    screen_id: String,
+    name: String,
+}
+
+#[post("/devices/approve")]
+pub(crate) async fn approve_device(approve_req: web::Json<ApproveDeviceRequest>, caller: RequirePermission<ScreensManage>, pool: web::Data<DbPool>)
+-> actix_web::Result<HttpResponse> {
+    let email = caller.into_inner().email;
+
+    let ApproveDeviceRequest { pairing_code, screen_id, name } = approve_req.into_inner();
+    let pairing_code = devices::normalize_pairing_code(&pairing_code);
+
+    // Use web::block to avoid blocking async
+    let device = web::block(move || {
+        let mut conn = pool.get()?;
+
+        if actions::get_screen(&mut conn, &screen_id)?.is_none() {
+            return Ok(Err("Unknown screen id"));
+        }
+        let Some(device) = actions::approve_device(&mut conn, &pairing_code, &screen_id, &name, &email, Utc::now().naive_utc())? else {
+            return Ok(Err("Unknown or expired pairing code"));
+        };
+        audit::record(&mut conn, &email, "device.approve", &device.id, Change::created(&device))?;
+
+        Ok::<_, actions::DbError>(Ok(device))
+    }).await?.map_err(error::ErrorInternalServerError)?
+    .map_err(error::ErrorBadRequest)?;
+
+    log::info!("Device {} paired to screen {:?} by {:?}", device.id, device.screen_id, device.approved_by);
+
+    Ok(HttpResponse::Ok().json(device))
+}
+
+#[get("/devices")]
+pub(crate) async fn list_devices(_: RequirePermission<ScreensManage>, pool: web::Data<DbPool>) -> actix_web::Result<HttpResponse> {
+    // Use web::block to avoid blocking async
+    let all_devices = web::block(move || {
+        let mut conn = pool.get()?;
+
+        actions::get_all_devices(&mut conn)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().json(all_devices))
+}
+
+#[delete("/devices/{id}")]
+pub(crate) async fn revoke_device(path: web::Path<String>, caller: RequirePermission<ScreensManage>, pool: web::Data<DbPool>)
+-> actix_web::Result<HttpResponse> {
+    let email = caller.into_inner().email;
+
+    let id = path.into_inner();
+    let device_id = id.clone();
+    let revoked_by = email.clone();
+
+    // Use web::block to avoid blocking async
+    let revoked = web::block(move || {
+        let mut conn = pool.get()?;
+
+        let now = Utc::now().naive_utc();
+        let Some(before) = actions::get_all_devices(&mut conn)?.into_iter().find(|device| device.id == device_id) else {
+            return Ok(false);
+        };
+        if !actions::revoke_device(&mut conn, &device_id, now)? {
+            return Ok(false);
+        }
+        let after = models::Device { pairing_code: None, revoked_at: Some(now), ..before.clone() };
+        audit::record(&mut conn, &revoked_by, "device.revoke", &device_id, Change::changed(&before, &after))?;
+
+        Ok::<_, actions::DbError>(true)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    if revoked {
+        log::info!("Device {id} revoked by {email}");
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+// --- Device health ---
+
+// A screen sends a heartbeat every 30 seconds, so this is a few missed heartbeats
+const DEFAULT_STALE_AFTER_SECONDS: i64 = 120;
+// Keep a misbehaving screen from filling the database with errors
+const MAX_CLIENT_ERRORS: usize = 20;
+const MAX_CLIENT_ERROR_LENGTH: usize = 500;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct HeartbeatRequest {
+    current_slide_id: Option<String>,
+    manifest_version: Option<String>,
+    uptime_seconds: i64,
+    /// Errors the screen ran into since its previous heartbeat
+    #[serde(default)]
+    client_errors: Vec<String>,
+}
+
+#[post("/device/heartbeat")]
+pub(crate) async fn device_heartbeat(
+    heartbeat: web::Json<HeartbeatRequest>,
+    device: AuthenticatedDevice,
+    pool: web::Data<DbPool>,
+) -> actix_web::Result<HttpResponse> {
+    let HeartbeatRequest { current_slide_id, manifest_version, uptime_seconds, client_errors } = heartbeat.into_inner();
+
+    let client_errors: Vec<String> = client_errors.into_iter()
+        .take(MAX_CLIENT_ERRORS)
+        .map(|e| e.chars().take(MAX_CLIENT_ERROR_LENGTH).collect())
+        .collect();
+    for client_error in &client_errors {
+        log::warn!("Device {} reported an error: {client_error}", device.0.id);
+    }
+
+    let status = models::DeviceStatus {
+        device_id: device.0.id,
+        last_seen_at: Utc::now().naive_utc(),
+        current_slide_id,
+        manifest_version,
+        uptime_seconds,
+        client_errors: serde_json::to_string(&client_errors).map_err(error::ErrorInternalServerError)?,
+    };
+
+    // Use web::block to avoid blocking async
+    web::block(move || {
+        let mut conn = pool.get()?;
+
+        actions::upsert_device_status(&mut conn, &status)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Debug, Deserialize)]
+struct HealthQuery {
+    stale_after_seconds: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+struct DeviceHealthResponse {
+    device_id: String,
+    name: Option<String>,
+    screen_id: Option<String>,
+    last_seen_at: Option<DateTime<Utc>>,
+    /// True if the device hasn't sent a heartbeat in `stale_after_seconds`
+    stale: bool,
+    current_slide_id: Option<String>,
+    manifest_version: Option<String>,
+    uptime_seconds: Option<i64>,
+    client_errors: Vec<String>,
+}
+
+#[get("/devices/health")]
+pub(crate) async fn devices_health(query: web::Query<HealthQuery>, _: RequirePermission<ScreensOperate>, pool: web::Data<DbPool>)
+-> actix_web::Result<HttpResponse> {
+    // TimeDelta::seconds panics for values that don't fit in a TimeDelta
+    let stale_after = Some(query.stale_after_seconds.unwrap_or(DEFAULT_STALE_AFTER_SECONDS))
+        .filter(|seconds| *seconds >= 0)
+        .and_then(TimeDelta::try_seconds)
+        .ok_or_else(|| error::ErrorBadRequest("stale_after_seconds must be a non-negative number of seconds"))?;
+    let now = Utc::now().naive_utc();
+
+    // Use web::block to avoid blocking async
+    let rows = web::block(move || {
+        let mut conn = pool.get()?;
+
+        actions::get_paired_devices_with_status(&mut conn)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    let health: Vec<DeviceHealthResponse> = rows.into_iter().map(|(device, status)| {
+        // A device that never sent a heartbeat counts as stale
+        let mut health = DeviceHealthResponse {
+            device_id: device.id,
+            name: device.name,
+            screen_id: device.screen_id,
+            last_seen_at: None,
+            stale: true,
+            current_slide_id: None,
+            manifest_version: None,
+            uptime_seconds: None,
+            client_errors: Vec::new(),
+        };
+        if let Some(status) = status {
+            health.last_seen_at = Some(status.last_seen_at.and_utc());
+            health.stale = now - status.last_seen_at > stale_after;
+            health.current_slide_id = status.current_slide_id;
+            health.manifest_version = status.manifest_version;
+            health.uptime_seconds = Some(status.uptime_seconds);
+            // Only the heartbeat route writes this column, so it is always a JSON array
+            health.client_errors = serde_json::from_str(&status.client_errors).unwrap_or_default();
+        }
+        health
+    }).collect();
+
+    Ok(HttpResponse::Ok().json(health))
+}
+
+// --- Screen commands ---
+
+// A reload queued while a screen is offline shouldn't surprise anyone hours later
+const COMMAND_LIFETIME_MINUTES: i64 = 10;
+const COMMAND_HISTORY_LIMIT: i64 = 100;
+
+/// What a screen is told to do. Stored as the snake_case name plus `slide_id`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "command", rename_all = "snake_case")]
+enum Command {
+    Reload,
+    JumpToSlide { slide_id: String },
+    /// Show an overlay with the screen's name, to find out which physical screen is which
+    Identify,
+    /// Clear cached data (except the device token) and reload
+    ClearCache,
+}
+
+impl Command {
+    fn into_parts(self) -> (&'static str, Option<String>) {
+        match self {
+            Command::Reload => ("reload", None),
+            Command::JumpToSlide { slide_id } => ("jump_to_slide", Some(slide_id)),
+            Command::Identify => ("identify", None),
+            Command::ClearCache => ("clear_cache", None),
+        }
+    }
+
+    fn from_parts(command: &str, slide_id: Option<String>) -> Option<Self> {
+        match (command, slide_id) {
+            ("reload", _) => Some(Command::Reload),
+            ("jump_to_slide", Some(slide_id)) => Some(Command::JumpToSlide { slide_id }),
+            ("identify", _) => Some(Command::Identify),
+            ("clear_cache", _) => Some(Command::ClearCache),
+            _ => None,
+        }
+    }
+}
+
+/// A queued command as shown to admins
+#[derive(Debug, Serialize)]
+struct CommandResponse {
+    id: String,
+    #[serde(flatten)]
+    command: Option<Command>,
+    issued_by: String,
+    issued_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    acknowledged_at: Option<DateTime<Utc>>,
+    acknowledged_by: Option<String>,
+    success: Option<bool>,
+    result_message: Option<String>,
+}
+
+impl From<models::ScreenCommand> for CommandResponse {
+    fn from(c: models::ScreenCommand) -> Self {
+        CommandResponse {
+            id: c.id,
+            command: Command::from_parts(&c.command, c.slide_id),
+            issued_by: c.issued_by,
+            issued_at: c.issued_at.and_utc(),
+            expires_at: c.expires_at.and_utc(),
+            acknowledged_at: c.acknowledged_at.map(|t| t.and_utc()),
+            acknowledged_by: c.acknowledged_by,
+            success: c.success,
+            result_message: c.result_message,
+        }
+    }
+}
+
+/// A pending command as sent to a device
+#[derive(Debug, Serialize)]
+struct DeviceCommandResponse {
+    id: String,
+    #[serde(flatten)]
+    command: Command,
+}
+
+#[post("/screens/{id}/commands")]
+pub(crate) async fn queue_screen_command(
+    path: web::Path<String>,
+    command: web::Json<Command>,
+    caller: RequirePermission<ScreensOperate>,
+    pool: web::Data<DbPool>,
+    broadcaster: web::Data<Broadcaster>,
+) -> actix_web::Result<HttpResponse> {
+    let email = caller.into_inner().email;
+
+    let screen_id = path.into_inner();
+    let (command, slide_id) = command.into_inner().into_parts();
+    let now = Utc::now().naive_utc();
+    let screen_command = models::ScreenCommand {
+        id: Uuid::new_v4().into(),
+        screen_id: screen_id.clone(),
+        command: command.to_string(),
+        slide_id,
+        issued_by: email,
+        issued_at: now,
+        expires_at: now + TimeDelta::minutes(COMMAND_LIFETIME_MINUTES),
+        acknowledged_at: None,
+        acknowledged_by: None,
+        success: None,
+        result_message: None,
+    };
+
+    // Use web::block to avoid blocking async
+    let screen_command = web::block(move || {
+        let mut conn = pool.get()?;
+
+        if actions::get_screen(&mut conn, &screen_command.screen_id)?.is_none() {
+            return Ok(None);
+        }
+        let screen_command = actions::insert_screen_command(&mut conn, screen_command)?;
+        audit::record(&mut conn, &screen_command.issued_by, "screen.command", &screen_command.screen_id, Change::created(&screen_command))?;
+
+        Ok::<_, actions::DbError>(Some(screen_command))
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    let Some(screen_command) = screen_command else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    log::info!("{} queued {} for screen {}", screen_command.issued_by, screen_command.command, screen_id);
+
+    broadcaster.send(ScreenEvent::Commands { screen_id });
+
+    Ok(HttpResponse::Created().json(CommandResponse::from(screen_command)))
+}
+
+#[get("/screens/{id}/commands")]
+pub(crate) async fn list_screen_commands(path: web::Path<String>, _: RequirePermission<ScreensOperate>, pool: web::Data<DbPool>)
+-> actix_web::Result<HttpResponse> {
+    let screen_id = path.into_inner();
+
+    // Use web::block to avoid blocking async
+    let history = web::block(move || {
+        let mut conn = pool.get()?;
+
+        actions::get_screen_command_history(&mut conn, &screen_id, COMMAND_HISTORY_LIMIT)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    let history: Vec<CommandResponse> = history.into_iter().map(CommandResponse::from).collect();
+
+    Ok(HttpResponse::Ok().json(history))
+}
+
+/// Polled by a device (and whenever it gets a `commands` event) to get the commands for its screen
+#[get("/device/commands")]
+pub(crate) async fn get_device_commands(device: AuthenticatedDevice, pool: web::Data<DbPool>) -> actix_web::Result<HttpResponse> {
+    let Some(screen_id) = device.screen_id().map(str::to_owned) else {
+        return Ok(HttpResponse::Forbidden().finish());
+    };
+
+    // Use web::block to avoid blocking async
+    let pending = web::block(move || {
+        let mut conn = pool.get()?;
+
+        actions::get_pending_screen_commands(&mut conn, &screen_id, Utc::now().naive_utc())
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    let pending: Vec<DeviceCommandResponse> = pending.into_iter()
+        .filter_map(|c| Some(DeviceCommandResponse {
+            command: Command::from_parts(&c.command, c.slide_id)?,
+            id: c.id,
+        }))
+        .collect();
+
+    Ok(HttpResponse::Ok().json(pending))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AcknowledgeCommandRequest {
+    success: bool,
+    message: Option<String>,
+}
+
+#[post("/device/commands/{id}/ack")]
+pub(crate) async fn acknowledge_device_command(
+    path: web::Path<String>,
+    ack: web::Json<AcknowledgeCommandRequest>,
+    device: AuthenticatedDevice,
+    pool: web::Data<DbPool>,
+) -> actix_web::Result<HttpResponse> {
+    let Some(screen_id) = device.screen_id().map(str::to_owned) else {
+        return Ok(HttpResponse::Forbidden().finish());
+    };
+    let command_id = path.into_inner();
+    let AcknowledgeCommandRequest { success, message } = ack.into_inner();
+
+    // Use web::block to avoid blocking async
+    let acknowledged = web::block(move || {
+        let mut conn = pool.get()?;
+
+        actions::acknowledge_screen_command(
+            &mut conn, &command_id, &screen_id, &device.0.id, success, message.as_deref(), Utc::now().naive_utc(),
+        )
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    if acknowledged {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+// --- Proof of play ---
+
+// Screens send plays in batches, this keeps a single request reasonable
+const MAX_PLAY_EVENTS_PER_BATCH: usize = 1000;
+// Nothing stays on the screen for longer than a day, so longer durations are bogus
+const MAX_PLAY_DURATION_MS: i64 = 24 * 60 * 60 * 1000;
+const DEFAULT_IMPRESSIONS_DAYS: u64 = 30;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PlayEventRequest {
+    slide_id: String,
+    started_at: DateTime<Utc>,
+    duration_ms: i64,
+}
+
+/// Called by a screen with the slides it has shown since the previous batch
+#[post("/device/plays")]
+pub(crate) async fn report_plays(
+    plays: web::Json<Vec<PlayEventRequest>>,
+    device: AuthenticatedDevice,
+    pool: web::Data<DbPool>,
+) -> actix_web::Result<HttpResponse> {
+    let Some(screen_id) = device.screen_id().map(str::to_owned) else {
+        return Ok(HttpResponse::Forbidden().finish());
+    };
+    let plays = plays.into_inner();
+    if plays.len() > MAX_PLAY_EVENTS_PER_BATCH {
+        return Err(error::ErrorBadRequest(format!("At most {MAX_PLAY_EVENTS_PER_BATCH} plays per batch")));
+    }
+
+    let now = Utc::now();
+    let events: Vec<models::NewPlayEvent> = plays.into_iter()
+        // A screen with a wrong clock would otherwise report plays in the future
+        .filter(|play| (0..=MAX_PLAY_DURATION_MS).contains(&play.duration_ms) && play.started_at <= now)
+        .map(|play| models::NewPlayEvent {
+            slide_id: play.slide_id,
+            screen_id: screen_id.clone(),
+            device_id: device.0.id.clone(),
+            started_at: play.started_at.naive_utc(),
+            duration_ms: play.duration_ms,
+        })
+        .collect();
+
+    // Use web::block to avoid blocking async
+    web::block(move || {
+        let mut conn = pool.get()?;
+
+        actions::insert_play_events(&mut conn, &events)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum StatsFormat {
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImpressionsQuery {
+    /// First day to include, in UTC
+    from: Option<NaiveDate>,
+    /// Last day to include, in UTC
+    to: Option<NaiveDate>,
+    slide_id: Option<String>,
+    screen_id: Option<String>,
+    format: Option<StatsFormat>,
+}
+
+/// Quote a CSV field if it needs it. Fields that a spreadsheet would read as a formula get a `'` in front,
+/// since captions are written by slide editors.
+fn csv_field(field: &str) -> String {
+    let field = if field.starts_with(['=', '+', '-', '@']) { format!("'{field}") } else { field.to_string() };
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+#[get("/stats/impressions")]
+pub(crate) async fn get_impressions(query: web::Query<ImpressionsQuery>, _: RequirePermission<SlidesRead>, pool: web::Data<DbPool>)
+-> actix_web::Result<HttpResponse> {
+    let ImpressionsQuery { from, to, slide_id, screen_id, format } = query.into_inner();
+    let to = to.unwrap_or_else(|| Utc::now().date_naive());
+    let from = from.or_else(|| to.checked_sub_days(Days::new(DEFAULT_IMPRESSIONS_DAYS - 1)));
+    // `to` is inclusive, so everything before the start of the next day
+    let (Some(from), Some(next_day)) = (from, to.checked_add_days(Days::new(1))) else {
+        return Err(error::ErrorBadRequest("to is out of range"));
+    };
+    if from > to {
+        return Err(error::ErrorBadRequest("from must not be after to"));
+    }
+    let midnight = chrono::NaiveTime::MIN;
+    let (from, to) = (from.and_time(midnight), next_day.and_time(midnight));
+
+    // Use web::block to avoid blocking async
+    let impressions = web::block(move || {
+        let mut conn = pool.get()?;
+
+        actions::get_impressions(&mut conn, from, to, slide_id.as_deref(), screen_id.as_deref())
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    match format.unwrap_or(StatsFormat::Json) {
+        StatsFormat::Json => Ok(HttpResponse::Ok().json(impressions)),
+        StatsFormat::Csv => {
+            let mut csv = String::from("day,slide_id,caption,screen_id,impressions,total_seconds\n");
+            for i in impressions {
+                csv.push_str(&format!("{},{},{},{},{},{}\n",
+                    i.day,
+                    csv_field(&i.slide_id),
+                    csv_field(i.caption.as_deref().unwrap_or_default()),
+                    csv_field(&i.screen_id),
+                    i.impressions,
+                    i.total_seconds,
+                ));
+            }
+            Ok(HttpResponse::Ok()
+                .content_type("text/csv; charset=utf-8")
+                .insert_header((header::CONTENT_DISPOSITION, "attachment; filename=\"impressions.csv\""))
+                .body(csv))
+        },
+    }
+}
+
+// --- Audit log ---
+
+const DEFAULT_AUDIT_LIMIT: i64 = 50;
+const MAX_AUDIT_LIMIT: i64 = 500;
+
+#[derive(Debug, Serialize)]
+struct AuditPage {
+    events: Vec<audit::AuditEventResponse>,
+    /// Pass as `before_id` to get the next page. None on the last page.
+    next_before_id: Option<i32>,
+}
+
+/// Who changed what, newest first
+#[get("/audit")]
+pub(crate) async fn list_audit_events(query: web::Query<audit::AuditQuery>, _: RequirePermission<AuditRead>, pool: web::Data<DbPool>)
+-> actix_web::Result<HttpResponse> {
+    let query = query.into_inner();
+    let limit = query.limit.unwrap_or(DEFAULT_AUDIT_LIMIT);
+    if !(1..=MAX_AUDIT_LIMIT).contains(&limit) {
+        return Err(error::ErrorBadRequest(format!("limit must be between 1 and {MAX_AUDIT_LIMIT}")));
+    }
+
+    // Use web::block to avoid blocking async
+    let mut events = web::block(move || {
+        let mut conn = pool.get()?;
+
+        // One more than asked for, to know if there is another page
+        actions::get_audit_events(&mut conn, &query, limit + 1)
+    }).await?.map_err(error::ErrorInternalServerError)?;
+
+    let has_more = events.len() as i64 > limit;
+    events.truncate(limit as usize);
+    let next_before_id = events.last().filter(|_| has_more).map(|event| event.id);
+
+    Ok(HttpResponse::Ok().json(AuditPage {
+        events: events.into_iter().map(Into::into).collect(),
+        next_before_id,
+    }))
+}
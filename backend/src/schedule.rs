@@ -0,0 +1,326 @@
+//! Settings that change automatically at certain times, like dark mode in the evening,
+//! and the quiet hours when the screens are blanked.
+//! All times here are local time, since that's what people mean by "at 18:00 on Fridays".
+
+use chrono::{Datelike, Days, Duration, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::models::{ColorMode, LayoutType, QuietHoursException, SettingsSchedule};
+
+// How far ahead to look for the next change. Weekly rules repeat, so a week and a day is enough.
+const LOOKAHEAD_DAYS: i64 = 8;
+
+/// When a schedule applies
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum ScheduleRule {
+    /// Every week on the given days. If `end_time` is before `start_time` the window ends the next day.
+    Weekly(WeeklyWindow),
+    /// Once, from `start` until `end`
+    DateRange { start: NaiveDateTime, end: NaiveDateTime },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub(crate) struct WeeklyWindow {
+    pub weekdays: Vec<Weekday>,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+}
+
+impl WeeklyWindow {
+    /// The window that starts on `day`, if it starts on that weekday
+    fn window_starting_on(&self, day: NaiveDate) -> Option<(NaiveDateTime, NaiveDateTime)> {
+        if !self.weekdays.contains(&day.weekday()) {
+            return None;
+        }
+        let start = day.and_time(self.start_time);
+        let end = if self.end_time > self.start_time {
+            day.and_time(self.end_time)
+        } else {
+            (day + Duration::days(1)).and_time(self.end_time)
+        };
+        Some((start, end))
+    }
+
+    pub(crate) fn is_active(&self, now: NaiveDateTime) -> bool {
+        // A window that started yesterday can still be running
+        [now.date() - Duration::days(1), now.date()].into_iter()
+            .filter_map(|day| self.window_starting_on(day))
+            .any(|(start, end)| start <= now && now < end)
+    }
+
+    /// Every time this window starts or ends after `now`, in the coming days
+    pub(crate) fn boundaries_after(&self, now: NaiveDateTime) -> Vec<NaiveDateTime> {
+        (-1..LOOKAHEAD_DAYS)
+            .filter_map(|offset| self.window_starting_on(now.date() + Duration::days(offset)))
+            .flat_map(|(start, end)| [start, end])
+            .filter(|&time| time > now)
+            .collect()
+    }
+}
+
+impl ScheduleRule {
+    pub(crate) fn is_active(&self, now: NaiveDateTime) -> bool {
+        match self {
+            ScheduleRule::Weekly(window) => window.is_active(now),
+            ScheduleRule::DateRange { start, end } => *start <= now && now < *end,
+        }
+    }
+
+    fn boundaries_after(&self, now: NaiveDateTime) -> Vec<NaiveDateTime> {
+        match self {
+            ScheduleRule::Weekly(window) => window.boundaries_after(now),
+            ScheduleRule::DateRange { start, end } => [*start, *end].into_iter().filter(|&time| time > now).collect(),
+        }
+    }
+
+    /// Stored as a weekday bitmask plus times for weekly rules, or as a start and end for date ranges
+    pub(crate) fn into_parts(self) -> RuleParts {
+        match self {
+            ScheduleRule::Weekly(WeeklyWindow { weekdays, start_time, end_time }) => RuleParts {
+                weekdays: Some(weekdays_to_mask(&weekdays)),
+                start_time: Some(start_time),
+                end_time: Some(end_time),
+                ..Default::default()
+            },
+            ScheduleRule::DateRange { start, end } => RuleParts {
+                start_at: Some(start),
+                end_at: Some(end),
+                ..Default::default()
+            },
+        }
+    }
+
+    pub(crate) fn from_parts(parts: RuleParts) -> Option<Self> {
+        match parts {
+            RuleParts { weekdays: Some(mask), start_time: Some(start_time), end_time: Some(end_time), .. } =>
+                Some(ScheduleRule::Weekly(WeeklyWindow { weekdays: mask_to_weekdays(mask), start_time, end_time })),
+            RuleParts { start_at: Some(start), end_at: Some(end), .. } =>
+                Some(ScheduleRule::DateRange { start, end }),
+            _ => None,
+        }
+    }
+}
+
+/// The columns a `ScheduleRule` is stored in
+#[derive(Debug, Default)]
+pub(crate) struct RuleParts {
+    pub weekdays: Option<i32>,
+    pub start_time: Option<NaiveTime>,
+    pub end_time: Option<NaiveTime>,
+    pub start_at: Option<NaiveDateTime>,
+    pub end_at: Option<NaiveDateTime>,
+}
+
+impl From<&SettingsSchedule> for RuleParts {
+    fn from(schedule: &SettingsSchedule) -> Self {
+        RuleParts {
+            weekdays: schedule.weekdays,
+            start_time: schedule.start_time,
+            end_time: schedule.end_time,
+            start_at: schedule.start_at,
+            end_at: schedule.end_at,
+        }
+    }
+}
+
+/// Monday is bit 0 and Sunday bit 6
+pub(crate) fn weekdays_to_mask(weekdays: &[Weekday]) -> i32 {
+    weekdays.iter().fold(0, |mask, day| mask | 1 << day.num_days_from_monday())
+}
+
+pub(crate) fn mask_to_weekdays(mask: i32) -> Vec<Weekday> {
+    (0..7u8)
+        .filter(|bit| mask & 1 << bit != 0)
+        .filter_map(|bit| Weekday::try_from(bit).ok())
+        .collect()
+}
+
+/// The layout and colour mode that apply at some point in time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ResolvedSettings {
+    pub layout_type: LayoutType,
+    pub color_mode: ColorMode,
+}
+
+/// Applies every active schedule on top of `base`. Date ranges win over weekly rules,
+/// and otherwise the most recently created schedule wins. Each setting is resolved on
+/// its own, so a schedule that only sets the colour mode leaves the layout alone.
+pub(crate) fn resolve(base: ResolvedSettings, schedules: &[SettingsSchedule], now: NaiveDateTime) -> ResolvedSettings {
+    let mut active: Vec<_> = schedules.iter()
+        .filter_map(|schedule| Some((schedule, ScheduleRule::from_parts(schedule.into())?)))
+        .filter(|(_, rule)| rule.is_active(now))
+        .collect();
+    active.sort_by_key(|(schedule, rule)| (matches!(rule, ScheduleRule::DateRange { .. }), schedule.created_at));
+
+    // Sorted from lowest to highest precedence, so later schedules overwrite earlier ones
+    active.into_iter().fold(base, |resolved, (schedule, _)| ResolvedSettings {
+        layout_type: schedule.layout_type.unwrap_or(resolved.layout_type),
+        color_mode: schedule.color_mode.unwrap_or(resolved.color_mode),
+    })
+}
+
+/// The next time the resolved settings change, if that happens in the coming days
+pub(crate) fn next_change(base: ResolvedSettings, schedules: &[SettingsSchedule], now: NaiveDateTime) -> Option<NaiveDateTime> {
+    let current = resolve(base, schedules, now);
+    let mut boundaries: Vec<_> = schedules.iter()
+        .filter_map(|schedule| ScheduleRule::from_parts(schedule.into()))
+        .flat_map(|rule| rule.boundaries_after(now))
+        .collect();
+    boundaries.sort();
+    boundaries.dedup();
+
+    // A boundary doesn't always change anything, e.g. when a higher priority schedule is active
+    boundaries.into_iter().find(|&time| resolve(base, schedules, time) != current)
+}
+
+/// When the screens show a clock instead of their content, to save power and panel life
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub(crate) struct QuietHours {
+    pub windows: Vec<WeeklyWindow>,
+    /// Days that don't follow the weekly windows, like holidays or a late event
+    pub exceptions: Vec<QuietHoursException>,
+}
+
+impl QuietHours {
+    pub(crate) fn is_quiet(&self, now: NaiveDateTime) -> bool {
+        match self.exceptions.iter().find(|exception| exception.date == now.date()) {
+            Some(exception) => exception.quiet,
+            None => self.windows.iter().any(|window| window.is_active(now)),
+        }
+    }
+
+    /// The next time the screens go quiet or wake up, if that happens in the coming days
+    pub(crate) fn next_change(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        let current = self.is_quiet(now);
+        let mut boundaries: Vec<_> = self.windows.iter()
+            .flat_map(|window| window.boundaries_after(now))
+            .chain(self.exceptions.iter().flat_map(|exception| {
+                // The last date there is has no next day
+                [Some(exception.date), exception.date.checked_add_days(Days::new(1))].into_iter()
+                    .flatten()
+                    .map(|day| day.and_time(NaiveTime::MIN))
+            }))
+            .filter(|&time| time > now)
+            .collect();
+        boundaries.sort();
+
+        boundaries.into_iter().find(|&time| self.is_quiet(time) != current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::NaiveDate;
+
+    fn at(date: &str, time: &str) -> NaiveDateTime {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+            .and_time(NaiveTime::parse_from_str(time, "%H:%M").unwrap())
+    }
+
+    fn schedule(rule: ScheduleRule, layout_type: Option<LayoutType>, color_mode: Option<ColorMode>, created_at: NaiveDateTime)
+    -> SettingsSchedule {
+        let parts = rule.into_parts();
+        SettingsSchedule {
+            id: uuid::Uuid::new_v4().into(),
+            name: "Test".to_string(),
+            layout_type,
+            color_mode,
+            weekdays: parts.weekdays,
+            start_time: parts.start_time,
+            end_time: parts.end_time,
+            start_at: parts.start_at,
+            end_at: parts.end_at,
+            created_at,
+        }
+    }
+
+    const BASE: ResolvedSettings = ResolvedSettings { layout_type: LayoutType::Mixed, color_mode: ColorMode::LightMode };
+
+    #[test]
+    fn test_weekday_mask() {
+        let days = vec![Weekday::Mon, Weekday::Wed, Weekday::Sun];
+        assert_eq!(weekdays_to_mask(&days), 0b1000101);
+        assert_eq!(mask_to_weekdays(weekdays_to_mask(&days)), days);
+    }
+
+    #[test]
+    fn test_weekly_window_over_midnight() {
+        // 2025-11-07 is a Friday
+        let window = WeeklyWindow { weekdays: vec![Weekday::Fri], start_time: at("2025-11-07", "18:00").time(), end_time: at("2025-11-07", "07:00").time() };
+        assert!(!window.is_active(at("2025-11-07", "17:59")));
+        assert!(window.is_active(at("2025-11-07", "18:00")));
+        assert!(window.is_active(at("2025-11-08", "06:59")));
+        assert!(!window.is_active(at("2025-11-08", "07:00")));
+        // Thursday night isn't in the schedule
+        assert!(!window.is_active(at("2025-11-07", "03:00")));
+    }
+
+    #[test]
+    fn test_quiet_hours() {
+        let weekdays = vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri];
+        let quiet_hours = QuietHours {
+            windows: vec![WeeklyWindow { weekdays, start_time: at("2025-12-22", "22:00").time(), end_time: at("2025-12-22", "07:00").time() }],
+            exceptions: vec![QuietHoursException {
+                date: NaiveDate::from_ymd_opt(2025, 12, 24).unwrap(),
+                quiet: true,
+                name: "Julafton".to_string(),
+            }],
+        };
+
+        // 2025-12-22 is a Monday
+        assert!(!quiet_hours.is_quiet(at("2025-12-22", "21:00")));
+        assert!(quiet_hours.is_quiet(at("2025-12-22", "23:00")));
+        assert_eq!(quiet_hours.next_change(at("2025-12-22", "21:00")), Some(at("2025-12-22", "22:00")));
+        assert_eq!(quiet_hours.next_change(at("2025-12-22", "23:00")), Some(at("2025-12-23", "07:00")));
+
+        // Quiet all of Christmas Eve, so the screens don't wake up on the morning of the 24th
+        assert!(quiet_hours.is_quiet(at("2025-12-24", "12:00")));
+        assert_eq!(quiet_hours.next_change(at("2025-12-23", "23:00")), Some(at("2025-12-25", "07:00")));
+        assert_eq!(quiet_hours.next_change(at("2025-12-24", "12:00")), Some(at("2025-12-25", "07:00")));
+
+        // An exception on the last date there is doesn't overflow
+        let quiet_hours = QuietHours {
+            windows: vec![],
+            exceptions: vec![QuietHoursException { date: NaiveDate::MAX, quiet: true, name: "The end".to_string() }],
+        };
+        assert_eq!(quiet_hours.next_change(at("2025-12-22", "21:00")), Some(NaiveDate::MAX.and_time(NaiveTime::MIN)));
+    }
+
+    #[test]
+    fn test_resolve_and_next_change() {
+        let created = at("2025-11-01", "12:00");
+        let every_day = vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri, Weekday::Sat, Weekday::Sun];
+        let schedules = vec![
+            // Dark mode every night
+            schedule(
+                ScheduleRule::Weekly(WeeklyWindow { weekdays: every_day, start_time: at("2025-11-07", "18:00").time(), end_time: at("2025-11-07", "07:00").time() }),
+                None, Some(ColorMode::DarkMode), created,
+            ),
+            // An event with only slides, in light mode
+            schedule(
+                ScheduleRule::DateRange { start: at("2025-11-07", "17:00"), end: at("2025-11-07", "22:00") },
+                Some(LayoutType::FullscreenSlideshow), Some(ColorMode::LightMode), created,
+            ),
+        ];
+
+        assert_eq!(resolve(BASE, &schedules, at("2025-11-07", "12:00")), BASE);
+        assert_eq!(next_change(BASE, &schedules, at("2025-11-07", "12:00")), Some(at("2025-11-07", "17:00")));
+
+        // The date range wins over the weekly rule
+        assert_eq!(
+            resolve(BASE, &schedules, at("2025-11-07", "19:00")),
+            ResolvedSettings { layout_type: LayoutType::FullscreenSlideshow, color_mode: ColorMode::LightMode },
+        );
+        // The weekly rule starting at 18:00 doesn't change anything while the event is on
+        assert_eq!(next_change(BASE, &schedules, at("2025-11-07", "17:30")), Some(at("2025-11-07", "22:00")));
+
+        assert_eq!(
+            resolve(BASE, &schedules, at("2025-11-07", "23:00")),
+            ResolvedSettings { layout_type: LayoutType::Mixed, color_mode: ColorMode::DarkMode },
+        );
+        assert_eq!(next_change(BASE, &schedules, at("2025-11-07", "23:00")), Some(at("2025-11-08", "07:00")));
+    }
+}
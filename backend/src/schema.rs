@@ -1,10 +1,261 @@
 // @generated automatically by Diesel CLI.
 
 diesel::table! {
-    settings (id) {
+    audit_events (id) {
         id -> Integer,
-        layout_type -> Text,
-        color_mode -> Text,
+        actor -> Text,
+        action -> Text,
+        target_id -> Text,
+        before -> Nullable<Text>,
+        after -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    calendar_feeds (id) {
+        id -> Text,
+        name -> Text,
+        url -> Text,
+        created_at -> Timestamp,
+        slide_days_before -> Nullable<Integer>,
+    }
+}
+
+diesel::table! {
+    device_status (device_id) {
+        device_id -> Text,
+        last_seen_at -> Timestamp,
+        current_slide_id -> Nullable<Text>,
+        manifest_version -> Nullable<Text>,
+        uptime_seconds -> BigInt,
+        client_errors -> Text,
+    }
+}
+
+diesel::table! {
+    devices (id) {
+        id -> Text,
+        token_hash -> Text,
+        pairing_code -> Nullable<Text>,
+        pairing_expires_at -> Nullable<Timestamp>,
+        name -> Nullable<Text>,
+        screen_id -> Nullable<Text>,
+        approved_by -> Nullable<Text>,
+        created_at -> Timestamp,
+        approved_at -> Nullable<Timestamp>,
+        revoked_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    event_slide_options (event_key) {
+        event_key -> Text,
+        feed_id -> Text,
+        event_uid -> Text,
+        suppressed -> Bool,
+        poster_filetype -> Nullable<Text>,
+        updated_by -> Text,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    group_members (group_id, user_id) {
+        group_id -> Text,
+        user_id -> Text,
+        role -> Text,
+    }
+}
+
+diesel::table! {
+    groups (id) {
+        id -> Text,
+        name -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    invites (id) {
+        id -> Text,
+        token_hash -> Text,
+        role -> Text,
+        created_by -> Text,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+        used_by -> Nullable<Text>,
+        used_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    overrides (id) {
+        id -> Text,
+        message -> Nullable<Text>,
+        slide_id -> Nullable<Text>,
+        created_by -> Text,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+        cleared_by -> Nullable<Text>,
+        cleared_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    play_events (id) {
+        id -> Integer,
+        slide_id -> Text,
+        screen_id -> Text,
+        device_id -> Text,
+        started_at -> Timestamp,
+        duration_ms -> BigInt,
+    }
+}
+
+diesel::table! {
+    provisioning_domains (domain) {
+        domain -> Text,
+        role -> Text,
+        created_by -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    provisioning_emails (email) {
+        email -> Text,
+        role -> Nullable<Text>,
+        created_by -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    quiet_hours (id) {
+        id -> Integer,
+        weekdays -> Integer,
+        start_time -> Time,
+        end_time -> Time,
+    }
+}
+
+diesel::table! {
+    quiet_hours_exceptions (date) {
+        date -> Date,
+        quiet -> Bool,
+        name -> Text,
+    }
+}
+
+diesel::table! {
+    screen_commands (id) {
+        id -> Text,
+        screen_id -> Text,
+        command -> Text,
+        slide_id -> Nullable<Text>,
+        issued_by -> Text,
+        issued_at -> Timestamp,
+        expires_at -> Timestamp,
+        acknowledged_at -> Nullable<Timestamp>,
+        acknowledged_by -> Nullable<Text>,
+        success -> Nullable<Bool>,
+        result_message -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    screen_sl_sites (screen_id, site_id) {
+        screen_id -> Text,
+        site_id -> Integer,
+    }
+}
+
+diesel::table! {
+    screens (id) {
+        id -> Text,
+        name -> Text,
+        layout_type -> Nullable<Text>,
+        color_mode -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    sessions (id) {
+        id -> Text,
+        key_hash -> Text,
+        state -> Text,
+        email -> Nullable<Text>,
+        user_agent -> Nullable<Text>,
+        created_at -> Timestamp,
+        last_seen_at -> Timestamp,
+        expires_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    setting_values (key) {
+        key -> Text,
+        value -> Text,
+    }
+}
+
+diesel::table! {
+    settings_schedules (id) {
+        id -> Text,
+        name -> Text,
+        layout_type -> Nullable<Text>,
+        color_mode -> Nullable<Text>,
+        weekdays -> Nullable<Integer>,
+        start_time -> Nullable<Time>,
+        end_time -> Nullable<Time>,
+        start_at -> Nullable<Timestamp>,
+        end_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    sl_muted_deviations (deviation_case_id) {
+        deviation_case_id -> BigInt,
+        muted_by -> Text,
+        muted_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    sl_tracked_lines (id) {
+        id -> Integer,
+        site_id -> Integer,
+        transport_mode -> Text,
+        line_id -> Integer,
+        direction_code -> Nullable<Integer>,
+    }
+}
+
+diesel::table! {
+    sl_tracked_sites (site_id) {
+        site_id -> Integer,
+        name -> Text,
+    }
+}
+
+diesel::table! {
+    slide_screens (slide_id, screen_id) {
+        slide_id -> Text,
+        screen_id -> Text,
+    }
+}
+
+diesel::table! {
+    slide_status_changes (id) {
+        id -> Text,
+        slide_id -> Text,
+        from_status -> Nullable<Text>,
+        to_status -> Text,
+        changed_by -> Text,
+        comment -> Nullable<Text>,
+        changed_at -> Timestamp,
     }
 }
 
@@ -16,6 +267,9 @@ diesel::table! {
         end_date -> Timestamp,
         active -> Bool,
         filetype -> Text,
+        created_by -> Nullable<Text>,
+        group_id -> Nullable<Text>,
+        status -> Text,
     }
 }
 
@@ -23,12 +277,48 @@ diesel::table! {
     users (id) {
         id -> Text,
         email -> Text,
-        admin -> Bool,
+        role -> Text,
     }
 }
 
+diesel::joinable!(device_status -> devices (device_id));
+diesel::joinable!(devices -> screens (screen_id));
+diesel::joinable!(group_members -> groups (group_id));
+diesel::joinable!(group_members -> users (user_id));
+diesel::joinable!(screen_commands -> devices (acknowledged_by));
+diesel::joinable!(screen_commands -> screens (screen_id));
+diesel::joinable!(screen_sl_sites -> screens (screen_id));
+diesel::joinable!(sl_tracked_lines -> sl_tracked_sites (site_id));
+diesel::joinable!(slide_screens -> screens (screen_id));
+diesel::joinable!(slide_screens -> slides (slide_id));
+diesel::joinable!(slides -> groups (group_id));
+
 diesel::allow_tables_to_appear_in_same_query!(
-    settings,
+    audit_events,
+    calendar_feeds,
+    device_status,
+    devices,
+    event_slide_options,
+    group_members,
+    groups,
+    invites,
+    overrides,
+    play_events,
+    provisioning_domains,
+    provisioning_emails,
+    quiet_hours,
+    quiet_hours_exceptions,
+    screen_commands,
+    screen_sl_sites,
+    screens,
+    sessions,
+    setting_values,
+    settings_schedules,
+    sl_muted_deviations,
+    sl_tracked_lines,
+    sl_tracked_sites,
+    slide_screens,
+    slide_status_changes,
     slides,
     users,
 );
@@ -0,0 +1,180 @@
+//! Login sessions stored in SQLite, so they can be listed and revoked. The cookie only holds a random session key,
+//! and only its hash is stored (like device tokens, see `devices::hash_token`).
+//! A session ends when it hasn't been used for `IDLE_TIMEOUT_DAYS`, and `MAX_AGE_DAYS` after login at the latest.
+
+use std::collections::HashMap;
+
+use actix_session::storage::{LoadError, SaveError, SessionKey, SessionStore, UpdateError};
+use actix_web::cookie::time::Duration;
+use actix_web::web;
+use chrono::{NaiveDateTime, TimeDelta, Utc};
+use uuid::Uuid;
+
+use crate::actions::{self, DbError};
+use crate::devices::{generate_token, hash_token};
+use crate::models;
+
+use super::DbPool;
+
+/// How long a session lasts without being used. Every request moves the expiry forward.
+pub(crate) const IDLE_TIMEOUT_DAYS: i64 = 7;
+/// How long a session lasts after login, however much it is used
+pub(crate) const MAX_AGE_DAYS: i64 = 30;
+
+/// The session keys `verify_token` stores who logged in and with which browser under
+pub(crate) const AUTH_KEY: &str = "auth";
+pub(crate) const USER_AGENT_KEY: &str = "user_agent";
+
+type SessionState = HashMap<String, String>;
+
+pub(crate) struct SqliteSessionStore {
+    pool: DbPool,
+    max_age: TimeDelta,
+}
+
+impl SqliteSessionStore {
+    pub(crate) fn new(pool: DbPool) -> Self {
+        SqliteSessionStore { pool, max_age: TimeDelta::days(MAX_AGE_DAYS) }
+    }
+
+    /// Runs a database action on the blocking thread pool
+    async fn run<T: Send + 'static>(
+        &self,
+        action: impl FnOnce(&mut diesel::SqliteConnection) -> Result<T, DbError> + Send + 'static,
+    ) -> anyhow::Result<T> {
+        let pool = self.pool.clone();
+        let result = web::block(move || {
+            let mut conn = pool.get()?;
+            action(&mut conn)
+        }).await?;
+        result.map_err(|e| anyhow::anyhow!(e))
+    }
+}
+
+/// When a session used now expires: after the idle timeout `ttl`, but never later than its max age
+fn expires_at(created_at: NaiveDateTime, now: NaiveDateTime, ttl: &Duration, max_age: TimeDelta) -> NaiveDateTime {
+    (now + TimeDelta::seconds(ttl.whole_seconds())).min(created_at + max_age)
+}
+
+/// The session state is a map of JSON values. `auth` and `user_agent` are copied to their own columns.
+fn parse_state(state: &SessionState) -> (Option<String>, Option<String>) {
+    let email = state.get(AUTH_KEY)
+        .and_then(|auth| serde_json::from_str::<serde_json::Value>(auth).ok())
+        .and_then(|auth| auth["email"].as_str().map(str::to_string));
+    let user_agent = state.get(USER_AGENT_KEY)
+        .and_then(|user_agent| serde_json::from_str::<String>(user_agent).ok());
+    (email, user_agent)
+}
+
+impl SessionStore for SqliteSessionStore {
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<SessionState>, LoadError> {
+        let key_hash = hash_token(session_key.as_ref());
+        let session = self.run(move |conn| actions::get_session_by_key_hash(conn, &key_hash, Utc::now().naive_utc()))
+            .await
+            .map_err(LoadError::Other)?;
+
+        session
+            .map(|session| serde_json::from_str(&session.state))
+            .transpose()
+            .map_err(|e| LoadError::Deserialization(e.into()))
+    }
+
+    async fn save(&self, session_state: SessionState, ttl: &Duration) -> Result<SessionKey, SaveError> {
+        let key = generate_token();
+        let (email, user_agent) = parse_state(&session_state);
+        let now = Utc::now().naive_utc();
+        let session = models::Session {
+            id: Uuid::new_v4().into(),
+            key_hash: hash_token(&key),
+            state: serde_json::to_string(&session_state).map_err(|e| SaveError::Serialization(e.into()))?,
+            email,
+            user_agent,
+            created_at: now,
+            last_seen_at: now,
+            expires_at: expires_at(now, now, ttl, self.max_age),
+        };
+
+        self.run(move |conn| actions::insert_session(conn, &session))
+            .await
+            .map_err(SaveError::Other)?;
+
+        SessionKey::try_from(key).map_err(|e| SaveError::Other(e.into()))
+    }
+
+    async fn update(&self, session_key: SessionKey, session_state: SessionState, ttl: &Duration) -> Result<SessionKey, UpdateError> {
+        let key_hash = hash_token(session_key.as_ref());
+        let now = Utc::now().naive_utc();
+        let lookup_hash = key_hash.clone();
+        let session = self.run(move |conn| actions::get_session_by_key_hash(conn, &lookup_hash, now))
+            .await
+            .map_err(UpdateError::Other)?;
+
+        // Expired or revoked since it was loaded, so it gets a new key
+        let Some(session) = session else {
+            return self.save(session_state, ttl).await.map_err(|e| match e {
+                SaveError::Serialization(e) => UpdateError::Serialization(e),
+                SaveError::Other(e) => UpdateError::Other(e),
+            });
+        };
+
+        let (email, user_agent) = parse_state(&session_state);
+        let state = serde_json::to_string(&session_state).map_err(|e| UpdateError::Serialization(e.into()))?;
+        let expires_at = expires_at(session.created_at, now, ttl, self.max_age);
+        self.run(move |conn| {
+            actions::update_session_state(conn, &key_hash, &state, email.as_deref(), user_agent.as_deref(), now, expires_at)
+        })
+        .await
+        .map_err(UpdateError::Other)?;
+
+        Ok(session_key)
+    }
+
+    async fn update_ttl(&self, session_key: &SessionKey, ttl: &Duration) -> anyhow::Result<()> {
+        let key_hash = hash_token(session_key.as_ref());
+        let now = Utc::now().naive_utc();
+        let ttl = *ttl;
+        let max_age = self.max_age;
+
+        self.run(move |conn| {
+            let Some(session) = actions::get_session_by_key_hash(conn, &key_hash, now)? else {
+                return Ok(());
+            };
+            actions::touch_session(conn, &key_hash, now, expires_at(session.created_at, now, &ttl, max_age))
+        })
+        .await
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> anyhow::Result<()> {
+        let key_hash = hash_token(session_key.as_ref());
+        self.run(move |conn| actions::delete_session_by_key_hash(conn, &key_hash)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    #[test]
+    fn test_sessions_expire_after_max_age() {
+        let login = NaiveDate::from_ymd_opt(2025, 11, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+        let ttl = Duration::days(IDLE_TIMEOUT_DAYS);
+        let max_age = TimeDelta::days(MAX_AGE_DAYS);
+
+        assert_eq!(expires_at(login, login, &ttl, max_age), login + TimeDelta::days(IDLE_TIMEOUT_DAYS));
+        // Used every day, but it still ends MAX_AGE_DAYS after login
+        let later = login + TimeDelta::days(MAX_AGE_DAYS - 1);
+        assert_eq!(expires_at(login, later, &ttl, max_age), login + max_age);
+    }
+
+    #[test]
+    fn test_parse_state() {
+        let state = HashMap::from([
+            (AUTH_KEY.to_string(), r#"{"email":"admin@example.com","role":"admin"}"#.to_string()),
+            (USER_AGENT_KEY.to_string(), r#""Firefox""#.to_string()),
+        ]);
+        assert_eq!(parse_state(&state), (Some("admin@example.com".to_string()), Some("Firefox".to_string())));
+        assert_eq!(parse_state(&HashMap::new()), (None, None));
+    }
+}
@@ -0,0 +1,153 @@
+//! Every setting the screens have, with its type, default and validation.
+//! Values are stored as JSON in `setting_values`, and a setting without a stored value uses its default.
+//! Adding a setting only needs an entry in `SETTINGS` (and the screen using it).
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::models::{ColorMode, LayoutType, Settings};
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum SettingKind {
+    /// One of `options`
+    Enum { options: &'static [&'static str], default: &'static str },
+    Integer { min: i64, max: i64, default: i64 },
+    Bool { default: bool },
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct SettingDefinition {
+    pub key: &'static str,
+    pub description: &'static str,
+    #[serde(flatten)]
+    pub kind: SettingKind,
+}
+
+pub(crate) const LAYOUT_TYPE: &str = "layout_type";
+pub(crate) const COLOR_MODE: &str = "color_mode";
+
+pub(crate) const SETTINGS: &[SettingDefinition] = &[
+    SettingDefinition {
+        key: LAYOUT_TYPE,
+        description: "What the screen shows: only slides, or slides next to SL departures",
+        kind: SettingKind::Enum { options: LayoutType::VALUES, default: "mixed" },
+    },
+    SettingDefinition {
+        key: COLOR_MODE,
+        description: "Light or dark colours",
+        kind: SettingKind::Enum { options: ColorMode::VALUES, default: "light_mode" },
+    },
+    SettingDefinition {
+        key: "slide_interval_seconds",
+        description: "How long each slide is shown",
+        kind: SettingKind::Integer { min: 1, max: 600, default: 3 },
+    },
+    SettingDefinition {
+        key: "sl_refresh_seconds",
+        description: "How often SL departures are refreshed",
+        kind: SettingKind::Integer { min: 5, max: 600, default: 5 },
+    },
+    SettingDefinition {
+        key: "show_clock",
+        description: "Show the time in the header",
+        kind: SettingKind::Bool { default: false },
+    },
+];
+
+pub(crate) fn definition(key: &str) -> Option<&'static SettingDefinition> {
+    SETTINGS.iter().find(|definition| definition.key == key)
+}
+
+impl SettingDefinition {
+    pub(crate) fn default_value(&self) -> Value {
+        match self.kind {
+            SettingKind::Enum { default, .. } => default.into(),
+            SettingKind::Integer { default, .. } => default.into(),
+            SettingKind::Bool { default } => default.into(),
+        }
+    }
+
+    pub(crate) fn validate(&self, value: &Value) -> Result<(), String> {
+        let valid = match self.kind {
+            SettingKind::Enum { options, .. } => value.as_str().is_some_and(|value| options.contains(&value)),
+            SettingKind::Integer { min, max, .. } => value.as_i64().is_some_and(|value| (min..=max).contains(&value)),
+            SettingKind::Bool { .. } => value.is_boolean(),
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(format!("Invalid value for {}: {}", self.key, value))
+        }
+    }
+}
+
+/// The value of every setting, with defaults filled in
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct SettingValues(BTreeMap<&'static str, Value>);
+
+impl SettingValues {
+    /// Stored values for settings that no longer exist are ignored, but invalid values give an error
+    pub(crate) fn from_stored(stored: Vec<(String, String)>) -> Result<Self, String> {
+        let mut values: BTreeMap<_, _> = SETTINGS.iter()
+            .map(|definition| (definition.key, definition.default_value()))
+            .collect();
+        for (key, value) in stored {
+            let Some(definition) = definition(&key) else {
+                continue;
+            };
+            let value = serde_json::from_str(&value).map_err(|e| format!("Invalid value for {key}: {e}"))?;
+            definition.validate(&value)?;
+            values.insert(definition.key, value);
+        }
+        Ok(SettingValues(values))
+    }
+
+    pub(crate) fn get(&self, key: &str) -> &Value {
+        &self.0[key]
+    }
+
+    pub(crate) fn settings(&self) -> Settings {
+        // Both are validated against the enum values, so they can always be parsed
+        Settings {
+            layout_type: self.get(LAYOUT_TYPE).as_str().and_then(|value| value.parse().ok()).unwrap_or(LayoutType::Mixed),
+            color_mode: self.get(COLOR_MODE).as_str().and_then(|value| value.parse().ok()).unwrap_or(ColorMode::LightMode),
+        }
+    }
+
+    /// Every setting except the layout and colour mode, which can be changed by schedules and screens
+    pub(crate) fn other(&self) -> BTreeMap<&'static str, Value> {
+        self.0.iter()
+            .filter(|(&key, _)| key != LAYOUT_TYPE && key != COLOR_MODE)
+            .map(|(&key, value)| (key, value.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_are_valid() {
+        for definition in SETTINGS {
+            assert_eq!(definition.validate(&definition.default_value()), Ok(()), "{}", definition.key);
+        }
+    }
+
+    #[test]
+    fn test_stored_values() {
+        let values = SettingValues::from_stored(vec![
+            ("color_mode".to_string(), "\"dark_mode\"".to_string()),
+            ("removed_setting".to_string(), "1".to_string()),
+        ]).unwrap();
+        assert_eq!(values.settings(), Settings { layout_type: LayoutType::Mixed, color_mode: ColorMode::DarkMode });
+        assert_eq!(values.get("slide_interval_seconds"), &Value::from(3));
+
+        assert!(SettingValues::from_stored(vec![("color_mode".to_string(), "\"neon\"".to_string())]).is_err());
+        assert!(SettingValues::from_stored(vec![("slide_interval_seconds".to_string(), "0".to_string())]).is_err());
+        assert!(SettingValues::from_stored(vec![("slide_interval_seconds".to_string(), "not json".to_string())]).is_err());
+    }
+}
@@ -0,0 +1,493 @@
+//! Fetches departures and deviations (disruptions like "Red line replaced by buses") from
+//! SL's APIs for the tracked sites, so the screens don't have to call SL themselves.
+//! Responses are cached per site for a short while, since every screen asks for the same data.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, FixedOffset, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::SlTrackedLine;
+
+const DEFAULT_SL_API_URL: &str = "https://transport.integration.sl.se/v1";
+const DEFAULT_SL_DEVIATIONS_URL: &str = "https://deviations.integration.sl.se/v1";
+// Departures are shown with minute precision, so there's no point in fetching more often than this
+const CACHE_TTL: Duration = Duration::from_secs(30);
+// Deviations are planned well ahead or change rarely once they're published
+const DEVIATIONS_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The transport modes SL uses
+pub(crate) const TRANSPORT_MODES: &[&str] = &["TRAIN", "METRO", "BUS", "TRAM", "FERRY", "SHIP", "TAXI"];
+
+// --- SL API responses, only the fields we use ---
+
+#[derive(Debug, Deserialize)]
+struct SlDeparturesResponse {
+    departures: Vec<SlDeparture>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SlDeparture {
+    destination: String,
+    via: Option<String>,
+    direction_code: i32,
+    direction: String,
+    state: String,
+    display: String,
+    scheduled: NaiveDateTime,
+    expected: NaiveDateTime,
+    journey: SlJourney,
+    stop_point: SlStopPoint,
+    line: SlLine,
+}
+
+#[derive(Debug, Deserialize)]
+struct SlJourney {
+    id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct SlStopPoint {
+    id: i64,
+    name: String,
+    designation: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SlLine {
+    id: i32,
+    designation: String,
+    transport_mode: String,
+    group_of_lines: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SlDeviation {
+    deviation_case_id: i64,
+    publish: SlPublish,
+    priority: SlPriority,
+    message_variants: Vec<SlMessageVariant>,
+    #[serde(default)]
+    scope: SlScope,
+}
+
+#[derive(Debug, Deserialize)]
+struct SlPublish {
+    from: DateTime<FixedOffset>,
+    upto: Option<DateTime<FixedOffset>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SlPriority {
+    importance_level: i32,
+}
+
+#[derive(Debug, Deserialize)]
+struct SlMessageVariant {
+    header: String,
+    details: Option<String>,
+    language: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SlScope {
+    #[serde(default)]
+    lines: Vec<SlScopeLine>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub(crate) struct SlScopeLine {
+    pub id: i32,
+    pub transport_mode: String,
+    pub designation: String,
+}
+
+/// A departure as sent to the screens. Times are local time, like SL sends them.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub(crate) struct Departure {
+    pub site_id: i32,
+    pub stop_point_id: i64,
+    pub stop_point_name: String,
+    pub stop_point_designation: String,
+    pub transport_mode: String,
+    /// Missing for most buses
+    pub line_group: Option<String>,
+    pub line_id: i32,
+    pub line_designation: String,
+    pub direction_code: i32,
+    pub direction: String,
+    pub destination: String,
+    pub via: Option<String>,
+    pub journey_id: i64,
+    pub scheduled_time: NaiveDateTime,
+    pub expected_time: NaiveDateTime,
+    pub state: String,
+    pub display_time: String,
+}
+
+impl Departure {
+    fn from_sl(site_id: i32, departure: SlDeparture) -> Self {
+        Departure {
+            site_id,
+            stop_point_id: departure.stop_point.id,
+            stop_point_name: departure.stop_point.name,
+            stop_point_designation: departure.stop_point.designation.unwrap_or_default(),
+            transport_mode: departure.line.transport_mode,
+            line_group: departure.line.group_of_lines,
+            line_id: departure.line.id,
+            line_designation: departure.line.designation,
+            direction_code: departure.direction_code,
+            direction: departure.direction,
+            destination: departure.destination,
+            via: departure.via,
+            journey_id: departure.journey.id,
+            scheduled_time: departure.scheduled,
+            expected_time: departure.expected,
+            state: departure.state,
+            display_time: departure.display,
+        }
+    }
+
+    /// No tracked lines means every departure from the site is shown
+    pub(crate) fn is_tracked(&self, tracked_lines: &[SlTrackedLine]) -> bool {
+        tracked_lines.is_empty() || tracked_lines.iter().any(|line| {
+            line.transport_mode == self.transport_mode
+                && line.line_id == self.line_id
+                && line.direction_code.is_none_or(|direction| direction == self.direction_code)
+        })
+    }
+}
+
+/// How much a deviation affects travellers, from SL's importance level (1-9)
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Severity {
+    Low,
+    Medium,
+    High,
+}
+
+/// A deviation as sent to the screens
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub(crate) struct Deviation {
+    /// SL's id, the same for every version of the message
+    pub id: i64,
+    pub header: String,
+    pub details: Option<String>,
+    pub severity: Severity,
+    pub importance_level: i32,
+    pub valid_from: DateTime<Utc>,
+    pub valid_until: Option<DateTime<Utc>>,
+    /// Empty if the deviation isn't about specific lines, like a broken lift
+    pub lines: Vec<SlScopeLine>,
+}
+
+impl Deviation {
+    fn from_sl(deviation: SlDeviation) -> Option<Self> {
+        // The screens are in Swedish, but some messages only have an English variant
+        let mut variants = deviation.message_variants;
+        let index = variants.iter().position(|variant| variant.language == "sv").unwrap_or(0);
+        if variants.is_empty() {
+            return None;
+        }
+        let message = variants.swap_remove(index);
+
+        let importance_level = deviation.priority.importance_level;
+        Some(Deviation {
+            id: deviation.deviation_case_id,
+            header: message.header,
+            details: message.details,
+            severity: match importance_level {
+                7.. => Severity::High,
+                4..=6 => Severity::Medium,
+                _ => Severity::Low,
+            },
+            importance_level,
+            valid_from: deviation.publish.from.with_timezone(&Utc),
+            valid_until: deviation.publish.upto.map(|upto| upto.with_timezone(&Utc)),
+            lines: deviation.scope.lines,
+        })
+    }
+
+    pub(crate) fn is_valid_at(&self, now: DateTime<Utc>) -> bool {
+        self.valid_from <= now && self.valid_until.is_none_or(|until| now < until)
+    }
+
+    /// Deviations that aren't about specific lines are always shown, like the departures
+    /// of a site without tracked lines
+    pub(crate) fn is_tracked(&self, tracked_lines: &[SlTrackedLine]) -> bool {
+        tracked_lines.is_empty() || self.lines.is_empty() || self.lines.iter().any(|line| {
+            tracked_lines.iter().any(|tracked| tracked.transport_mode == line.transport_mode && tracked.line_id == line.id)
+        })
+    }
+}
+
+type Cache<T> = Mutex<HashMap<i32, (Instant, Vec<T>)>>;
+
+fn cached<T: Clone>(cache: &Cache<T>, site_id: i32, ttl: Duration) -> Option<Vec<T>> {
+    match cache.lock().unwrap().get(&site_id) {
+        Some((fetched_at, values)) if fetched_at.elapsed() < ttl => Some(values.clone()),
+        _ => None,
+    }
+}
+
+pub(crate) struct SlClient {
+    http: reqwest::Client,
+    base_url: String,
+    deviations_url: String,
+    cache: Cache<Departure>,
+    deviations_cache: Cache<Deviation>,
+}
+
+impl SlClient {
+    pub(crate) fn new(base_url: String, deviations_url: String) -> Self {
+        SlClient {
+            http: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .expect("Failed to build HTTP client"),
+            base_url,
+            deviations_url,
+            cache: Mutex::new(HashMap::new()),
+            deviations_cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Uses `SL_API_URL` and `SL_DEVIATIONS_URL` if set, so tests and development can use a mock of the SL APIs
+    pub(crate) fn from_env() -> Self {
+        Self::new(
+            std::env::var("SL_API_URL").unwrap_or_else(|_| DEFAULT_SL_API_URL.to_string()),
+            std::env::var("SL_DEVIATIONS_URL").unwrap_or_else(|_| DEFAULT_SL_DEVIATIONS_URL.to_string()),
+        )
+    }
+
+    async fn fetch(&self, url: &str, what: &str) -> Result<String, String> {
+        let response = self.http.get(url).send().await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("Failed to fetch {what}: {e}"))?;
+        response.text().await.map_err(|e| format!("Failed to read {what}: {e}"))
+    }
+
+    pub(crate) async fn departures(&self, site_id: i32) -> Result<Vec<Departure>, String> {
+        if let Some(departures) = cached(&self.cache, site_id, CACHE_TTL) {
+            return Ok(departures);
+        }
+
+        let url = format!("{}/sites/{}/departures", self.base_url, site_id);
+        let body = self.fetch(&url, &format!("departures for site {site_id}")).await?;
+        let parsed: SlDeparturesResponse = serde_json::from_str(&body)
+            .map_err(|e| format!("Failed to parse departures for site {site_id}: {e}"))?;
+
+        let departures: Vec<_> = parsed.departures.into_iter()
+            .map(|departure| Departure::from_sl(site_id, departure))
+            .collect();
+        self.cache.lock().unwrap().insert(site_id, (Instant::now(), departures.clone()));
+        Ok(departures)
+    }
+
+    /// Current and upcoming deviations at a site, including those for lines stopping there
+    pub(crate) async fn deviations(&self, site_id: i32) -> Result<Vec<Deviation>, String> {
+        if let Some(deviations) = cached(&self.deviations_cache, site_id, DEVIATIONS_CACHE_TTL) {
+            return Ok(deviations);
+        }
+
+        let url = format!("{}/messages?site={}", self.deviations_url, site_id);
+        let body = self.fetch(&url, &format!("deviations for site {site_id}")).await?;
+        let parsed: Vec<SlDeviation> = serde_json::from_str(&body)
+            .map_err(|e| format!("Failed to parse deviations for site {site_id}: {e}"))?;
+
+        let deviations: Vec<_> = parsed.into_iter().filter_map(Deviation::from_sl).collect();
+        self.deviations_cache.lock().unwrap().insert(site_id, (Instant::now(), deviations.clone()));
+        Ok(deviations)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use actix_web::{get, web, App, HttpResponse, HttpServer};
+
+    pub(crate) const DEPARTURES_FIXTURE: &str = r#"{
+        "departures": [
+            {
+                "destination": "Mörby centrum",
+                "direction_code": 1,
+                "direction": "Mörby centrum",
+                "state": "EXPECTED",
+                "display": "3 min",
+                "scheduled": "2025-11-07T12:03:00",
+                "expected": "2025-11-07T12:03:30",
+                "journey": { "id": 2025110712030 },
+                "stop_point": { "id": 2221, "name": "Tekniska högskolan", "designation": "1" },
+                "line": { "id": 14, "designation": "14", "transport_mode": "METRO", "group_of_lines": "Tunnelbanans röda linje" }
+            },
+            {
+                "destination": "Fruängen",
+                "direction_code": 2,
+                "direction": "Fruängen",
+                "state": "EXPECTED",
+                "display": "5 min",
+                "scheduled": "2025-11-07T12:05:00",
+                "expected": "2025-11-07T12:05:00",
+                "journey": { "id": 2025110712050 },
+                "stop_point": { "id": 2222, "name": "Tekniska högskolan" },
+                "line": { "id": 14, "designation": "14", "transport_mode": "METRO", "group_of_lines": "Tunnelbanans röda linje" }
+            },
+            {
+                "destination": "Gärdet",
+                "direction_code": 1,
+                "direction": "Gärdet",
+                "state": "ATSTOP",
+                "display": "Nu",
+                "scheduled": "2025-11-07T12:00:00",
+                "expected": "2025-11-07T12:00:00",
+                "journey": { "id": 2025110712000 },
+                "stop_point": { "id": 10100, "name": "Tekniska högskolan" },
+                "line": { "id": 4, "designation": "4", "transport_mode": "BUS" }
+            }
+        ]
+    }"#;
+
+    pub(crate) const DEVIATIONS_FIXTURE: &str = r#"[
+        {
+            "version": 2,
+            "deviation_case_id": 1001,
+            "publish": { "from": "2025-11-01T00:00:00.000+01:00", "upto": "2035-11-30T23:59:00.000+01:00" },
+            "priority": { "importance_level": 8, "influence_level": 8, "urgency_level": 8 },
+            "message_variants": [
+                { "header": "Line 14 replaced by buses", "details": "Because of track work", "language": "en" },
+                { "header": "Röda linjen ersätts av bussar", "details": "På grund av spårarbete", "language": "sv" }
+            ],
+            "scope": { "lines": [{ "id": 14, "transport_mode": "METRO", "designation": "14" }] }
+        },
+        {
+            "version": 1,
+            "deviation_case_id": 1002,
+            "publish": { "from": "2025-11-01T00:00:00.000+01:00" },
+            "priority": { "importance_level": 2, "influence_level": 2, "urgency_level": 2 },
+            "message_variants": [{ "header": "Hissen ur funktion", "language": "sv" }],
+            "scope": { "stop_areas": [{ "id": 1001, "name": "Tekniska högskolan" }] }
+        },
+        {
+            "version": 1,
+            "deviation_case_id": 1003,
+            "publish": { "from": "2025-11-01T00:00:00.000+01:00" },
+            "priority": { "importance_level": 5, "influence_level": 5, "urgency_level": 5 },
+            "message_variants": [{ "header": "Buss 4 går inte till Gärdet", "language": "sv" }],
+            "scope": { "lines": [{ "id": 4, "transport_mode": "BUS", "designation": "4" }] }
+        },
+        {
+            "version": 3,
+            "deviation_case_id": 1004,
+            "publish": { "from": "2025-10-01T00:00:00.000+02:00", "upto": "2025-10-31T23:59:00.000+01:00" },
+            "priority": { "importance_level": 9, "influence_level": 9, "urgency_level": 9 },
+            "message_variants": [{ "header": "Tunnelbanan står still", "language": "sv" }],
+            "scope": {}
+        }
+    ]"#;
+
+    #[derive(Deserialize)]
+    struct MessagesQuery {
+        site: i32,
+    }
+
+    #[get("/messages")]
+    async fn mock_deviations(query: web::Query<MessagesQuery>, requests: web::Data<Arc<AtomicUsize>>) -> HttpResponse {
+        requests.fetch_add(1, Ordering::SeqCst);
+        match query.site {
+            9204 => HttpResponse::Ok().content_type("application/json").body(DEVIATIONS_FIXTURE),
+            _ => HttpResponse::Ok().content_type("application/json").body("[]"),
+        }
+    }
+
+    #[get("/sites/{site_id}/departures")]
+    async fn mock_departures(path: web::Path<i32>, requests: web::Data<Arc<AtomicUsize>>) -> HttpResponse {
+        requests.fetch_add(1, Ordering::SeqCst);
+        match path.into_inner() {
+            9204 => HttpResponse::Ok().content_type("application/json").body(DEPARTURES_FIXTURE),
+            _ => HttpResponse::NotFound().finish(),
+        }
+    }
+
+    /// Starts a mock of the SL APIs on a random port, serving both departures and deviations.
+    /// Returns its URL and a count of the requests it got.
+    pub(crate) fn start_mock_sl_api() -> (String, Arc<AtomicUsize>) {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let data = requests.clone();
+        let server = HttpServer::new(move || App::new().app_data(web::Data::new(data.clone())).service(mock_departures).service(mock_deviations))
+            .workers(1)
+            .bind(("127.0.0.1", 0))
+            .unwrap();
+        let url = format!("http://{}", server.addrs()[0]);
+        actix_web::rt::spawn(server.run());
+        (url, requests)
+    }
+
+    fn tracked_line(line_id: i32, direction_code: Option<i32>) -> SlTrackedLine {
+        SlTrackedLine { site_id: 9204, transport_mode: "METRO".to_string(), line_id, direction_code }
+    }
+
+    #[actix_web::test]
+    async fn test_departures_are_cached() {
+        let (url, requests) = start_mock_sl_api();
+        let client = SlClient::new(url.clone(), url);
+
+        let departures = client.departures(9204).await.unwrap();
+        assert_eq!(departures.len(), 3);
+        assert_eq!(departures[0].line_group.as_deref(), Some("Tunnelbanans röda linje"));
+        assert_eq!(departures[1].stop_point_designation, "");
+        assert_eq!(departures[2].line_group, None);
+
+        // The second call is served from the cache
+        assert_eq!(client.departures(9204).await.unwrap(), departures);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        assert!(client.departures(1).await.is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_tracked_lines() {
+        let (url, _) = start_mock_sl_api();
+        let departures = SlClient::new(url.clone(), url).departures(9204).await.unwrap();
+        let tracked = |lines: &[SlTrackedLine]| departures.iter().filter(|d| d.is_tracked(lines)).count();
+
+        assert_eq!(tracked(&[]), 3);
+        assert_eq!(tracked(&[tracked_line(14, None)]), 2);
+        assert_eq!(tracked(&[tracked_line(14, Some(1))]), 1);
+        // Line 4 is a bus, not a metro
+        assert_eq!(tracked(&[tracked_line(4, None)]), 0);
+    }
+
+    #[actix_web::test]
+    async fn test_deviations() {
+        let (url, requests) = start_mock_sl_api();
+        let client = SlClient::new(url.clone(), url);
+
+        let deviations = client.deviations(9204).await.unwrap();
+        assert_eq!(deviations.len(), 4);
+        // The Swedish variant is used
+        assert_eq!(deviations[0].header, "Röda linjen ersätts av bussar");
+        assert_eq!(deviations[0].severity, Severity::High);
+        assert_eq!(deviations[1].severity, Severity::Low);
+        assert_eq!(deviations[1].valid_until, None);
+
+        let now: DateTime<Utc> = "2025-11-07T12:00:00Z".parse().unwrap();
+        assert!(deviations[0].is_valid_at(now));
+        assert!(!deviations[0].is_valid_at("2035-12-01T12:00:00Z".parse().unwrap()));
+        assert!(!deviations[3].is_valid_at(now));
+
+        // Only the red line is tracked, but deviations that aren't about a line are kept
+        let tracked: Vec<_> = deviations.iter().filter(|d| d.is_tracked(&[tracked_line(14, Some(1))])).map(|d| d.id).collect();
+        assert_eq!(tracked, vec![1001, 1002, 1004]);
+
+        client.deviations(9204).await.unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+}
@@ -1,4 +1,4 @@
-import { useEffect, useState } from 'react'
+import { useEffect, useRef, useState } from 'react'
 
 import '../index.css';
 import '../styles/App.css';
@@ -6,52 +6,54 @@ import '../styles/App.css';
 import { SlideData } from '../types/slides/SlideData.ts'
 
 import SlData from "../types/sl/SlData.ts";
+import { CalendarEvent } from "../types/events/CalendarEvent.ts";
 
 import { LayoutType, ColorMode } from '../types/settings/settings-types.ts'
 import { Settings } from '../types/settings/Settings.ts';
 import FullscreenSlideshowLayout from './layouts/FullscreenSlideshowLayout.tsx';
 import MixedLayout from './layouts/MixedLayout.tsx';
+import OverrideLayout from './layouts/OverrideLayout.tsx';
+import QuietLayout from './layouts/QuietLayout.tsx';
+import { ScreenOverride } from '../types/override/ScreenOverride.ts';
+import { ScreenApi } from '../types/device/Device.ts';
+import { reportContentLoaded } from '../util/health.ts';
+import { pollCommands } from '../util/commands.ts';
+import { openEventStream } from '../util/eventStream.ts';
 
-function App() {
+function App({ api }: { api: ScreenApi }) {
   // const BASE_URL = 'http://localhost:8080'; // replace with 'http://f.kth.se/konsol'
 
   const [slides, setSlides] = useState<SlideData[]>([]);
 
-  const [sl, setSl] = useState<{ data: SlData }>({data: new SlData([
-        { site_id: 9204, tracked_lines: undefined }, // tekniska högskolan
-        { site_id: 9600, tracked_lines: undefined }, // östra station
-        // { site_id: 1080, tracked_lines: undefined }  // stockholm city
-    ])});
+  // The tracked SL sites are configured in the backend
+  const [sl, setSl] = useState<{ data: SlData }>({data: new SlData(api.base, api.headers)});
 
   const [settings, setSettings] = useState<Settings>(new Settings(
     LayoutType.Mixed, 
     ColorMode.DarkMode
   ));
 
-  useEffect(() => {
-    fetch(`${import.meta.env.VITE_API_BASE_URL}/screen/slides`)
+  // Upcoming events from the calendar feeds configured in the backend
+  const [events, setEvents] = useState<CalendarEvent[]>([]);
+
+  // An active override replaces all other content
+  const [screenOverride, setScreenOverride] = useState<ScreenOverride | null>(null);
+
+  const fetchSlides = () => {
+    fetch(`${api.base}/slides`, { headers: api.headers })
       .then(response => response.json())
       .then(data => {
         setSlides(data);
+        reportContentLoaded();
         console.log(data);
       })
       .catch(error => {
         console.error('Error fetching slides:', error)
       })
-  }, []);
-
-  useEffect(() => {
-    const handle = setInterval(() => {
-      sl.data.update().then(() => {
-        setSl({data: sl.data});
-      });
-    },
-      5 * 1000)
-    return () => clearInterval(handle);
-  }, []);
+  };
 
-  useEffect(() => {
-    fetch(`${import.meta.env.VITE_API_BASE_URL}/screen/settings`)
+  const fetchSettings = () => {
+    fetch(`${api.base}/settings`, { headers: api.headers })
       .then(response => response.json())
       .then(data => {
         setSettings(data);
@@ -59,13 +61,125 @@ function App() {
       .catch(error => {
         console.error('Error fetching settings:', error)
       })
+  };
+
+  const fetchEvents = () => {
+    fetch(`${import.meta.env.VITE_API_BASE_URL}/screen/events`, { headers: api.headers })
+      .then(response => response.json())
+      .then(data => {
+        setEvents(data);
+      })
+      .catch(error => {
+        console.error('Error fetching events:', error)
+      })
+  };
+
+  const fetchOverride = () => {
+    fetch(`${import.meta.env.VITE_API_BASE_URL}/screen/override`, { headers: api.headers })
+      // 204 No Content means there is no active override
+      .then(response => response.status === 204 ? null : response.json())
+      .then(data => {
+        setScreenOverride(data);
+      })
+      .catch(error => {
+        console.error('Error fetching override:', error)
+      })
+  };
+
+  useEffect(() => {
+    fetchSlides();
+    fetchSettings();
+    fetchOverride();
+  }, []);
+
+  // The backend doesn't push anything when an override expires, so we refetch at expiry
+  useEffect(() => {
+    if (screenOverride === null) return;
+    const handle = setTimeout(() => {
+      fetchOverride();
+      fetchSlides();
+    }, new Date(screenOverride.expires_at).getTime() - Date.now());
+    return () => clearTimeout(handle);
+  }, [screenOverride]);
+
+  // Settings schedules change the settings without any event being pushed, so we refetch when they do
+  useEffect(() => {
+    if (!settings.next_change) return;
+    const handle = setTimeout(fetchSettings, new Date(settings.next_change).getTime() - Date.now());
+    return () => clearTimeout(handle);
+  }, [settings]);
+
+  // The backend doesn't serve slides during quiet hours, so they have to be refetched afterwards
+  const wasQuiet = useRef(false);
+  useEffect(() => {
+    if (wasQuiet.current && !settings.quiet) fetchSlides();
+    wasQuiet.current = settings.quiet === true;
+  }, [settings.quiet]);
+
+  // The backend pushes an event whenever slides or settings change, so we refetch them
+  useEffect(() => {
+    const resync = () => {
+      fetchSlides();
+      fetchSettings();
+      fetchOverride();
+    };
+    // The stream reconnects by itself, but we might have missed events while disconnected
+    let connected_before = false;
+    return openEventStream(`${import.meta.env.VITE_API_BASE_URL}/screen/stream`, api.headers, {
+      slides: fetchSlides,
+      settings: fetchSettings,
+      override: (data) => {
+        // The override is sent along with the event, but the slides it shows have to be refetched
+        setScreenOverride(JSON.parse(data).active);
+        fetchSlides();
+      },
+      commands: (data) => {
+        if (JSON.parse(data).screen_id === api.device.screen_id) {
+          pollCommands(api.device.token);
+        }
+      },
+      resync,
+    }, () => {
+      if (connected_before) resync();
+      connected_before = true;
+    });
+  }, []);
+
+  // The backend fetches the calendar feeds every 15 minutes, so there's no point in asking more often.
+  // Refetching also drops events that have ended.
+  useEffect(() => {
+    fetchEvents();
+    const handle = setInterval(fetchEvents, 5 * 60 * 1000);
+    return () => clearInterval(handle);
   }, []);
 
+  // Commands queued while the event stream was down are picked up by polling
+  useEffect(() => {
+    const token = api.device.token;
+    pollCommands(token);
+    const handle = setInterval(() => pollCommands(token), 60 * 1000);
+    return () => clearInterval(handle);
+  }, []);
+
+  useEffect(() => {
+    const handle = setInterval(() => {
+      sl.data.update().then(() => {
+        setSl({data: sl.data});
+      });
+    },
+      (settings.sl_refresh_seconds ?? 5) * 1000)
+    return () => clearInterval(handle);
+  }, [settings.sl_refresh_seconds]);
+
   return <> {
+    screenOverride !== null ?
+      <OverrideLayout screen_override={screenOverride} slides={slides} slide_interval_seconds={settings.slide_interval_seconds}/> :
+    settings.quiet ?
+      <QuietLayout/> :
     settings.layout_type === LayoutType.FullscreenSlideshow ? 
-      <FullscreenSlideshowLayout slides={slides}/> :
+      <FullscreenSlideshowLayout slides={slides} slide_interval_seconds={settings.slide_interval_seconds}/> :
     settings.layout_type === LayoutType.Mixed ?
-      <MixedLayout slides={slides} sl_data={sl.data}/> :
+      <MixedLayout slides={slides} sl_data={sl.data} events={events} slide_interval_seconds={settings.slide_interval_seconds} show_clock={settings.show_clock}/> :
     (() => {throw new Error("Invalid layout type");})()
   } </>;
 }
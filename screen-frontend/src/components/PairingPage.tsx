@@ -0,0 +1,19 @@
+import '../styles/PairingPage.css';
+
+/**
+ * Shown on a kiosk that hasn't been paired to a screen yet
+ */
+function PairingPage({pairing_code}: {pairing_code: string | null}) {
+  return <div className="pairing">
+    <h1>Waiting for pairing</h1>
+    {pairing_code === null ?
+      <p>Requesting a pairing code...</p> :
+      <>
+        <p>Enter this code in the admin page to pair this screen:</p>
+        <p className="pairing-code">{pairing_code}</p>
+      </>
+    }
+  </div>;
+}
+
+export default PairingPage;
@@ -0,0 +1,109 @@
+import { useEffect, useState } from 'react';
+
+import App from './App.tsx';
+import PairingPage from './PairingPage.tsx';
+import { DeviceStatus, DEVICE_TOKEN_KEY } from '../types/device/Device.ts';
+import { startHeartbeat } from '../util/health.ts';
+import { startPlayReporting } from '../util/plays.ts';
+import { IDENTIFY_EVENT } from '../util/commands.ts';
+import '../styles/ScreenRoot.css';
+
+// Every kiosk has to be paired to a screen by an admin before it gets any content
+const STATUS_POLL_INTERVAL = 10 * 1000;
+const IDENTIFY_DURATION = 10 * 1000;
+
+function ScreenRoot() {
+  const [token, setToken] = useState<string | null>(localStorage.getItem(DEVICE_TOKEN_KEY));
+  const [status, setStatus] = useState<DeviceStatus | null>(null);
+  // Bumped to ask for a pairing code again, e.g. when too many kiosks are already waiting
+  const [pairingAttempt, setPairingAttempt] = useState(0);
+  // Set by the `identify` command, shows the device name on top of everything
+  const [identifying, setIdentifying] = useState(false);
+
+  useEffect(() => {
+    let handle: ReturnType<typeof setTimeout> | undefined;
+    const identify = () => {
+      setIdentifying(true);
+      clearTimeout(handle);
+      handle = setTimeout(() => setIdentifying(false), IDENTIFY_DURATION);
+    };
+    window.addEventListener(IDENTIFY_EVENT, identify);
+    return () => {
+      window.removeEventListener(IDENTIFY_EVENT, identify);
+      clearTimeout(handle);
+    };
+  }, []);
+
+  useEffect(() => {
+    if (token !== null) return;
+    let retry: ReturnType<typeof setTimeout> | undefined;
+    fetch(`${import.meta.env.VITE_API_BASE_URL}/devices/pair`, { method: 'POST' })
+      .then(response => {
+        if (!response.ok) {
+          throw new Error(`Pairing request failed with ${response.status}`);
+        }
+        return response.json();
+      })
+      .then(data => {
+        localStorage.setItem(DEVICE_TOKEN_KEY, data.token);
+        setToken(data.token);
+      })
+      .catch(error => {
+        console.error('Error requesting pairing:', error)
+        retry = setTimeout(() => setPairingAttempt(attempt => attempt + 1), STATUS_POLL_INTERVAL);
+      });
+    return () => clearTimeout(retry);
+  }, [token, pairingAttempt]);
+
+  // Keeps polling after pairing so a revoked device goes back to the pairing page
+  useEffect(() => {
+    if (token === null) return;
+    const poll = () => {
+      fetch(`${import.meta.env.VITE_API_BASE_URL}/device/status`, {
+        headers: { Authorization: `Bearer ${token}` }
+      })
+        .then(response => {
+          if (response.status === 401) {
+            // Revoked, or the pairing code expired. Forget the token to get a new code.
+            localStorage.removeItem(DEVICE_TOKEN_KEY);
+            setToken(null);
+            setStatus(null);
+            return;
+          }
+          return response.json().then(data => setStatus(data));
+        })
+        .catch(error => {
+          console.error('Error fetching device status:', error)
+        });
+    };
+    poll();
+    const handle = setInterval(poll, STATUS_POLL_INTERVAL);
+    return () => clearInterval(handle);
+  }, [token]);
+
+  const paired = status?.state === 'paired';
+  useEffect(() => {
+    if (!paired || token === null) return;
+    const stopHeartbeat = startHeartbeat(token);
+    const stopPlayReporting = startPlayReporting(token);
+    return () => {
+      stopHeartbeat();
+      stopPlayReporting();
+    };
+  }, [paired, token]);
+
+  if (status === null || status.state === 'pending' || token === null) {
+    return <PairingPage pairing_code={status?.state === 'pending' ? status.pairing_code : null} />;
+  }
+
+  return <>
+    <App api={{
+      base: `${import.meta.env.VITE_API_BASE_URL}/screens/${status.screen_id}`,
+      headers: { Authorization: `Bearer ${token}` },
+      device: { token, screen_id: status.screen_id },
+    }} />
+    {identifying && <div className="identify-overlay">{status.name}</div>}
+  </>;
+}
+
+export default ScreenRoot;
@@ -0,0 +1,39 @@
+import React from "react";
+
+import { CalendarEvent } from "../../types/events/CalendarEvent.ts";
+import '../../styles/events/EventList.css';
+
+const formatDay = (date: Date) => date.toLocaleDateString('sv-SE', { weekday: 'long', day: 'numeric', month: 'long' });
+const formatTime = (date: Date) => date.toLocaleTimeString('sv-SE', { hour: '2-digit', minute: '2-digit' });
+
+// Upcoming events grouped by the day they start, replacing the embedded Google calendar
+const EventList: React.FC<{events: CalendarEvent[]}> = ({events}) => {
+    const days = new Map<string, CalendarEvent[]>();
+    for (const event of events) {
+        // Events that are already going on are shown under today
+        const start = new Date(Math.max(new Date(event.start).getTime(), Date.now()));
+        const day = formatDay(start);
+        days.set(day, [...(days.get(day) ?? []), event]);
+    }
+
+    return <div className="event-list">
+        <h2>Kommande evenemang</h2>
+        {events.length === 0 && <p className="event-list-empty">Inga kommande evenemang</p>}
+        {[...days.entries()].map(([day, dayEvents]) =>
+            <div key={day} className="event-day">
+                <h3>{day}</h3>
+                {dayEvents.map(event =>
+                    <div key={`${event.uid}-${event.start}`} className="event">
+                        <p className="event-time">
+                            {event.all_day ? "Heldag" : `${formatTime(new Date(event.start))}–${formatTime(new Date(event.end))}`}
+                        </p>
+                        <p className="event-title">{event.title}</p>
+                        {event.location && <p className="event-location">{event.location}</p>}
+                    </div>
+                )}
+            </div>
+        )}
+    </div>;
+};
+
+export default EventList;
@@ -1,9 +1,9 @@
 import { SlideData } from "../../types/slides/SlideData";
 import Slideshow from "../slides/Slideshow"
 
-function FullscreenSlideshowLayout({slides}: {slides: SlideData[]}) {
+function FullscreenSlideshowLayout({slides, slide_interval_seconds}: {slides: SlideData[], slide_interval_seconds?: number}) {
   return <>
-    <Slideshow slides={slides} />
+    <Slideshow slides={slides} interval_seconds={slide_interval_seconds} />
   </>;
 }
 
@@ -2,14 +2,32 @@ import SlData from "../../types/sl/SlData";
 import { useState, useEffect } from "react";
 import { SlideData } from "../../types/slides/SlideData";
 import SlDepartureList from "../sl/SlDepartureList";
+import SlDeviationList from "../sl/SlDeviationList";
 import Slideshow from "../slides/Slideshow";
+import EventList from "../events/EventList";
+import { CalendarEvent } from "../../types/events/CalendarEvent";
 import '../../styles/layouts/MixedLayout.css';
 import fysikF from '../../assets/FrakturF2020.png';
 
 
-function MixedLayout({slides, sl_data}: {slides: SlideData[], sl_data: SlData}) {
+type Props = {
+  slides: SlideData[];
+  sl_data: SlData;
+  events: CalendarEvent[];
+  slide_interval_seconds?: number;
+  show_clock?: boolean;
+}
+
+function MixedLayout({slides, sl_data, events, slide_interval_seconds, show_clock}: Props) {
 
 const [active, setActive] = useState("slide");
+const [now, setNow] = useState(new Date());
+
+  useEffect(() => {
+    if (!show_clock) return;
+    const interval = setInterval(() => setNow(new Date()), 10 * 1000);
+    return () => clearInterval(interval);
+  }, [show_clock]);
 
   useEffect(() => {
     const interval = setInterval(() => {
@@ -22,24 +40,22 @@ const [active, setActive] = useState("slide");
     <div className="header">
       <img src={fysikF} alt="Fraktur F" className="fysikf"/>
       <h1>KONSol</h1>
+      {show_clock && <p className="clock">{now.toLocaleTimeString('sv-SE', { hour: '2-digit', minute: '2-digit' })}</p>}
     </div>
     
     <div className='left'> {
       (active === "slide" && slides.length > 0) ? (
-        <Slideshow slides={slides} />
+        <Slideshow slides={slides} interval_seconds={slide_interval_seconds} />
       ) : (
         <div className="calendar-container">
-          <iframe
-            className="calendar-frame"
-            src="https://calendar.google.com/calendar/embed?height=600&wkst=2&ctz=Europe%2FStockholm&showPrint=0&showNav=0&mode=AGENDA&hl=sv&title=Fysiksektionens%20kalender&showTabs=0&showCalendars=0&showTz=0&showDate=0&src=ZnlzaWtzZWt0aW9uZW4uc2VfMDE4N3ZibWRjaXZsOG10aW8xNDJlMjNjYXNAZ3JvdXAuY2FsZW5kYXIuZ29vZ2xlLmNvbQ&color=%23FF642B"
-            scrolling="no">
-          </iframe>
+          <EventList events={events}/>
         </div>
       )
     } </div>
 
     <div className="right">
       <p className="last-update">{`Senast uppdaterad: ${sl_data.last_update ? sl_data.last_update.toLocaleTimeString() : "Aldrig"}`}</p>
+      <SlDeviationList sl_data={sl_data}/>
       <SlDepartureList sl_data={sl_data}/>
     </div>
   </>;
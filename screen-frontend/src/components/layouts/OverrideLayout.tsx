@@ -0,0 +1,14 @@
+import { ScreenOverride } from "../../types/override/ScreenOverride";
+import { SlideData } from "../../types/slides/SlideData";
+import Slideshow from "../slides/Slideshow";
+import '../../styles/layouts/OverrideLayout.css';
+
+function OverrideLayout({screen_override, slides, slide_interval_seconds}: {screen_override: ScreenOverride, slides: SlideData[], slide_interval_seconds?: number}) {
+  // While an override is active, the backend only returns the override's slide
+  return <div className="override">
+    {screen_override.message && <h1 className="override-message">{screen_override.message}</h1>}
+    {screen_override.slide_id && <Slideshow slides={slides} interval_seconds={slide_interval_seconds} />}
+  </div>;
+}
+
+export default OverrideLayout;
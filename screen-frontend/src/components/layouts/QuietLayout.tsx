@@ -0,0 +1,18 @@
+import { useEffect, useState } from "react";
+import '../../styles/layouts/QuietLayout.css';
+
+// Shown during quiet hours instead of slides and departures
+function QuietLayout() {
+  const [now, setNow] = useState(new Date());
+
+  useEffect(() => {
+    const interval = setInterval(() => setNow(new Date()), 10 * 1000);
+    return () => clearInterval(interval);
+  }, []);
+
+  return <div className="quiet">
+    <p className="quiet-clock">{now.toLocaleTimeString('sv-SE', { hour: '2-digit', minute: '2-digit' })}</p>
+  </div>;
+}
+
+export default QuietLayout;
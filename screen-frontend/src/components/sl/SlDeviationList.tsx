@@ -0,0 +1,17 @@
+import React from "react";
+
+import SlData from "../../types/sl/SlData.ts";
+import '../../styles/sl/SlDeviationList.css';
+
+const SlDeviationList: React.FC<{sl_data: SlData}> = ({sl_data}) => {
+    return <div className="sl-deviation-list">
+        {sl_data.deviations.map(deviation =>
+            <div key={deviation.id} className={`sl-deviation sl-deviation-${deviation.severity}`}>
+                <p className="sl-deviation-header">{deviation.header}</p>
+                {deviation.severity !== "low" && deviation.details && <p className="sl-deviation-details">{deviation.details}</p>}
+            </div>
+        )}
+    </div>;
+};
+
+export default SlDeviationList;
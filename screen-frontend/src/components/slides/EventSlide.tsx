@@ -0,0 +1,18 @@
+import { CalendarEvent } from '../../types/events/CalendarEvent.ts';
+
+import '../../styles/slides/EventSlide.css';
+
+// The slide generated for a calendar event that doesn't have a poster. The title is already shown as the caption.
+const EventSlide = ({ event }: { event: CalendarEvent }) => {
+  const start = new Date(event.start);
+  const day = start.toLocaleDateString('sv-SE', { weekday: 'long', day: 'numeric', month: 'long' });
+  const time = start.toLocaleTimeString('sv-SE', { hour: '2-digit', minute: '2-digit' });
+
+  return <div className='event-slide'>
+    <p className='event-slide-when'>{event.all_day ? day : `${day} kl. ${time}`}</p>
+    {event.location && <p className='event-slide-where'>{event.location}</p>}
+    {event.description && <p className='event-slide-description'>{event.description}</p>}
+  </div>;
+};
+
+export default EventSlide;
@@ -3,12 +3,17 @@ import { useEffect, useState } from 'react';
 import '../../styles/slides/Slideshow.css';
 
 import { SlideData } from '../../types/slides/SlideData.ts';
+import EventSlide from './EventSlide.tsx';
+import { reportCurrentSlide } from '../../util/health.ts';
+import { reportPlay } from '../../util/plays.ts';
+import { JUMP_TO_SLIDE_EVENT } from '../../util/commands.ts';
 
 type Props = {
     slides: SlideData[];
+    interval_seconds?: number;
 }
 
-const Slideshow = ({ slides }: Props) => {
+const Slideshow = ({ slides, interval_seconds = 3 }: Props) => {
     const [currentIndex, setCurrentIndex] = useState(0);
 
     const goToNextSlide = () => {
@@ -21,9 +26,30 @@ const Slideshow = ({ slides }: Props) => {
 
     
     useEffect(() => {
-        const interval = setInterval(goToNextSlide, 3000);
+        const interval = setInterval(goToNextSlide, interval_seconds * 1000);
         return () => clearInterval(interval);
+    }, [slides, interval_seconds]);
+
+    // Sent by the `jump_to_slide` command
+    useEffect(() => {
+        const jump = (event: Event) => {
+            const index = slides.findIndex(slide => slide.id === (event as CustomEvent<string>).detail);
+            if (index !== -1) setCurrentIndex(index);
+        };
+        window.addEventListener(JUMP_TO_SLIDE_EVENT, jump);
+        return () => window.removeEventListener(JUMP_TO_SLIDE_EVENT, jump);
     }, [slides]);
+
+    useEffect(() => {
+        if (slides.length === 0) {
+            reportCurrentSlide(null);
+            return;
+        }
+        const slide_id = slides[currentIndex % slides.length].id;
+        const started_at = new Date();
+        reportCurrentSlide(slide_id);
+        return () => reportPlay(slide_id, started_at);
+    }, [slides, currentIndex]);
     
     if (slides.length === 0) {
         return <div>Loading slides...</div>;
@@ -33,8 +59,12 @@ const Slideshow = ({ slides }: Props) => {
     <>
         <div key={slides[currentIndex].id}>
           <h2>{slides[currentIndex].caption}</h2>
-          {/* TODO: use env var instead of hardcoded link, also https */}
-          <img className='slide-image' src={`${import.meta.env.VITE_API_BASE_URL}/screen/slides/images/${slides[currentIndex].id}.${slides[currentIndex].filetype}`} alt={slides[currentIndex].caption} />
+          {slides[currentIndex].event && !slides[currentIndex].filetype ? (
+            <EventSlide event={slides[currentIndex].event}/>
+          ) : (
+            // TODO: use env var instead of hardcoded link, also https
+            <img className='slide-image' src={`${import.meta.env.VITE_API_BASE_URL}/screen/slides/images/${slides[currentIndex].id}.${slides[currentIndex].filetype}`} alt={slides[currentIndex].caption} />
+          )}
         </div>
         <button onClick={goToPreviousSlide}>Previous</button>
         <button onClick={goToNextSlide}>Next</button>
@@ -0,0 +1,21 @@
+/**
+ * Returned by `/device/status`. A kiosk is pending until an admin approves its pairing code.
+ */
+type DeviceStatus =
+    | { state: "pending", pairing_code: string, pairing_expires_at: string }
+    | { state: "paired", screen_id: string, name: string };
+
+/**
+ * Where the screen fetches its slides and settings from, and how it authenticates
+ */
+interface ScreenApi {
+    base: string;         // e.g. `${VITE_API_BASE_URL}/screens/<id>`
+    headers: HeadersInit; // The device token, which every screen request needs
+    device: { token: string, screen_id: string };
+}
+
+// The device token is only handed out once, so it's kept in localStorage across reloads
+const DEVICE_TOKEN_KEY = 'konsol_device_token';
+
+export type { DeviceStatus, ScreenApi };
+export { DEVICE_TOKEN_KEY };
@@ -0,0 +1,13 @@
+// An occurrence of an event from the calendar feeds configured in the backend
+interface CalendarEvent {
+    uid: string;
+    feed_id: string;
+    title: string;
+    description: string | null;
+    location: string | null;
+    start: string;
+    end: string;
+    all_day: boolean;   // All-day events start and end at local midnight
+}
+
+export type { CalendarEvent };
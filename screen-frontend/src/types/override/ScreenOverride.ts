@@ -0,0 +1,11 @@
+/**
+ * An emergency message and/or slide that replaces everything else on the screen until it expires
+ */
+interface ScreenOverride {
+    id: string;
+    message: string | null;
+    slide_id: string | null;
+    expires_at: string; // ISO 8601, UTC
+}
+
+export type { ScreenOverride };
@@ -4,6 +4,13 @@ import { LayoutType, ColorMode } from "./settings-types";
 class Settings {
     layout_type: LayoutType;
     color_mode: ColorMode;
+    sl_site_ids?: number[]; // Only set for screens configured in the backend
+    quiet?: boolean; // During quiet hours only a clock is shown
+    next_change?: string | null; // When a settings schedule or quiet hours change the settings next
+    // Settings from the backend's settings registry, missing when the backend is older
+    slide_interval_seconds?: number;
+    sl_refresh_seconds?: number;
+    show_clock?: boolean;
 
     constructor(layout_type: LayoutType, color_mode: ColorMode) {
         this.layout_type = layout_type;
@@ -14,6 +21,12 @@ class Settings {
 const settings_schema = z.object({
     layout_type: z.enum(LayoutType),
     color_mode: z.enum(ColorMode),
+    sl_site_ids: z.optional(z.array(z.number())),
+    quiet: z.optional(z.boolean()),
+    next_change: z.optional(z.nullable(z.string())),
+    slide_interval_seconds: z.optional(z.number()),
+    sl_refresh_seconds: z.optional(z.number()),
+    show_clock: z.optional(z.boolean()),
 });
 
 function parse_settings(json: object): Settings {
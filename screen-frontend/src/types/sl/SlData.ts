@@ -1,46 +1,58 @@
-import {parse_departures, SlDeparture, SlTrackedSite} from "./SlDeparture.ts";
+import {parse_departures, SlDeparture} from "./SlDeparture.ts";
+import {parse_deviations, SlDeviation} from "./SlDeviation.ts";
 
+/**
+ * Departures and deviations from the SL sites tracked in the backend. The backend fetches them from SL,
+ * caches them and filters out lines that aren't tracked.
+ */
 class SlData {
-    tracked_sites: SlTrackedSite[];
+    base: string;
+    headers: HeadersInit;
     departures: SlDeparture[];
+    deviations: SlDeviation[];
     last_update: Date | null; // null if no update has taken place yet
 
-    constructor(tracked_sites: SlTrackedSite[]) {
-        this.tracked_sites = tracked_sites;
+    constructor(base: string, headers: HeadersInit) {
+        this.base = base;
+        this.headers = headers;
         this.departures = [];
+        this.deviations = [];
         this.last_update = null;
     }
 
     async update() {
-        const departures: SlDeparture[] = [];
+        await Promise.all([this.update_departures(), this.update_deviations()]);
+    }
 
-        for (const tracked_site of this.tracked_sites) {
-            const url = `https://transport.integration.sl.se/v1/sites/${tracked_site.site_id}/departures`;
-            const response = await fetch(url);
+    async update_deviations() {
+        try {
+            const response = await fetch(`${this.base}/deviations`, { headers: this.headers });
             if (!response.ok) {
-                console.error(`Failed to fetch data from site with id ${tracked_site.site_id}`);
-                return;
-            }
-            const json: object = await response.json();
-            let unfiltered_departures;
-            try {
-                unfiltered_departures = parse_departures(json, tracked_site.site_id);
-            } catch (e) {
-                console.error(e);
+                console.error(`Failed to fetch deviations: ${response.status}`);
                 return;
             }
-            const filtered_departures = unfiltered_departures.filter(departure =>
-                tracked_site.tracked_lines === undefined ||
-                tracked_site.tracked_lines.some(tracked_line =>
-                    tracked_line.transport_mode === departure.transport_mode &&
-                    tracked_line.line_id === departure.line_id &&
-                    (tracked_line.direction_code === undefined || tracked_line.direction_code == departure.direction_code)
-                )
-            );
-            departures.push(...filtered_departures);
+            this.deviations = parse_deviations(await response.json());
+        } catch (e) {
+            console.error(e);
         }
+    }
 
-        this.departures = departures;
+    async update_departures() {
+        const response = await fetch(`${this.base}/departures`, { headers: this.headers });
+        if (!response.ok) {
+            console.error(`Failed to fetch departures: ${response.status}`);
+            return;
+        }
+        const json: object = await response.json();
+        try {
+            this.departures = parse_departures(json);
+        } catch (e) {
+            console.error(e);
+            return;
+        }
+        if ("failed_site_ids" in json && Array.isArray(json.failed_site_ids) && json.failed_site_ids.length > 0) {
+            console.error(`Failed to fetch departures from sites ${json.failed_site_ids.join(", ")}`);
+        }
         this.last_update = new Date();
     }
 }
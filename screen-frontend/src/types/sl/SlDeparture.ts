@@ -25,65 +25,47 @@ interface SlDeparture {                     // Example:
     display_time: string                    // "Nu"
 }
 
+// Departures are fetched through the backend, which has already picked out the fields we need
 const departure_schema = z.object({
-    destination: z.string(),
-    via: z.optional(z.string()),
+    site_id: z.number(),
+    stop_point_id: z.number(),
+    stop_point_name: z.string(),
+    stop_point_designation: z.string(),
+    transport_mode: z.nativeEnum(SlTransportMode),
+    line_group: z.nullable(z.nativeEnum(SlLineGroup)),
+    line_id: z.number(),
+    line_designation: z.string(),
     direction_code: z.number(),
     direction: z.string(),
+    destination: z.string(),
+    via: z.nullable(z.string()),
+    journey_id: z.number(),
+    scheduled_time: z.string().datetime({ local: true }),
+    expected_time: z.string().datetime({ local: true }),
     state: z.nativeEnum(SlDepartureState),
-    display: z.string(),
-    scheduled: z.string().datetime({ local: true }),
-    expected: z.string().datetime({ local: true }),
-    journey: z.object({
-        id: z.number()
-    }),
-    stop_point: z.object({
-        id: z.number(),
-        name: z.string(),
-        designation: z.optional(z.string()),
-    }),
-    line: z.object({
-        id: z.number(),
-        designation: z.string(),
-        transport_mode: z.nativeEnum(SlTransportMode),
-        group_of_lines: z.optional(z.nativeEnum(SlLineGroup)),
-    })
+    display_time: z.string(),
 });
 
 /**
- * Parses a JSON object fetched via the SL API into an {@link SlDeparture} object
+ * Parses a departure from the backend's `/departures` endpoint into an {@link SlDeparture} object
  */
-function parse_departure(json: object, site_id: number): SlDeparture {
+function parse_departure(json: object): SlDeparture {
     const parse_result = departure_schema.safeParse(json);
     if (parse_result.success) {
         const data = parse_result.data;
-        let line_group = data.line.group_of_lines;
-        if (line_group == undefined) {
-            if (data.line.transport_mode == SlTransportMode.Bus)
+        let line_group = data.line_group;
+        if (line_group === null) {
+            if (data.transport_mode == SlTransportMode.Bus)
                 line_group = SlLineGroup.Bus;
             else
                 throw new Error("Expected line group");
         }
         return {
-            site_id,
-            stop_point_id: data.stop_point.id,
-            transport_mode: data.line.transport_mode,
+            ...data,
             line_group,
-            line_id: data.line.id,
-            direction_code: data.direction_code,
-            journey_id: data.journey.id,
-
-            stop_point_name: data.stop_point.name,
-            stop_point_designation: data.stop_point.designation || "",
-            destination: data.destination,
-            via: data.via,
-            direction: data.direction,
-            line_designation: data.line.designation,
-
-            scheduled_time: new Date(data.scheduled),
-            expected_time: new Date(data.expected),
-            state: data.state,
-            display_time: data.display
+            via: data.via ?? undefined,
+            scheduled_time: new Date(data.scheduled_time),
+            expected_time: new Date(data.expected_time),
         };
     } else {
         console.error(json);
@@ -91,12 +73,10 @@ function parse_departure(json: object, site_id: number): SlDeparture {
     }
 }
 
-function parse_departures(json: object, site_id: number): SlDeparture[] {
+function parse_departures(json: object): SlDeparture[] {
     if (!("departures" in json) || !Array.isArray(json.departures))
         throw new Error("Expected JSON property 'departures'");
-    return json.departures.map((d: object) => {
-        return parse_departure(d, site_id);
-    });
+    return json.departures.map((d: object) => parse_departure(d));
 }
 
 export type {SlTrackedLine, SlTrackedSite, SlDepartureState, SlDeparture};
@@ -0,0 +1,50 @@
+import { z } from "zod";
+
+import {SlTransportMode} from "./sl-types.ts";
+
+type SlDeviationSeverity = "high" | "medium" | "low";
+
+interface SlDeviation {                     // Example:
+    id: number,                             // 1001
+    header: string,                         // "Röda linjen ersätts av bussar"
+    details: string | undefined,            // "På grund av spårarbete"
+    severity: SlDeviationSeverity,          // "high"
+    valid_from: Date,
+    valid_until: Date | undefined,          // undefined if SL hasn't said when it ends
+    lines: { id: number, transport_mode: SlTransportMode, designation: string }[],
+}
+
+// Deviations are fetched through the backend, which has already removed muted and expired ones
+const deviation_schema = z.object({
+    id: z.number(),
+    header: z.string(),
+    details: z.nullable(z.string()),
+    severity: z.enum(["high", "medium", "low"]),
+    valid_from: z.string().datetime(),
+    valid_until: z.nullable(z.string().datetime()),
+    lines: z.array(z.object({
+        id: z.number(),
+        transport_mode: z.nativeEnum(SlTransportMode),
+        designation: z.string(),
+    })),
+});
+
+/**
+ * Parses the response of the backend's `/deviations` endpoint into {@link SlDeviation} objects
+ */
+function parse_deviations(json: object): SlDeviation[] {
+    if (!("deviations" in json) || !Array.isArray(json.deviations))
+        throw new Error("Expected JSON property 'deviations'");
+    return json.deviations.map((d: object) => {
+        const data = deviation_schema.parse(d);
+        return {
+            ...data,
+            details: data.details ?? undefined,
+            valid_from: new Date(data.valid_from),
+            valid_until: data.valid_until ? new Date(data.valid_until) : undefined,
+        };
+    });
+}
+
+export type {SlDeviation, SlDeviationSeverity};
+export {parse_deviations}
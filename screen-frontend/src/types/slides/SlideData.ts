@@ -1,10 +1,13 @@
+import { CalendarEvent } from '../events/CalendarEvent.ts';
+
 interface SlideData {
     id: string;
     caption: string;
     start_date: Date;
     end_date: Date;
     active: boolean;
-    filetype: string;
+    filetype: string;       // Empty for event slides without a poster
+    event?: CalendarEvent;  // Set for slides generated from calendar events
 }
 
 export type { SlideData };
@@ -0,0 +1,77 @@
+import { DEVICE_TOKEN_KEY } from "../types/device/Device.ts";
+
+/**
+ * Runs commands that admins queue for this screen (reload, jump to slide, identify, clear cache)
+ * and acknowledges them to the backend.
+ */
+
+type Command =
+    | { id: string, command: "reload" }
+    | { id: string, command: "jump_to_slide", slide_id: string }
+    | { id: string, command: "identify" }
+    | { id: string, command: "clear_cache" };
+
+// Listened to by the slideshow and ScreenRoot, since they own the state these commands change
+const JUMP_TO_SLIDE_EVENT = 'konsol:jump_to_slide';
+const IDENTIFY_EVENT = 'konsol:identify';
+
+function acknowledge(token: string, command: Command, success: boolean, message: string | null = null): Promise<Response> {
+    return fetch(`${import.meta.env.VITE_API_BASE_URL}/device/commands/${command.id}/ack`, {
+        method: 'POST',
+        headers: { 'Authorization': `Bearer ${token}`, 'Content-Type': 'application/json' },
+        body: JSON.stringify({ success, message }),
+    });
+}
+
+async function clearCache() {
+    if ('caches' in window) {
+        for (const key of await caches.keys()) {
+            await caches.delete(key);
+        }
+    }
+    // Everything except the device token, or the screen would have to be paired again
+    const token = localStorage.getItem(DEVICE_TOKEN_KEY);
+    localStorage.clear();
+    if (token !== null) localStorage.setItem(DEVICE_TOKEN_KEY, token);
+}
+
+async function run(token: string, command: Command) {
+    switch (command.command) {
+        case 'reload':
+            // Acknowledge first, the page is gone after reloading
+            await acknowledge(token, command, true);
+            window.location.reload();
+            return;
+        case 'clear_cache':
+            await clearCache();
+            await acknowledge(token, command, true);
+            window.location.reload();
+            return;
+        case 'jump_to_slide':
+            window.dispatchEvent(new CustomEvent(JUMP_TO_SLIDE_EVENT, { detail: command.slide_id }));
+            await acknowledge(token, command, true);
+            return;
+        case 'identify':
+            window.dispatchEvent(new CustomEvent(IDENTIFY_EVENT));
+            await acknowledge(token, command, true);
+            return;
+    }
+}
+
+/** Fetch the pending commands for this screen and run them in order */
+async function pollCommands(token: string) {
+    try {
+        const response = await fetch(`${import.meta.env.VITE_API_BASE_URL}/device/commands`, {
+            headers: { 'Authorization': `Bearer ${token}` },
+        });
+        if (!response.ok) return;
+        const commands: Command[] = await response.json();
+        for (const command of commands) {
+            await run(token, command).catch(error => acknowledge(token, command, false, String(error)));
+        }
+    } catch (error) {
+        console.error('Error fetching commands:', error);
+    }
+}
+
+export { pollCommands, JUMP_TO_SLIDE_EVENT, IDENTIFY_EVENT };
@@ -0,0 +1,65 @@
+/**
+ * Reads a Server-Sent Events stream like EventSource, but with headers, since EventSource can't send
+ * the device token. Reconnects by itself when the connection drops.
+ */
+
+const RECONNECT_DELAY = 5 * 1000;
+
+type EventHandlers = { [event: string]: (data: string) => void };
+
+/**
+ * Calls the handler for each event's name with its data, and `onOpen` every time the stream (re)connects.
+ * Returns a function that closes the stream.
+ */
+function openEventStream(url: string, headers: HeadersInit, handlers: EventHandlers, onOpen: () => void): () => void {
+    const controller = new AbortController();
+    let closed = false;
+
+    const dispatch = (message: string) => {
+        let event = 'message';
+        const data: string[] = [];
+        for (const line of message.split('\n')) {
+            if (line.startsWith('event:')) event = line.slice('event:'.length).trim();
+            else if (line.startsWith('data:')) data.push(line.slice('data:'.length).trimStart());
+        }
+        handlers[event]?.(data.join('\n'));
+    };
+
+    const connect = async () => {
+        while (!closed) {
+            try {
+                const response = await fetch(url, { headers, signal: controller.signal });
+                if (!response.ok || response.body === null) {
+                    throw new Error(`Event stream responded with ${response.status}`);
+                }
+                onOpen();
+
+                const reader = response.body.pipeThrough(new TextDecoderStream()).getReader();
+                let buffer = '';
+                for (;;) {
+                    const { value, done } = await reader.read();
+                    if (done) break;
+                    buffer += value;
+                    // Events end with an empty line
+                    let end;
+                    while ((end = buffer.indexOf('\n\n')) !== -1) {
+                        dispatch(buffer.slice(0, end));
+                        buffer = buffer.slice(end + 2);
+                    }
+                }
+            } catch (error) {
+                if (closed) return;
+                console.error('Lost connection to the event stream, reconnecting', error);
+            }
+            await new Promise(resolve => setTimeout(resolve, RECONNECT_DELAY));
+        }
+    };
+    connect();
+
+    return () => {
+        closed = true;
+        controller.abort();
+    };
+}
+
+export { openEventStream };
@@ -0,0 +1,56 @@
+/**
+ * Collects what the screen is doing and sends it to the backend as heartbeats,
+ * so admins can see when a screen has frozen or lost its connection.
+ */
+
+const HEARTBEAT_INTERVAL = 30 * 1000;
+
+let current_slide_id: string | null = null;
+let manifest_version: string | null = null;
+let client_errors: string[] = [];
+
+/** Called by the slideshow whenever it shows a new slide */
+function reportCurrentSlide(slide_id: string | null) {
+    current_slide_id = slide_id;
+}
+
+/** Called whenever new content is loaded. We use the time it was loaded as its version. */
+function reportContentLoaded() {
+    manifest_version = new Date().toISOString();
+}
+
+function reportError(message: string) {
+    client_errors.push(message);
+}
+
+window.addEventListener('error', event => reportError(event.message));
+window.addEventListener('unhandledrejection', event => reportError(String(event.reason)));
+
+/**
+ * Sends a heartbeat every 30 seconds using the device token. Returns a function that stops it.
+ */
+function startHeartbeat(token: string): () => void {
+    const send = () => {
+        const errors = client_errors;
+        client_errors = [];
+        fetch(`${import.meta.env.VITE_API_BASE_URL}/device/heartbeat`, {
+            method: 'POST',
+            headers: { 'Authorization': `Bearer ${token}`, 'Content-Type': 'application/json' },
+            body: JSON.stringify({
+                current_slide_id,
+                manifest_version,
+                uptime_seconds: Math.floor(performance.now() / 1000),
+                client_errors: errors,
+            }),
+        }).catch(error => {
+            // Try again with the next heartbeat
+            client_errors.unshift(...errors);
+            console.error('Error sending heartbeat:', error);
+        });
+    };
+    send();
+    const handle = setInterval(send, HEARTBEAT_INTERVAL);
+    return () => clearInterval(handle);
+}
+
+export { reportCurrentSlide, reportContentLoaded, reportError, startHeartbeat };
@@ -0,0 +1,57 @@
+/**
+ * Records which slides have been shown and for how long, and sends them to the backend
+ * in batches so admins can see how often each slide is displayed.
+ */
+
+const FLUSH_INTERVAL = 60 * 1000;
+// Keep at most this many plays around if the backend can't be reached
+const MAX_QUEUED_PLAYS = 1000;
+
+type Play = {
+    slide_id: string;
+    started_at: string;
+    duration_ms: number;
+};
+
+let queue: Play[] = [];
+let reporting = false;
+
+/** Called by the slideshow when a slide is no longer shown */
+function reportPlay(slide_id: string, started_at: Date) {
+    // Only paired screens can report plays
+    if (!reporting) return;
+    queue.push({ slide_id, started_at: started_at.toISOString(), duration_ms: Date.now() - started_at.getTime() });
+    if (queue.length > MAX_QUEUED_PLAYS) {
+        queue = queue.slice(queue.length - MAX_QUEUED_PLAYS);
+    }
+}
+
+/**
+ * Sends the recorded plays every minute using the device token. Returns a function that stops it.
+ */
+function startPlayReporting(token: string): () => void {
+    reporting = true;
+    const flush = () => {
+        if (queue.length === 0) return;
+        const plays = queue;
+        queue = [];
+        fetch(`${import.meta.env.VITE_API_BASE_URL}/device/plays`, {
+            method: 'POST',
+            headers: { 'Authorization': `Bearer ${token}`, 'Content-Type': 'application/json' },
+            body: JSON.stringify(plays),
+        }).then(response => {
+            if (!response.ok) throw new Error(`HTTP ${response.status}`);
+        }).catch(error => {
+            // Try again with the next flush
+            queue = [...plays, ...queue].slice(-MAX_QUEUED_PLAYS);
+            console.error('Error reporting plays:', error);
+        });
+    };
+    const handle = setInterval(flush, FLUSH_INTERVAL);
+    return () => {
+        clearInterval(handle);
+        reporting = false;
+    };
+}
+
+export { reportPlay, startPlayReporting };